@@ -1,15 +1,18 @@
-use linux_embedded_hal::I2cdev;
-use lsm303agr::{AccelOutputDataRate, Lsm303agr};
+use linux_embedded_hal::{Delay, I2cdev};
+use lsm303agr::{AccelOutputDataRate, Lsm303agr, StreamEvent};
 
 fn main() {
     let dev = I2cdev::new("/dev/i2c-1").unwrap();
     let mut sensor = Lsm303agr::new_with_i2c(dev);
     sensor.init().unwrap();
     sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
-    loop {
-        if sensor.accel_status().unwrap().xyz_new_data {
-            let data = sensor.accel_data().unwrap();
-            println!("Acceleration: x {} y {} z {}", data.x, data.y, data.z);
+    let mut delay = Delay;
+    for event in sensor.accel_stream(&mut delay).unwrap() {
+        match event.unwrap() {
+            StreamEvent::Sample { data, .. } => {
+                println!("Acceleration: x {} y {} z {}", data.x, data.y, data.z)
+            }
+            StreamEvent::Dropped { .. } => println!("Samples dropped"),
         }
     }
 }
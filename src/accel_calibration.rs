@@ -0,0 +1,259 @@
+//! Accelerometer six-position offset and gain calibration.
+//!
+//! The device is placed in six orientations, with each axis pointing up and
+//! down in turn. In each orientation the axis pointing up or down measures
+//! +1g or -1g, from which its offset and gain can be computed.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    Error, Lsm303agr, Measurement,
+};
+use embedded_hal::blocking::delay::DelayMs;
+
+/// Readings further away than this from ±1g (in mg) on the axis pointing
+/// up/down are rejected.
+const POSITION_TOLERANCE_MG: i32 = 300;
+/// Output data rate periods to wait for new data before giving up
+const DATA_READY_PERIODS: u32 = 10;
+
+/// Accelerometer calibration
+///
+/// Corrected samples are computed as `(sample - offset) * scale`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccelCalibration {
+    /// Zero-g offset per axis in mg (milli-g)
+    pub offset: [f32; 3],
+    /// Gain correction per axis
+    pub scale: [f32; 3],
+}
+
+impl Default for AccelCalibration {
+    fn default() -> Self {
+        AccelCalibration {
+            offset: [0.0; 3],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+impl AccelCalibration {
+    /// Apply the calibration to an accelerometer measurement in mg
+    pub fn apply(&self, measurement: Measurement) -> Measurement {
+        let correct = |v: i32, i: usize| libm::roundf((v as f32 - self.offset[i]) * self.scale[i]);
+        Measurement {
+            x: correct(measurement.x, 0) as i32,
+            y: correct(measurement.y, 1) as i32,
+            z: correct(measurement.z, 2) as i32,
+        }
+    }
+}
+
+/// Accelerometer calibration position
+///
+/// The position is named after the axis pointing up, away from the earth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccelCalibrationPosition {
+    /// X axis pointing up (+1g on X)
+    XUp,
+    /// X axis pointing down (-1g on X)
+    XDown,
+    /// Y axis pointing up (+1g on Y)
+    YUp,
+    /// Y axis pointing down (-1g on Y)
+    YDown,
+    /// Z axis pointing up (+1g on Z)
+    ZUp,
+    /// Z axis pointing down (-1g on Z)
+    ZDown,
+}
+
+impl AccelCalibrationPosition {
+    const ALL: [AccelCalibrationPosition; 6] = [
+        AccelCalibrationPosition::XUp,
+        AccelCalibrationPosition::XDown,
+        AccelCalibrationPosition::YUp,
+        AccelCalibrationPosition::YDown,
+        AccelCalibrationPosition::ZUp,
+        AccelCalibrationPosition::ZDown,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn axis(self) -> usize {
+        self.index() / 2
+    }
+
+    fn expected_mg(self) -> i32 {
+        match self {
+            AccelCalibrationPosition::XUp
+            | AccelCalibrationPosition::YUp
+            | AccelCalibrationPosition::ZUp => 1000,
+            _ => -1000,
+        }
+    }
+
+    /// Detect the position the device is in from an accelerometer
+    /// measurement in mg
+    pub fn detect(measurement: Measurement) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .cloned()
+            .find(|position| position.matches(measurement))
+    }
+
+    fn matches(self, measurement: Measurement) -> bool {
+        let values = [measurement.x, measurement.y, measurement.z];
+        let axis = self.axis();
+        (values[axis] - self.expected_mg()).abs() <= POSITION_TOLERANCE_MG
+            && values
+                .iter()
+                .enumerate()
+                .all(|(i, v)| i == axis || v.abs() < POSITION_TOLERANCE_MG)
+    }
+}
+
+/// Accelerometer calibration errors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccelCalibrationError {
+    /// The measurement does not correspond to the requested position
+    WrongPosition,
+    /// Some positions have no samples
+    MissingPositions,
+}
+
+/// Accelerometer six-position calibration accumulator
+///
+/// Collect samples in all [positions](AccelCalibrationPosition) with
+/// [`calibrate_accel_position()`](Lsm303agr::calibrate_accel_position) or
+/// [`add_sample()`](AccelCalibrator::add_sample), then call
+/// [`solve()`](AccelCalibrator::solve).
+#[derive(Debug, Default, Clone)]
+pub struct AccelCalibrator {
+    sums: [[i64; 3]; 6],
+    counts: [u32; 6],
+}
+
+impl AccelCalibrator {
+    /// Create a new empty calibrator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an uncalibrated accelerometer measurement in mg taken in the
+    /// given position
+    ///
+    /// Returns `AccelCalibrationError::WrongPosition` if the measurement does
+    /// not correspond to the position.
+    pub fn add_sample(
+        &mut self,
+        position: AccelCalibrationPosition,
+        measurement: Measurement,
+    ) -> Result<(), AccelCalibrationError> {
+        if !position.matches(measurement) {
+            return Err(AccelCalibrationError::WrongPosition);
+        }
+        let sums = &mut self.sums[position.index()];
+        sums[0] += i64::from(measurement.x);
+        sums[1] += i64::from(measurement.y);
+        sums[2] += i64::from(measurement.z);
+        self.counts[position.index()] += 1;
+        Ok(())
+    }
+
+    /// Number of samples collected in a position
+    pub fn samples(&self, position: AccelCalibrationPosition) -> u32 {
+        self.counts[position.index()]
+    }
+
+    /// Next position without samples, if any
+    pub fn next_position(&self) -> Option<AccelCalibrationPosition> {
+        AccelCalibrationPosition::ALL
+            .iter()
+            .cloned()
+            .find(|position| self.samples(*position) == 0)
+    }
+
+    /// Whether samples have been collected in all positions
+    pub fn is_complete(&self) -> bool {
+        self.next_position().is_none()
+    }
+
+    /// Solve the calibration from the collected samples
+    pub fn solve(&self) -> Result<AccelCalibration, AccelCalibrationError> {
+        if !self.is_complete() {
+            return Err(AccelCalibrationError::MissingPositions);
+        }
+        let mut calibration = AccelCalibration::default();
+        for axis in 0..3 {
+            let up = self.average(2 * axis, axis);
+            let down = self.average(2 * axis + 1, axis);
+            calibration.offset[axis] = (up + down) / 2.0;
+            calibration.scale[axis] = 2000.0 / (up - down);
+        }
+        Ok(calibration)
+    }
+
+    fn average(&self, position: usize, axis: usize) -> f32 {
+        self.sums[position][axis] as f32 / self.counts[position] as f32
+    }
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Set the accelerometer calibration applied by
+    /// [`accel_data()`](Lsm303agr::accel_data)
+    ///
+    /// Use `None` to disable the calibration.
+    pub fn set_accel_calibration(&mut self, calibration: Option<AccelCalibration>) {
+        self.accel_calibration = calibration;
+    }
+
+    /// Get the accelerometer calibration applied by
+    /// [`accel_data()`](Lsm303agr::accel_data)
+    pub fn accel_calibration(&self) -> Option<AccelCalibration> {
+        self.accel_calibration
+    }
+
+    /// Collect `samples` accelerometer measurements in the given position
+    ///
+    /// The measurements are taken without the current accelerometer
+    /// calibration. The `delay` is used to wait between data-ready polls.
+    /// The calibrator is only updated if all samples could be collected.
+    ///
+    /// Returns `Error::NotReady` if the accelerometer is powered down,
+    /// `Error::Timeout` if it stops providing new data and
+    /// `Error::InvalidInputData` if the device is not in the expected
+    /// position.
+    pub fn calibrate_accel_position<D: DelayMs<u8>>(
+        &mut self,
+        calibrator: &mut AccelCalibrator,
+        position: AccelCalibrationPosition,
+        samples: u16,
+        delay: &mut D,
+    ) -> Result<(), Error<CommE, PinE>> {
+        let odr = self.accel_odr.ok_or(Error::NotReady)?;
+        let max_polls = DATA_READY_PERIODS * 1000 / u32::from(odr.frequency_hz());
+        let mut collecting = calibrator.clone();
+        for _ in 0..samples {
+            let mut polls = 0;
+            while !self.accel_status()?.xyz_new_data {
+                polls += 1;
+                if polls > max_polls {
+                    return Err(Error::Timeout);
+                }
+                delay.delay_ms(1);
+            }
+            let measurement = self.accel_data_uncalibrated()?;
+            collecting
+                .add_sample(position, measurement)
+                .map_err(|_| Error::InvalidInputData)?;
+        }
+        *calibrator = collecting;
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     interface::{ReadData, WriteData},
-    AccelMode, AccelOutputDataRate, AccelScale, BitFlags as BF, Error, Lsm303agr, Register,
+    registers::{CtrlReg1A, CtrlReg4A},
+    AccelMode, AccelOutputDataRate, AccelScale, Error, Lsm303agr, Register,
 };
 
 impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
@@ -13,20 +14,21 @@ where
     /// When changing from a low-power-only output data rate setting into
     /// a high-resolution or normal power mode, it changes into normal mode.
     pub fn set_accel_odr(&mut self, odr: AccelOutputDataRate) -> Result<(), Error<CommE, PinE>> {
-        let (mask, lp_only, lp_compat) = match odr {
-            AccelOutputDataRate::Hz1 => (1 << 4, false, true),
-            AccelOutputDataRate::Hz10 => (2 << 4, false, true),
-            AccelOutputDataRate::Hz25 => (3 << 4, false, true),
-            AccelOutputDataRate::Hz50 => (4 << 4, false, true),
-            AccelOutputDataRate::Hz100 => (5 << 4, false, true),
-            AccelOutputDataRate::Hz200 => (6 << 4, false, true),
-            AccelOutputDataRate::Hz400 => (7 << 4, false, true),
-            AccelOutputDataRate::Khz1_620LowPower => (8 << 4, true, true),
-            AccelOutputDataRate::Khz1_344 => (9 << 4, false, false),
-            AccelOutputDataRate::Khz5_376LowPower => (9 << 4, true, true),
+        let (lp_only, lp_compat) = match odr {
+            AccelOutputDataRate::Hz1
+            | AccelOutputDataRate::Hz10
+            | AccelOutputDataRate::Hz25
+            | AccelOutputDataRate::Hz50
+            | AccelOutputDataRate::Hz100
+            | AccelOutputDataRate::Hz200
+            | AccelOutputDataRate::Hz400 => (false, true),
+            AccelOutputDataRate::Khz1_620LowPower | AccelOutputDataRate::Khz5_376LowPower => {
+                (true, true)
+            }
+            AccelOutputDataRate::Khz1_344 => (false, false),
         };
-        let lp_enabled = self.ctrl_reg1_a.is_high(BF::LP_EN);
-        let hr_enabled = self.ctrl_reg4_a.is_high(BF::HR);
+        let lp_enabled = CtrlReg1A::from(self.ctrl_reg1_a.bits).lp_en;
+        let hr_enabled = CtrlReg4A::from(self.ctrl_reg4_a.bits).hr;
         let mut should_lp_be_enabled = lp_enabled;
         if lp_enabled {
             if !lp_compat {
@@ -43,19 +45,29 @@ where
                 should_lp_be_enabled = true;
             }
         }
-        let lp_flag = if should_lp_be_enabled { BF::LP_EN } else { 0 };
-        let reg1 = (self.ctrl_reg1_a.bits & !(BF::LP_EN | (0x7 << 4))) | mask | lp_flag;
-        self.iface
-            .write_accel_register(Register::CTRL_REG1_A, reg1)?;
+        let reg1 = CtrlReg1A {
+            odr: accel_odr_bits(odr),
+            lp_en: should_lp_be_enabled,
+            ..self.ctrl_reg1_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG1_A, reg1)?;
         self.ctrl_reg1_a = reg1.into();
         self.accel_odr = Some(odr);
         Ok(())
     }
 
+    /// Get the accelerometer output data rate
+    ///
+    /// Returns `None` if the accelerometer is powered down.
+    pub fn get_accel_odr(&self) -> Option<AccelOutputDataRate> {
+        self.accel_odr
+    }
+
     /// Set accelerometer power/resolution mode
     ///
-    /// Returns `Error::InvalidInputData` if the mode is incompatible with the current
-    /// accelerometer output data rate.
+    /// Returns `Error::IncompatibleOdrMode` if the mode is incompatible with
+    /// the current accelerometer output data rate.
     pub fn set_accel_mode(&mut self, mode: AccelMode) -> Result<(), Error<CommE, PinE>> {
         check_accel_odr_is_compatible_with_mode(self.accel_odr, mode)?;
 
@@ -73,9 +85,12 @@ where
                 self.enable_lp()?;
             }
             AccelMode::PowerDown => {
-                let reg1 = self.ctrl_reg1_a.bits & !(0xf << 4);
-                self.iface
-                    .write_accel_register(Register::CTRL_REG1_A, reg1)?;
+                let reg1 = CtrlReg1A {
+                    odr: 0,
+                    ..self.ctrl_reg1_a.bits.into()
+                }
+                .bits();
+                self.write_accel_register(Register::CTRL_REG1_A, reg1)?;
                 self.ctrl_reg1_a = reg1.into();
                 self.accel_odr = None;
             }
@@ -84,16 +99,15 @@ where
     }
 
     /// Get the accelerometer mode
-    pub fn get_accel_mode(&mut self) -> AccelMode {
-        let power_down = (self.ctrl_reg1_a.bits >> 4 & 0xf) == 0;
-        let lp_enabled = self.ctrl_reg1_a.is_high(BF::LP_EN);
-        let hr_enabled = self.ctrl_reg4_a.is_high(BF::HR);
+    pub fn get_accel_mode(&self) -> AccelMode {
+        let reg1 = CtrlReg1A::from(self.ctrl_reg1_a.bits);
+        let hr_enabled = CtrlReg4A::from(self.ctrl_reg4_a.bits).hr;
 
-        if power_down {
+        if reg1.odr == 0 {
             AccelMode::PowerDown
         } else if hr_enabled {
             AccelMode::HighResolution
-        } else if lp_enabled {
+        } else if reg1.lp_en {
             AccelMode::LowPower
         } else {
             AccelMode::Normal
@@ -106,76 +120,105 @@ where
     /// `AccelScale::G2` for example can return values between -2g and +2g
     /// where g is the gravity of the earth (~9.82 m/s²).
     pub fn set_accel_scale(&mut self, scale: AccelScale) -> Result<(), Error<CommE, PinE>> {
-        let fs = match scale {
-            AccelScale::G2 => 0b00,
-            AccelScale::G4 => 0b01,
-            AccelScale::G8 => 0b10,
-            AccelScale::G16 => 0b11,
-        };
-        let reg4 = self.ctrl_reg4_a.bits & !(0b11 << 4) | (fs << 4);
-        self.iface
-            .write_accel_register(Register::CTRL_REG4_A, reg4)?;
+        let reg4 = CtrlReg4A {
+            fs: accel_scale_bits(scale),
+            ..self.ctrl_reg4_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG4_A, reg4)?;
         self.ctrl_reg4_a = reg4.into();
         Ok(())
     }
 
     /// Get accelerometer scaling factor
     pub fn get_accel_scale(&self) -> AccelScale {
-        let fs = (self.ctrl_reg4_a.bits & (0b11 << 4)) >> 4;
-        match fs {
+        match CtrlReg4A::from(self.ctrl_reg4_a.bits).fs {
             0b00 => AccelScale::G2,
             0b01 => AccelScale::G4,
             0b10 => AccelScale::G8,
-            0b11 => AccelScale::G16,
-            _ => unreachable!("bit shift above means we cannot be here"),
+            _ => AccelScale::G16,
         }
     }
 
     fn enable_hr(&mut self) -> Result<(), Error<CommE, PinE>> {
-        let reg4 = self.ctrl_reg4_a.with_high(BF::HR);
-        self.iface
-            .write_accel_register(Register::CTRL_REG4_A, reg4.bits)?;
-        self.ctrl_reg4_a = reg4;
-        Ok(())
+        self.set_hr(true)
     }
 
     fn disable_hr(&mut self) -> Result<(), Error<CommE, PinE>> {
-        let reg4 = self.ctrl_reg4_a.with_low(BF::HR);
-        self.iface
-            .write_accel_register(Register::CTRL_REG4_A, reg4.bits)?;
-        self.ctrl_reg4_a = reg4;
+        self.set_hr(false)
+    }
+
+    fn set_hr(&mut self, hr: bool) -> Result<(), Error<CommE, PinE>> {
+        let reg4 = CtrlReg4A {
+            hr,
+            ..self.ctrl_reg4_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG4_A, reg4)?;
+        self.ctrl_reg4_a = reg4.into();
         Ok(())
     }
 
     fn enable_lp(&mut self) -> Result<(), Error<CommE, PinE>> {
-        let reg1 = self.ctrl_reg1_a.with_high(BF::LP_EN);
-        self.iface
-            .write_accel_register(Register::CTRL_REG1_A, reg1.bits)?;
-        self.ctrl_reg1_a = reg1;
-        Ok(())
+        self.set_lp(true)
     }
 
     fn disable_lp(&mut self) -> Result<(), Error<CommE, PinE>> {
-        let reg1 = self.ctrl_reg1_a.with_low(BF::LP_EN);
-        self.iface
-            .write_accel_register(Register::CTRL_REG1_A, reg1.bits)?;
-        self.ctrl_reg1_a = reg1;
+        self.set_lp(false)
+    }
+
+    fn set_lp(&mut self, lp_en: bool) -> Result<(), Error<CommE, PinE>> {
+        let reg1 = CtrlReg1A {
+            lp_en,
+            ..self.ctrl_reg1_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG1_A, reg1)?;
+        self.ctrl_reg1_a = reg1.into();
         Ok(())
     }
 }
 
-fn check_accel_odr_is_compatible_with_mode<CommE, PinE>(
+/// `ODR` field value of `CTRL_REG1_A`
+pub(crate) fn accel_odr_bits(odr: AccelOutputDataRate) -> u8 {
+    match odr {
+        AccelOutputDataRate::Hz1 => 1,
+        AccelOutputDataRate::Hz10 => 2,
+        AccelOutputDataRate::Hz25 => 3,
+        AccelOutputDataRate::Hz50 => 4,
+        AccelOutputDataRate::Hz100 => 5,
+        AccelOutputDataRate::Hz200 => 6,
+        AccelOutputDataRate::Hz400 => 7,
+        AccelOutputDataRate::Khz1_620LowPower => 8,
+        AccelOutputDataRate::Khz1_344 | AccelOutputDataRate::Khz5_376LowPower => 9,
+    }
+}
+
+/// `FS` field value of `CTRL_REG4_A`
+pub(crate) fn accel_scale_bits(scale: AccelScale) -> u8 {
+    match scale {
+        AccelScale::G2 => 0b00,
+        AccelScale::G4 => 0b01,
+        AccelScale::G8 => 0b10,
+        AccelScale::G16 => 0b11,
+    }
+}
+
+pub(crate) fn check_accel_odr_is_compatible_with_mode<CommE, PinE>(
     odr: Option<AccelOutputDataRate>,
     mode: AccelMode,
 ) -> Result<(), Error<CommE, PinE>> {
-    if (odr == Some(AccelOutputDataRate::Khz1_620LowPower)
-        || odr == Some(AccelOutputDataRate::Khz5_376LowPower))
-        && (mode == AccelMode::Normal || mode == AccelMode::HighResolution)
-        || (odr == Some(AccelOutputDataRate::Khz1_344) && mode == AccelMode::LowPower)
-    {
-        Err(Error::InvalidInputData)
-    } else {
-        Ok(())
+    match odr {
+        Some(odr @ AccelOutputDataRate::Khz1_620LowPower)
+        | Some(odr @ AccelOutputDataRate::Khz5_376LowPower)
+            if mode == AccelMode::Normal || mode == AccelMode::HighResolution =>
+        {
+            Err(Error::IncompatibleOdrMode { odr, mode })
+        }
+        Some(odr @ AccelOutputDataRate::Khz1_344) if mode == AccelMode::LowPower => {
+            Err(Error::IncompatibleOdrMode { odr, mode })
+        }
+        _ => Ok(()),
     }
 }
 
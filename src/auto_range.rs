@@ -0,0 +1,199 @@
+//! Automatic accelerometer full-scale ranging.
+//!
+//! The auto-ranger watches the unscaled accelerometer data and switches to
+//! the next larger scale as soon as a sample gets close to the limit of the
+//! current scale. It only switches back to the next smaller scale once the
+//! samples have stayed well within the range of that smaller scale for a
+//! while, so that the scale does not toggle back and forth.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    AccelMode, AccelScale, Error, Lsm303agr, Measurement,
+};
+
+/// Auto-ranging configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoRangeConfig {
+    /// Switch to the next larger scale when a sample on any axis reaches this
+    /// percentage of the current full scale
+    pub up_percent: u8,
+    /// Switch to the next smaller scale when the samples on all axes stay
+    /// below this percentage of the smaller full scale. Must be smaller than
+    /// `up_percent`.
+    pub down_percent: u8,
+    /// Number of consecutive small samples required before switching to the
+    /// next smaller scale
+    pub hold_samples: u16,
+}
+
+impl Default for AutoRangeConfig {
+    fn default() -> Self {
+        AutoRangeConfig {
+            up_percent: 90,
+            down_percent: 50,
+            hold_samples: 32,
+        }
+    }
+}
+
+/// Accelerometer auto-ranger
+///
+/// Read accelerometer data through it with
+/// [`accel_data_auto_ranged()`](Lsm303agr::accel_data_auto_ranged).
+/// The scale can be limited by setting the initial scale with
+/// [`set_accel_scale()`](Lsm303agr::set_accel_scale) and the lowest scale
+/// with [`with_min_scale()`](AutoRanger::with_min_scale).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoRanger {
+    config: AutoRangeConfig,
+    min_scale: AccelScale,
+    small_samples: u16,
+    settling: bool,
+}
+
+/// Auto-ranged accelerometer measurement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoRangedMeasurement {
+    /// Measurement in mg with the accelerometer calibration applied
+    pub data: Measurement,
+    /// Scale at which the measurement was taken
+    pub scale: AccelScale,
+    /// At least one axis was at the limit of the scale, so the real
+    /// acceleration may have been larger. If this happens at
+    /// `AccelScale::G16` the acceleration exceeded the range of the sensor.
+    pub clipped: bool,
+}
+
+impl AutoRanger {
+    /// Create a new auto-ranger
+    pub fn new(config: AutoRangeConfig) -> Self {
+        AutoRanger {
+            config,
+            min_scale: AccelScale::G2,
+            small_samples: 0,
+            settling: false,
+        }
+    }
+
+    /// Never switch below the given scale
+    pub fn with_min_scale(mut self, scale: AccelScale) -> Self {
+        self.min_scale = scale;
+        self
+    }
+
+    /// Configuration
+    pub fn config(&self) -> AutoRangeConfig {
+        self.config
+    }
+
+    /// Scale to switch to after a sample whose largest absolute unscaled value
+    /// on any axis is `max_abs` out of `limit`
+    fn next_scale(&mut self, scale: AccelScale, max_abs: u32, limit: u32) -> Option<AccelScale> {
+        let up = match scale {
+            AccelScale::G2 => Some(AccelScale::G4),
+            AccelScale::G4 => Some(AccelScale::G8),
+            AccelScale::G8 => Some(AccelScale::G16),
+            AccelScale::G16 => None,
+        };
+        let down = match scale {
+            AccelScale::G2 => None,
+            AccelScale::G4 => Some(AccelScale::G2),
+            AccelScale::G8 => Some(AccelScale::G4),
+            AccelScale::G16 => Some(AccelScale::G8),
+        }
+        .filter(|down| scale_index(*down) >= scale_index(self.min_scale));
+
+        if max_abs * 100 >= limit * u32::from(self.config.up_percent) {
+            self.small_samples = 0;
+            return up;
+        }
+        // The smaller scale has half the range.
+        if down.is_some() && max_abs * 200 < limit * u32::from(self.config.down_percent) {
+            self.small_samples = self.small_samples.saturating_add(1);
+            if self.small_samples >= self.config.hold_samples {
+                self.small_samples = 0;
+                return down;
+            }
+        } else {
+            self.small_samples = 0;
+        }
+        None
+    }
+}
+
+impl Default for AutoRanger {
+    fn default() -> Self {
+        Self::new(AutoRangeConfig::default())
+    }
+}
+
+fn scale_index(scale: AccelScale) -> u8 {
+    match scale {
+        AccelScale::G2 => 0,
+        AccelScale::G4 => 1,
+        AccelScale::G8 => 2,
+        AccelScale::G16 => 3,
+    }
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Read accelerometer data and adjust the scale for the next samples
+    ///
+    /// The measurement is scaled with the scale it was taken at, so it is
+    /// always in mg regardless of scale changes. The sample in the output
+    /// registers at the time of a scale change was taken at the previous
+    /// scale, so it is discarded and `nb::Error::WouldBlock` is returned until
+    /// the accelerometer has new data at the new scale. This is part of
+    /// normal operation, poll again later.
+    ///
+    /// Returns `Error::NotReady` if the accelerometer is powered down and
+    /// `Error::InvalidInputData` if `down_percent` is not smaller than
+    /// `up_percent`.
+    pub fn accel_data_auto_ranged(
+        &mut self,
+        ranger: &mut AutoRanger,
+    ) -> nb::Result<AutoRangedMeasurement, Error<CommE, PinE>> {
+        if ranger.config.down_percent >= ranger.config.up_percent {
+            return Err(nb::Error::Other(Error::InvalidInputData));
+        }
+        let limit: i32 = match self.get_accel_mode() {
+            AccelMode::PowerDown => return Err(nb::Error::Other(Error::NotReady)),
+            AccelMode::HighResolution => 1 << 11,
+            AccelMode::Normal => 1 << 9,
+            AccelMode::LowPower => 1 << 7,
+        };
+        if ranger.settling {
+            if !self.accel_status()?.xyz_new_data {
+                return Err(nb::Error::WouldBlock);
+            }
+            ranger.settling = false;
+        }
+        let scale = self.get_accel_scale();
+        let unscaled = self.accel_data_unscaled()?;
+        let axes = [
+            i32::from(unscaled.x),
+            i32::from(unscaled.y),
+            i32::from(unscaled.z),
+        ];
+        let clipped = axes.iter().any(|v| *v >= limit - 1 || *v <= -limit);
+        let max_abs = axes.iter().map(|v| v.unsigned_abs()).max().unwrap_or(0);
+
+        let measurement = self.scale_accel_data(unscaled);
+        let data = self.calibrate_accel_data(measurement);
+        if let Some(next) = ranger.next_scale(scale, max_abs, limit as u32) {
+            self.set_accel_scale(next)?;
+            // Reading the output registers clears the data-ready flag, so
+            // that it signals the first sample at the new scale.
+            self.accel_data_unscaled()?;
+            ranger.settling = true;
+        }
+        Ok(AutoRangedMeasurement {
+            data,
+            scale,
+            clipped,
+        })
+    }
+}
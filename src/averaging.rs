@@ -0,0 +1,212 @@
+//! Oversampling: averaging several fresh samples into one reading.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    mode, Error, Lsm303agr, Measurement, Sensor,
+};
+use embedded_hal::blocking::delay::DelayUs;
+
+/// Mean and standard deviation of one axis
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AveragedAxis {
+    /// Mean
+    pub mean: f32,
+    /// Sample standard deviation (0 for a single sample)
+    ///
+    /// The standard deviation of the mean itself is this divided by the
+    /// square root of the number of samples.
+    pub std_dev: f32,
+}
+
+/// Average of several measurements
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AveragedMeasurement {
+    /// X axis
+    pub x: AveragedAxis,
+    /// Y axis
+    pub y: AveragedAxis,
+    /// Z axis
+    pub z: AveragedAxis,
+    /// Number of averaged samples
+    pub samples: u16,
+}
+
+/// Welford's running mean and variance
+#[derive(Debug, Default)]
+struct Accumulator {
+    n: u16,
+    mean: [f64; 3],
+    m2: [f64; 3],
+}
+
+impl Accumulator {
+    fn add(&mut self, m: Measurement) {
+        self.n += 1;
+        let values = [f64::from(m.x), f64::from(m.y), f64::from(m.z)];
+        for (i, value) in values.iter().enumerate() {
+            let delta = value - self.mean[i];
+            self.mean[i] += delta / f64::from(self.n);
+            self.m2[i] += delta * (value - self.mean[i]);
+        }
+    }
+
+    fn result(&self) -> AveragedMeasurement {
+        let axis = |i: usize| AveragedAxis {
+            mean: self.mean[i] as f32,
+            std_dev: if self.n > 1 {
+                libm::sqrt(self.m2[i] / f64::from(self.n - 1)) as f32
+            } else {
+                0.0
+            },
+        };
+        AveragedMeasurement {
+            x: axis(0),
+            y: axis(1),
+            z: axis(2),
+            samples: self.n,
+        }
+    }
+}
+
+/// Data-ready polls per output data rate period
+const POLLS_PER_PERIOD: u32 = 8;
+/// Data-ready polls for one sample before giving up, 10 output data rate
+/// periods
+const MAX_POLLS: u32 = 10 * POLLS_PER_PERIOD;
+
+/// Time to wait before polling again if no new data is available
+fn poll_interval_us(frequency_hz: u16) -> u32 {
+    (1_000_000 / u32::from(frequency_hz) / POLLS_PER_PERIOD).max(1)
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Average `n` fresh accelerometer samples in mg
+    ///
+    /// Waits for new data using the status register, so every sample is read
+    /// exactly once. Run the accelerometer at an output data rate higher than
+    /// the rate at which averaged readings are needed. The samples are
+    /// calibrated like in [`accel_data()`](Lsm303agr::accel_data) and
+    /// overruns are added to the
+    /// [`overrun_counters()`](Lsm303agr::overrun_counters).
+    ///
+    /// Returns `Error::InvalidInputData` if `n` is 0, `Error::NotReady` if
+    /// the accelerometer is powered down and `Error::Timeout` if no new sample
+    /// is available within 10 output data rate periods.
+    pub fn accel_data_averaged<D: DelayUs<u32>>(
+        &mut self,
+        n: u16,
+        delay: &mut D,
+    ) -> Result<AveragedMeasurement, Error<CommE, PinE>> {
+        let odr = self.accel_odr.ok_or(Error::NotReady)?;
+        if n == 0 {
+            return Err(Error::InvalidInputData);
+        }
+        let mut accumulator = Accumulator::default();
+        let mut polls = 0;
+        while accumulator.n < n {
+            let status = self.accel_status()?;
+            if status.xyz_new_data {
+                if status.xyz_overrun {
+                    self.count_overrun(Sensor::Accelerometer);
+                }
+                accumulator.add(self.accel_data()?);
+                polls = 0;
+            } else {
+                polls += 1;
+                if polls > MAX_POLLS {
+                    return Err(Error::Timeout);
+                }
+                delay.delay_us(poll_interval_us(odr.frequency_hz()));
+            }
+        }
+        Ok(accumulator.result())
+    }
+}
+
+impl<DI, CommE, PinE> Lsm303agr<DI, mode::MagContinuous>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Average `n` fresh magnetometer samples in nT
+    ///
+    /// Waits for new data using the status register, so every sample is read
+    /// exactly once. Overruns are added to the
+    /// [`overrun_counters()`](Lsm303agr::overrun_counters).
+    ///
+    /// Returns `Error::InvalidInputData` if `n` is 0 and `Error::Timeout` if
+    /// no new sample is available within 10 output data rate periods.
+    pub fn mag_data_averaged<D: DelayUs<u32>>(
+        &mut self,
+        n: u16,
+        delay: &mut D,
+    ) -> Result<AveragedMeasurement, Error<CommE, PinE>> {
+        if n == 0 {
+            return Err(Error::InvalidInputData);
+        }
+        let interval = poll_interval_us(self.get_mag_odr().frequency_hz());
+        let mut accumulator = Accumulator::default();
+        let mut polls = 0;
+        while accumulator.n < n {
+            let status = self.mag_status()?;
+            if status.xyz_new_data {
+                if status.xyz_overrun {
+                    self.count_overrun(Sensor::Magnetometer);
+                }
+                accumulator.add(self.mag_output()?);
+                polls = 0;
+            } else {
+                polls += 1;
+                if polls > MAX_POLLS {
+                    return Err(Error::Timeout);
+                }
+                delay.delay_us(interval);
+            }
+        }
+        Ok(accumulator.result())
+    }
+}
+
+impl<DI, CommE, PinE> Lsm303agr<DI, mode::MagOneShot>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Average `n` one-shot magnetometer measurements in nT
+    ///
+    /// Triggers the measurements one after the other and waits for each of
+    /// them to complete.
+    ///
+    /// Returns `Error::InvalidInputData` if `n` is 0 and `Error::Timeout` if
+    /// a measurement does not complete within 10 output data rate periods.
+    pub fn mag_data_averaged<D: DelayUs<u32>>(
+        &mut self,
+        n: u16,
+        delay: &mut D,
+    ) -> Result<AveragedMeasurement, Error<CommE, PinE>> {
+        if n == 0 {
+            return Err(Error::InvalidInputData);
+        }
+        let interval = poll_interval_us(self.get_mag_odr().frequency_hz());
+        let mut accumulator = Accumulator::default();
+        let mut polls = 0;
+        while accumulator.n < n {
+            match self.mag_data() {
+                Ok(data) => {
+                    accumulator.add(data);
+                    polls = 0;
+                }
+                Err(nb::Error::WouldBlock) => {
+                    polls += 1;
+                    if polls > MAX_POLLS {
+                        return Err(Error::Timeout);
+                    }
+                    delay.delay_us(interval);
+                }
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+        }
+        Ok(accumulator.result())
+    }
+}
@@ -0,0 +1,482 @@
+//! Command-line tool for the LSM303AGR on Linux.
+//!
+//! Run `lsm303agr help` for usage.
+
+use embedded_hal::blocking::delay::DelayUs;
+use linux_embedded_hal::{Delay, I2cdev};
+use lsm303agr::{
+    interface::{ReadData, WriteData},
+    mode,
+    sim::Simulator,
+    AccelMode, AccelOutputDataRate, AccelScale, DeviceConfig, Error, Lsm303agr,
+    MagCalibrationError, MagCalibrator, MagOutputDataRate, Measurement, SelfTestResult,
+};
+use std::{env, fmt::Debug, process};
+
+const USAGE: &str = "\
+Usage: lsm303agr [--device <PATH> | --sim] <COMMAND> [OPTIONS]
+
+Bus:
+    --device <PATH>       I2C bus device (default /dev/i2c-1)
+    --sim                 Use a simulated device at rest instead of hardware
+
+Commands:
+    info                  Show the device identification
+    dump                  Show all registers
+    stream                Print accelerometer, magnetometer and temperature samples
+        --odr <HZ>            Accelerometer output data rate (default 10)
+        --mode <MODE>         Accelerometer mode: low-power, normal or
+                              high-resolution (default normal)
+        --scale <G>           Accelerometer scale: 2, 4, 8 or 16 (default 2)
+        --mag-odr <HZ>        Magnetometer output data rate: 10, 20, 50 or 100
+                              (default 10)
+        --format <FORMAT>     Output format: csv or json (default csv)
+        --count <N>           Stop after N samples
+    selftest              Run the accelerometer and magnetometer self-tests
+    calibrate             Calibrate the magnetometer while the device is rotated
+                          in all directions
+        --samples <N>         Number of samples to collect (default 1000)
+    help                  Show this help
+";
+
+/// Exit code for invalid arguments
+const USAGE_ERROR: i32 = 2;
+/// Sample periods to wait for new data before giving up
+const DATA_READY_PERIODS: u32 = 10;
+/// Data-ready polls per sample period
+const POLLS_PER_PERIOD: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Debug)]
+struct StreamOptions {
+    odr: AccelOutputDataRate,
+    mode: AccelMode,
+    scale: AccelScale,
+    mag_odr: MagOutputDataRate,
+    format: Format,
+    count: Option<u64>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Info,
+    Dump,
+    Stream(StreamOptions),
+    SelfTest,
+    Calibrate { samples: u32 },
+    Help,
+}
+
+#[derive(Debug)]
+enum Bus {
+    Device(String),
+    Sim,
+}
+
+#[derive(Debug)]
+struct Args {
+    bus: Bus,
+    command: Command,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut bus = Bus::Device("/dev/i2c-1".into());
+    let mut args = args.iter();
+    let command = loop {
+        match args.next().map(String::as_str) {
+            Some("--device") => bus = Bus::Device(value(&mut args, "--device")?.into()),
+            Some("--sim") => bus = Bus::Sim,
+            Some(command) => break command,
+            None => return Err("missing command".into()),
+        }
+    };
+    let command = match command {
+        "info" => Command::Info,
+        "dump" => Command::Dump,
+        "stream" => {
+            let mut options = StreamOptions {
+                odr: AccelOutputDataRate::Hz10,
+                mode: AccelMode::Normal,
+                scale: AccelScale::G2,
+                mag_odr: MagOutputDataRate::Hz10,
+                format: Format::Csv,
+                count: None,
+            };
+            while let Some(option) = args.next() {
+                let value = value(&mut args, option)?;
+                match option.as_str() {
+                    "--odr" => options.odr = parse_accel_odr(value)?,
+                    "--mode" => options.mode = parse_accel_mode(value)?,
+                    "--scale" => options.scale = parse_accel_scale(value)?,
+                    "--mag-odr" => options.mag_odr = parse_mag_odr(value)?,
+                    "--format" => options.format = parse_format(value)?,
+                    "--count" => options.count = Some(parse_number(option, value)?),
+                    _ => return Err(format!("unknown option {}", option)),
+                }
+            }
+            return Ok(Args {
+                bus,
+                command: Command::Stream(options),
+            });
+        }
+        "selftest" => Command::SelfTest,
+        "calibrate" => {
+            let mut samples = 1000;
+            while let Some(option) = args.next() {
+                let value = value(&mut args, option)?;
+                match option.as_str() {
+                    "--samples" => samples = parse_number(option, value)?,
+                    _ => return Err(format!("unknown option {}", option)),
+                }
+            }
+            return Ok(Args {
+                bus,
+                command: Command::Calibrate { samples },
+            });
+        }
+        "help" | "--help" | "-h" => Command::Help,
+        _ => return Err(format!("unknown command {}", command)),
+    };
+    match args.next() {
+        Some(arg) => Err(format!("unexpected argument {}", arg)),
+        None => Ok(Args { bus, command }),
+    }
+}
+
+fn value<'a>(args: &mut impl Iterator<Item = &'a String>, option: &str) -> Result<&'a str, String> {
+    args.next()
+        .map(String::as_str)
+        .ok_or_else(|| format!("missing value for {}", option))
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {} for {}", value, option))
+}
+
+fn parse_accel_odr(value: &str) -> Result<AccelOutputDataRate, String> {
+    Ok(match value {
+        "1" => AccelOutputDataRate::Hz1,
+        "10" => AccelOutputDataRate::Hz10,
+        "25" => AccelOutputDataRate::Hz25,
+        "50" => AccelOutputDataRate::Hz50,
+        "100" => AccelOutputDataRate::Hz100,
+        "200" => AccelOutputDataRate::Hz200,
+        "400" => AccelOutputDataRate::Hz400,
+        "1344" => AccelOutputDataRate::Khz1_344,
+        "1620" => AccelOutputDataRate::Khz1_620LowPower,
+        "5376" => AccelOutputDataRate::Khz5_376LowPower,
+        _ => return Err(format!("invalid accelerometer output data rate {}", value)),
+    })
+}
+
+fn parse_accel_mode(value: &str) -> Result<AccelMode, String> {
+    Ok(match value {
+        "low-power" => AccelMode::LowPower,
+        "normal" => AccelMode::Normal,
+        "high-resolution" => AccelMode::HighResolution,
+        _ => return Err(format!("invalid accelerometer mode {}", value)),
+    })
+}
+
+fn parse_accel_scale(value: &str) -> Result<AccelScale, String> {
+    Ok(match value {
+        "2" => AccelScale::G2,
+        "4" => AccelScale::G4,
+        "8" => AccelScale::G8,
+        "16" => AccelScale::G16,
+        _ => return Err(format!("invalid accelerometer scale {}", value)),
+    })
+}
+
+fn parse_mag_odr(value: &str) -> Result<MagOutputDataRate, String> {
+    Ok(match value {
+        "10" => MagOutputDataRate::Hz10,
+        "20" => MagOutputDataRate::Hz20,
+        "50" => MagOutputDataRate::Hz50,
+        "100" => MagOutputDataRate::Hz100,
+        _ => return Err(format!("invalid magnetometer output data rate {}", value)),
+    })
+}
+
+fn parse_format(value: &str) -> Result<Format, String> {
+    match value {
+        "csv" => Ok(Format::Csv),
+        "json" => Ok(Format::Json),
+        _ => Err(format!("invalid format {}", value)),
+    }
+}
+
+fn device_error<CommE: Debug, PinE: Debug>(error: Error<CommE, PinE>) -> String {
+    format!("device error: {}", error)
+}
+
+/// Poll `new_data` until it returns true, giving up after
+/// `DATA_READY_PERIODS` sample periods at `frequency_hz`
+fn wait_for_new_data<D: DelayUs<u32>>(
+    delay: &mut D,
+    sensor: &str,
+    frequency_hz: u16,
+    mut new_data: impl FnMut() -> Result<bool, String>,
+) -> Result<(), String> {
+    let poll_interval_us = (1_000_000 / u32::from(frequency_hz) / POLLS_PER_PERIOD).max(1);
+    for _ in 0..DATA_READY_PERIODS * POLLS_PER_PERIOD {
+        if new_data()? {
+            return Ok(());
+        }
+        delay.delay_us(poll_interval_us);
+    }
+    Err(format!("no new data from the {}", sensor))
+}
+
+fn run<DI, CommE, PinE, D>(
+    mut sensor: Lsm303agr<DI, mode::MagOneShot>,
+    delay: &mut D,
+    command: Command,
+) -> Result<(), String>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+    CommE: Debug,
+    PinE: Debug,
+    D: DelayUs<u32>,
+{
+    match command {
+        Command::Info => info(&mut sensor).map_err(device_error),
+        Command::Dump => dump(&mut sensor).map_err(device_error),
+        Command::Stream(options) => stream(sensor, delay, &options),
+        Command::SelfTest => self_test(&mut sensor, delay),
+        Command::Calibrate { samples } => calibrate(sensor, delay, samples),
+        Command::Help => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+    }
+}
+
+fn info<DI, CommE, PinE>(
+    sensor: &mut Lsm303agr<DI, mode::MagOneShot>,
+) -> Result<(), Error<CommE, PinE>>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    let detected = |detected: bool| if detected { "detected" } else { "not detected" };
+    println!(
+        "Accelerometer: WHO_AM_I_A 0x{:02x} ({})",
+        sensor.accelerometer_id()?,
+        detected(sensor.accelerometer_is_detected()?)
+    );
+    println!(
+        "Magnetometer: WHO_AM_I_M 0x{:02x} ({})",
+        sensor.magnetometer_id()?,
+        detected(sensor.magnetometer_is_detected()?)
+    );
+    Ok(())
+}
+
+fn dump<DI, CommE, PinE>(
+    sensor: &mut Lsm303agr<DI, mode::MagOneShot>,
+) -> Result<(), Error<CommE, PinE>>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    println!("{:#x?}", sensor.dump_registers()?);
+    Ok(())
+}
+
+fn stream<DI, CommE, PinE, D>(
+    mut sensor: Lsm303agr<DI, mode::MagOneShot>,
+    delay: &mut D,
+    options: &StreamOptions,
+) -> Result<(), String>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+    CommE: Debug,
+    PinE: Debug,
+    D: DelayUs<u32>,
+{
+    let config = DeviceConfig::default()
+        .with_accel_odr(options.odr)
+        .with_accel_mode(options.mode)
+        .with_accel_scale(options.scale)
+        .with_mag_odr(options.mag_odr);
+    sensor.apply_config(&config).map_err(|error| match error {
+        Error::IncompatibleOdrMode { .. } => format!(
+            "accelerometer output data rate {} Hz is not available in the selected mode",
+            options.odr.frequency_hz()
+        ),
+        error => device_error(error),
+    })?;
+    let mut sensor = sensor
+        .into_mag_continuous()
+        .map_err(|e| device_error(e.error))?;
+
+    if options.format == Format::Csv {
+        println!(
+            "sample,accel_x_mg,accel_y_mg,accel_z_mg,mag_x_nt,mag_y_nt,mag_z_nt,temperature_c"
+        );
+    }
+    // Wait for the first magnetometer sample, which may come after several
+    // accelerometer samples.
+    wait_for_new_data(
+        delay,
+        "magnetometer",
+        options.mag_odr.frequency_hz(),
+        || Ok(sensor.mag_status().map_err(device_error)?.xyz_new_data),
+    )?;
+    let mut sample = 0;
+    while options.count != Some(sample) {
+        wait_for_new_data(delay, "accelerometer", options.odr.frequency_hz(), || {
+            Ok(sensor.accel_status().map_err(device_error)?.xyz_new_data)
+        })?;
+        let accel = sensor.accel_data().map_err(device_error)?;
+        let mag = sensor.mag_data().map_err(device_error)?;
+        let temperature = sensor.temperature_celsius().map_err(device_error)?;
+        match options.format {
+            Format::Csv => println!(
+                "{},{},{},{},{},{},{},{:.2}",
+                sample, accel.x, accel.y, accel.z, mag.x, mag.y, mag.z, temperature
+            ),
+            Format::Json => println!(
+                "{{\"sample\":{},\"accel_mg\":[{},{},{}],\"mag_nt\":[{},{},{}],\"temperature_c\":{:.2}}}",
+                sample, accel.x, accel.y, accel.z, mag.x, mag.y, mag.z, temperature
+            ),
+        }
+        sample += 1;
+    }
+    Ok(())
+}
+
+fn self_test<DI, CommE, PinE, D>(
+    sensor: &mut Lsm303agr<DI, mode::MagOneShot>,
+    delay: &mut D,
+) -> Result<(), String>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+    CommE: Debug,
+    PinE: Debug,
+    D: DelayUs<u32>,
+{
+    let report = |name: &str, result: &SelfTestResult, unit: &str| {
+        let d = result.difference;
+        println!(
+            "{}: {} (difference x {} y {} z {} {})",
+            name,
+            if result.passed { "PASS" } else { "FAIL" },
+            d.x,
+            d.y,
+            d.z,
+            unit
+        );
+    };
+    let accel = sensor.accel_self_test(delay).map_err(device_error)?;
+    report("Accelerometer", &accel, "mg");
+    let mag = sensor.mag_self_test(delay).map_err(device_error)?;
+    report("Magnetometer", &mag, "nT");
+    if accel.passed && mag.passed {
+        Ok(())
+    } else {
+        Err("self-test failed".into())
+    }
+}
+
+fn calibrate<DI, CommE, PinE, D>(
+    mut sensor: Lsm303agr<DI, mode::MagOneShot>,
+    delay: &mut D,
+    samples: u32,
+) -> Result<(), String>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+    CommE: Debug,
+    PinE: Debug,
+    D: DelayUs<u32>,
+{
+    let odr = MagOutputDataRate::Hz100;
+    sensor.init().map_err(device_error)?;
+    sensor.set_mag_odr(odr).map_err(device_error)?;
+    let mut sensor = sensor
+        .into_mag_continuous()
+        .map_err(|e| device_error(e.error))?;
+    eprintln!("Rotate the device slowly in all directions...");
+    let mut calibrator = MagCalibrator::new();
+    let mut collected = 0;
+    while collected < samples {
+        wait_for_new_data(delay, "magnetometer", odr.frequency_hz(), || {
+            Ok(sensor.mag_status().map_err(device_error)?.xyz_new_data)
+        })?;
+        calibrator.add_sample(sensor.mag_data().map_err(device_error)?);
+        collected += 1;
+        if collected % 100 == 0 {
+            eprintln!(
+                "{} samples, {:.0}% coverage",
+                collected,
+                calibrator.coverage() * 100.0
+            );
+        }
+    }
+    let calibration = calibrator.solve().map_err(|error| {
+        let reason = match error {
+            MagCalibrationError::NotEnoughSamples => "not enough samples",
+            MagCalibrationError::InsufficientCoverage => {
+                "the device was not rotated in enough directions"
+            }
+            MagCalibrationError::InvalidFit => "the samples do not describe an ellipsoid",
+        };
+        format!("calibration failed: {}", reason)
+    })?;
+    let h = calibration.hard_iron;
+    let s = calibration.soft_iron;
+    let offset = calibration.hard_iron_offset();
+    println!("Hard iron (nT): {:.1} {:.1} {:.1}", h[0], h[1], h[2]);
+    println!("Soft iron:");
+    for row in s.iter() {
+        println!("    {:.4} {:.4} {:.4}", row[0], row[1], row[2]);
+    }
+    println!("Field strength (nT): {:.1}", calibration.field_strength);
+    println!(
+        "Hard-iron offset registers (LSB): {} {} {}",
+        offset.x, offset.y, offset.z
+    );
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(USAGE_ERROR);
+        }
+    };
+    let result = match args.bus {
+        Bus::Device(path) => match I2cdev::new(&path) {
+            Ok(dev) => run(Lsm303agr::new_with_i2c(dev), &mut Delay, args.command),
+            Err(e) => Err(format!("cannot open {}: {}", path, e)),
+        },
+        Bus::Sim => {
+            let sim = Simulator::new();
+            sim.set_acceleration(Measurement {
+                x: 0,
+                y: 0,
+                z: 1000,
+            });
+            sim.set_magnetic_field(Measurement {
+                x: 19_950,
+                y: 0,
+                z: -44_850,
+            });
+            let mut delay = sim.delay();
+            run(Lsm303agr::new_with_i2c(sim.i2c()), &mut delay, args.command)
+        }
+    };
+    if let Err(message) = result {
+        eprintln!("error: {}", message);
+        process::exit(1);
+    }
+}
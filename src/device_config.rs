@@ -0,0 +1,133 @@
+//! Declarative device configuration.
+//!
+//! A [`DeviceConfig`] describes the complete desired state of the
+//! accelerometer and the magnetometer. [`apply_config()`](Lsm303agr::apply_config)
+//! validates it before touching the device and then writes only the
+//! registers that differ, in an order that never passes through an invalid
+//! power mode.
+
+use crate::{
+    accel_mode_and_odr::{
+        accel_odr_bits, accel_scale_bits, check_accel_odr_is_compatible_with_mode,
+    },
+    interface::{ReadData, WriteData},
+    magnetometer::mag_odr_bits,
+    registers::{CfgRegAM, CfgRegCM, CtrlReg1A, CtrlReg4A, TempCfgRegA},
+    AccelMode, AccelOutputDataRate, AccelScale, DeviceConfig, Error, Lsm303agr, MagOutputDataRate,
+    Register,
+};
+
+impl DeviceConfig {
+    /// Set the accelerometer output data rate
+    pub fn with_accel_odr(mut self, odr: AccelOutputDataRate) -> Self {
+        self.accel_odr = Some(odr);
+        self
+    }
+
+    /// Set the accelerometer mode
+    ///
+    /// `AccelMode::PowerDown` requires no accelerometer output data rate and
+    /// all other modes require one.
+    pub fn with_accel_mode(mut self, mode: AccelMode) -> Self {
+        self.accel_mode = mode;
+        self
+    }
+
+    /// Set the accelerometer scale
+    pub fn with_accel_scale(mut self, scale: AccelScale) -> Self {
+        self.accel_scale = scale;
+        self
+    }
+
+    /// Set the magnetometer output data rate
+    pub fn with_mag_odr(mut self, odr: MagOutputDataRate) -> Self {
+        self.mag_odr = odr;
+        self
+    }
+
+    /// Check that the output data rate and mode can be combined
+    fn check<CommE, PinE>(&self) -> Result<(), Error<CommE, PinE>> {
+        match (self.accel_odr, self.accel_mode) {
+            (None, AccelMode::PowerDown) => Ok(()),
+            (None, _) => Err(Error::InvalidInputData),
+            (Some(odr), AccelMode::PowerDown) => Err(Error::IncompatibleOdrMode {
+                odr,
+                mode: AccelMode::PowerDown,
+            }),
+            (odr, mode) => check_accel_odr_is_compatible_with_mode(odr, mode),
+        }
+    }
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Apply a complete device configuration
+    ///
+    /// Returns `Error::IncompatibleOdrMode` without accessing the device if
+    /// the accelerometer output data rate and mode cannot be combined and
+    /// `Error::InvalidInputData` if an output data rate is missing.
+    ///
+    /// Like [`init()`](Lsm303agr::init), this also enables the temperature
+    /// sensor and block data update, so it can replace the sequence of
+    /// `init()` and setter calls. Registers whose cached value already
+    /// matches are not written. The magnetometer mode is selected with
+    /// [`into_mag_continuous()`](Lsm303agr::into_mag_continuous) and
+    /// [`into_mag_one_shot()`](Lsm303agr::into_mag_one_shot) and is kept.
+    pub fn apply_config(&mut self, config: &DeviceConfig) -> Result<(), Error<CommE, PinE>> {
+        config.check()?;
+
+        let temp_cfg = TempCfgRegA { temp_en: 0b11 }.bits();
+        let reg1 = CtrlReg1A {
+            odr: config.accel_odr.map_or(0, accel_odr_bits),
+            lp_en: config.accel_mode == AccelMode::LowPower,
+            ..self.ctrl_reg1_a.bits.into()
+        }
+        .bits();
+        let reg4 = CtrlReg4A {
+            bdu: true,
+            fs: accel_scale_bits(config.accel_scale),
+            hr: config.accel_mode == AccelMode::HighResolution,
+            ..self.ctrl_reg4_a.bits.into()
+        }
+        .bits();
+        let cfg_a = CfgRegAM {
+            odr: mag_odr_bits(config.mag_odr),
+            ..self.cfg_reg_a_m.bits.into()
+        }
+        .bits();
+        let cfg_c = CfgRegCM {
+            bdu: true,
+            ..self.cfg_reg_c_m.bits.into()
+        }
+        .bits();
+
+        self.update_accel_register(Register::TEMP_CFG_REG_A, temp_cfg)?;
+        // Low-power and high-resolution mode must never be enabled at the
+        // same time, so the register disabling one of them goes first.
+        if config.accel_mode == AccelMode::LowPower {
+            self.update_accel_register(Register::CTRL_REG4_A, reg4)?;
+            self.update_accel_register(Register::CTRL_REG1_A, reg1)?;
+        } else {
+            self.update_accel_register(Register::CTRL_REG1_A, reg1)?;
+            self.update_accel_register(Register::CTRL_REG4_A, reg4)?;
+        }
+        self.accel_odr = config.accel_odr;
+        self.update_mag_register(Register::CFG_REG_A_M, cfg_a)?;
+        self.update_mag_register(Register::CFG_REG_C_M, cfg_c)
+    }
+
+    /// Current device configuration
+    ///
+    /// This can be stored in [`Settings`](crate::Settings) and applied again
+    /// with [`apply_config()`](Lsm303agr::apply_config).
+    pub fn device_config(&self) -> DeviceConfig {
+        DeviceConfig {
+            accel_odr: self.accel_odr,
+            accel_mode: self.get_accel_mode(),
+            accel_scale: self.get_accel_scale(),
+            mag_odr: self.get_mag_odr(),
+        }
+    }
+}
@@ -2,26 +2,40 @@ use crate::{
     interface::{I2cInterface, ReadData, SpiInterface, WriteData},
     mode,
     register_address::{WHO_AM_I_A_VAL, WHO_AM_I_M_VAL},
-    AccelMode, AccelScale, BitFlags as BF, Config, Error, Lsm303agr, Measurement, PhantomData,
-    Register, Status, TemperatureStatus, UnscaledMeasurement,
+    registers::{CfgRegCM, CtrlReg4A, StatusRegA, StatusRegAuxA, TempCfgRegA},
+    AccelMode, AccelScale, Config, Error, Lsm303agr, Measurement, OverrunCounters, PhantomData,
+    Register, Sensor, Status, TemperatureStatus, UnscaledMeasurement,
 };
 
-impl<I2C> Lsm303agr<I2cInterface<I2C>, mode::MagOneShot> {
-    /// Create new instance of the LSM303AGR device communicating through I2C.
-    pub fn new_with_i2c(i2c: I2C) -> Self {
+impl<DI> Lsm303agr<DI, mode::MagOneShot> {
+    pub(crate) fn new_with_interface(iface: DI) -> Self {
         Lsm303agr {
-            iface: I2cInterface { i2c },
+            iface,
             ctrl_reg1_a: Config { bits: 0x7 },
+            ctrl_reg3_a: Config { bits: 0 },
             ctrl_reg4_a: Config { bits: 0 },
+            ctrl_reg5_a: Config { bits: 0 },
+            ctrl_reg6_a: Config { bits: 0 },
+            fifo_ctrl_reg_a: Config { bits: 0 },
             cfg_reg_a_m: Config { bits: 0x3 },
             cfg_reg_c_m: Config { bits: 0 },
             temp_cfg_reg_a: Config { bits: 0 },
             accel_odr: None,
+            accel_calibration: None,
+            overruns: OverrunCounters::default(),
+            verify_writes: false,
             _mag_mode: PhantomData,
         }
     }
 }
 
+impl<I2C> Lsm303agr<I2cInterface<I2C>, mode::MagOneShot> {
+    /// Create new instance of the LSM303AGR device communicating through I2C.
+    pub fn new_with_i2c(i2c: I2C) -> Self {
+        Self::new_with_interface(I2cInterface { i2c })
+    }
+}
+
 impl<I2C, MODE> Lsm303agr<I2cInterface<I2C>, MODE> {
     /// Destroy driver instance, return I2C bus.
     pub fn destroy(self) -> I2C {
@@ -32,20 +46,11 @@ impl<I2C, MODE> Lsm303agr<I2cInterface<I2C>, MODE> {
 impl<SPI, CSXL, CSMAG> Lsm303agr<SpiInterface<SPI, CSXL, CSMAG>, mode::MagOneShot> {
     /// Create new instance of the LSM303AGR device communicating through SPI.
     pub fn new_with_spi(spi: SPI, chip_select_accel: CSXL, chip_select_mag: CSMAG) -> Self {
-        Lsm303agr {
-            iface: SpiInterface {
-                spi,
-                cs_xl: chip_select_accel,
-                cs_mag: chip_select_mag,
-            },
-            ctrl_reg1_a: Config { bits: 0x7 },
-            ctrl_reg4_a: Config { bits: 0 },
-            cfg_reg_a_m: Config { bits: 0x3 },
-            cfg_reg_c_m: Config { bits: 0 },
-            temp_cfg_reg_a: Config { bits: 0 },
-            accel_odr: None,
-            _mag_mode: PhantomData,
-        }
+        Self::new_with_interface(SpiInterface {
+            spi,
+            cs_xl: chip_select_accel,
+            cs_mag: chip_select_mag,
+        })
     }
 }
 
@@ -62,21 +67,23 @@ where
 {
     /// Initialize registers
     pub fn init(&mut self) -> Result<(), Error<CommE, PinE>> {
-        let temp_cfg_reg = self
-            .temp_cfg_reg_a
-            .with_high(BF::TEMP_EN0)
-            .with_high(BF::TEMP_EN1);
-        self.iface
-            .write_accel_register(Register::TEMP_CFG_REG_A, temp_cfg_reg.bits)?;
-        self.temp_cfg_reg_a = temp_cfg_reg;
-        let reg4 = self.ctrl_reg4_a.with_high(BF::ACCEL_BDU);
-        self.iface
-            .write_accel_register(Register::CTRL_REG4_A, reg4.bits)?;
-        self.ctrl_reg4_a = reg4;
-        let regc = self.cfg_reg_c_m.with_high(BF::MAG_BDU);
-        self.iface
-            .write_mag_register(Register::CFG_REG_C_M, regc.bits)?;
-        self.cfg_reg_c_m = regc;
+        let temp_cfg_reg = TempCfgRegA { temp_en: 0b11 }.bits();
+        self.write_accel_register(Register::TEMP_CFG_REG_A, temp_cfg_reg)?;
+        self.temp_cfg_reg_a = temp_cfg_reg.into();
+        let reg4 = CtrlReg4A {
+            bdu: true,
+            ..self.ctrl_reg4_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG4_A, reg4)?;
+        self.ctrl_reg4_a = reg4.into();
+        let regc = CfgRegCM {
+            bdu: true,
+            ..self.cfg_reg_c_m.bits.into()
+        }
+        .bits();
+        self.write_mag_register(Register::CFG_REG_C_M, regc)?;
+        self.cfg_reg_c_m = regc.into();
         Ok(())
     }
 
@@ -92,9 +99,29 @@ where
     /// Returned in mg (milli-g) where 1g is 9.8m/s².
     ///
     /// If you need the raw unscaled measurement see [`Lsm303agr::accel_data_unscaled`].
+    ///
+    /// The accelerometer calibration is applied if one has been set with
+    /// [`Lsm303agr::set_accel_calibration`].
     pub fn accel_data(&mut self) -> Result<Measurement, Error<CommE, PinE>> {
+        let measurement = self.accel_data_uncalibrated()?;
+        Ok(self.calibrate_accel_data(measurement))
+    }
+
+    /// Apply the accelerometer calibration, if any
+    pub(crate) fn calibrate_accel_data(&self, measurement: Measurement) -> Measurement {
+        match self.accel_calibration {
+            Some(calibration) => calibration.apply(measurement),
+            None => measurement,
+        }
+    }
+
+    pub(crate) fn accel_data_uncalibrated(&mut self) -> Result<Measurement, Error<CommE, PinE>> {
         let unscaled = self.accel_data_unscaled()?;
+        Ok(self.scale_accel_data(unscaled))
+    }
 
+    /// Convert unscaled accelerometer data to mg without calibration
+    pub(crate) fn scale_accel_data(&mut self, unscaled: UnscaledMeasurement) -> Measurement {
         let mode = self.get_accel_mode();
         let scale = self.get_accel_scale();
 
@@ -104,27 +131,27 @@ where
                 AccelScale::G2 => 1,
                 AccelScale::G4 => 2,
                 AccelScale::G8 => 4,
-                AccelScale::G16 => 8,
+                AccelScale::G16 => 12,
             },
             AccelMode::LowPower => match scale {
                 AccelScale::G2 => 16,
                 AccelScale::G4 => 32,
                 AccelScale::G8 => 64,
-                AccelScale::G16 => 128,
+                AccelScale::G16 => 192,
             },
             AccelMode::Normal => match scale {
                 AccelScale::G2 => 4,
                 AccelScale::G4 => 8,
                 AccelScale::G8 => 16,
-                AccelScale::G16 => 32,
+                AccelScale::G16 => 48,
             },
         };
 
-        Ok(Measurement {
+        Measurement {
             x: (unscaled.x as i32) * scaling_factor,
             y: (unscaled.y as i32) * scaling_factor,
             z: (unscaled.z as i32) * scaling_factor,
-        })
+        }
     }
 
     /// Unscaled accelerometer data
@@ -132,7 +159,11 @@ where
         let data = self
             .iface
             .read_accel_3_double_registers(Register::OUT_X_L_A)?;
+        Ok(self.unscale_accel_data(data))
+    }
 
+    /// Convert the output register values to unscaled accelerometer data
+    pub(crate) fn unscale_accel_data(&self, data: (u16, u16, u16)) -> UnscaledMeasurement {
         let mode = self.get_accel_mode();
 
         let resolution_factor = match mode {
@@ -142,11 +173,11 @@ where
             AccelMode::Normal => 1 << 6,
         };
 
-        Ok(UnscaledMeasurement {
+        UnscaledMeasurement {
             x: (data.0 as i16) / resolution_factor,
             y: (data.1 as i16) / resolution_factor,
             z: (data.2 as i16) / resolution_factor,
-        })
+        }
     }
 
     /// Magnetometer status
@@ -176,6 +207,29 @@ where
         Ok(self.magnetometer_id()? == WHO_AM_I_M_VAL)
     }
 
+    /// Read and verify the accelerometer and magnetometer device IDs
+    ///
+    /// Returns `Error::WrongDeviceId` with the first ID that does not match.
+    pub fn check_device_ids(&mut self) -> Result<(), Error<CommE, PinE>> {
+        let found = self.accelerometer_id()?;
+        if found != WHO_AM_I_A_VAL {
+            return Err(Error::WrongDeviceId {
+                sensor: Sensor::Accelerometer,
+                expected: WHO_AM_I_A_VAL,
+                found,
+            });
+        }
+        let found = self.magnetometer_id()?;
+        if found != WHO_AM_I_M_VAL {
+            return Err(Error::WrongDeviceId {
+                sensor: Sensor::Magnetometer,
+                expected: WHO_AM_I_M_VAL,
+                found,
+            });
+        }
+        Ok(())
+    }
+
     /// Read temperature sensor data
     pub fn temperature_data(&mut self) -> Result<i16, Error<CommE, PinE>> {
         let data = self
@@ -200,22 +254,25 @@ where
     }
 }
 
+// STATUS_REG_M has the same layout as STATUS_REG_A.
 fn convert_status(st: u8) -> Status {
+    let st = StatusRegA::from(st);
     Status {
-        xyz_overrun: (st & BF::XYZOR) != 0,
-        z_overrun: (st & BF::ZOR) != 0,
-        y_overrun: (st & BF::YOR) != 0,
-        x_overrun: (st & BF::XOR) != 0,
-        xyz_new_data: (st & BF::XYZDR) != 0,
-        z_new_data: (st & BF::ZDR) != 0,
-        y_new_data: (st & BF::YDR) != 0,
-        x_new_data: (st & BF::XDR) != 0,
+        xyz_overrun: st.zyxor,
+        z_overrun: st.zor,
+        y_overrun: st.yor,
+        x_overrun: st.xor,
+        xyz_new_data: st.zyxda,
+        z_new_data: st.zda,
+        y_new_data: st.yda,
+        x_new_data: st.xda,
     }
 }
 
 fn convert_temperature_status(st: u8) -> TemperatureStatus {
+    let st = StatusRegAuxA::from(st);
     TemperatureStatus {
-        overrun: (st & BF::TOR) != 0,
-        new_data: (st & BF::TDA) != 0,
+        overrun: st.tor,
+        new_data: st.tda,
     }
 }
@@ -0,0 +1,159 @@
+//! Accelerometer FIFO.
+//!
+//! The FIFO stores up to 32 accelerometer samples, so that the MCU can sleep
+//! while they are collected and then read them in a batch.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    registers::{CtrlReg3A, CtrlReg5A, FifoCtrlRegA, FifoSrcRegA},
+    Error, Lsm303agr, Measurement, Register,
+};
+
+/// Number of samples the FIFO can hold
+pub const FIFO_CAPACITY: usize = 32;
+
+/// Accelerometer FIFO mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FifoMode {
+    /// FIFO disabled
+    Bypass,
+    /// Collect samples until the FIFO is full, then stop
+    Fifo,
+    /// Collect samples continuously, discarding the oldest ones when full
+    Stream,
+    /// Stream mode until the trigger event, then FIFO mode
+    StreamToFifo,
+}
+
+/// Accelerometer FIFO status
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FifoStatus {
+    /// The number of samples reached the watermark
+    pub watermark: bool,
+    /// The FIFO is full. In stream mode samples are being overwritten.
+    pub overrun: bool,
+    /// The FIFO is empty
+    pub empty: bool,
+    /// Number of unread samples
+    pub len: u8,
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Set the accelerometer FIFO mode and watermark level
+    ///
+    /// The FIFO is enabled for all modes except `FifoMode::Bypass`.
+    /// Switching to `FifoMode::Bypass` empties the FIFO.
+    ///
+    /// Returns `Error::InvalidInputData` if the watermark is greater than 31.
+    pub fn set_fifo_mode(
+        &mut self,
+        mode: FifoMode,
+        watermark: u8,
+    ) -> Result<(), Error<CommE, PinE>> {
+        let trigger = FifoCtrlRegA::from(self.fifo_ctrl_reg_a.bits).tr;
+        self.configure_fifo(mode, watermark, trigger)
+    }
+
+    /// Write the FIFO mode, watermark level and stream-to-FIFO trigger
+    /// selection and update the cached registers once written
+    pub(crate) fn configure_fifo(
+        &mut self,
+        mode: FifoMode,
+        watermark: u8,
+        trigger: bool,
+    ) -> Result<(), Error<CommE, PinE>> {
+        if watermark > 31 {
+            return Err(Error::InvalidInputData);
+        }
+        let reg5 = CtrlReg5A {
+            fifo_en: mode != FifoMode::Bypass,
+            ..self.ctrl_reg5_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG5_A, reg5)?;
+        self.ctrl_reg5_a = reg5.into();
+        let fm = match mode {
+            FifoMode::Bypass => 0b00,
+            FifoMode::Fifo => 0b01,
+            FifoMode::Stream => 0b10,
+            FifoMode::StreamToFifo => 0b11,
+        };
+        let fifo_ctrl = FifoCtrlRegA {
+            fm,
+            tr: trigger,
+            fth: watermark,
+        }
+        .bits();
+        self.write_accel_register(Register::FIFO_CTRL_REG_A, fifo_ctrl)?;
+        self.fifo_ctrl_reg_a = fifo_ctrl.into();
+        Ok(())
+    }
+
+    /// Enable or disable the FIFO watermark interrupt on the INT1 pin
+    pub fn set_fifo_watermark_interrupt(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), Error<CommE, PinE>> {
+        let reg3 = CtrlReg3A {
+            i1_wtm: enabled,
+            ..self.ctrl_reg3_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG3_A, reg3)?;
+        self.ctrl_reg3_a = reg3.into();
+        Ok(())
+    }
+
+    /// Accelerometer FIFO status
+    pub fn fifo_status(&mut self) -> Result<FifoStatus, Error<CommE, PinE>> {
+        let src = FifoSrcRegA::from(self.iface.read_accel_register(Register::FIFO_SRC_REG_A)?);
+        let len = if src.empty {
+            0
+        } else if src.ovrn_fifo {
+            FIFO_CAPACITY as u8
+        } else {
+            src.fss
+        };
+        Ok(FifoStatus {
+            watermark: src.wtm,
+            overrun: src.ovrn_fifo,
+            empty: src.empty,
+            len,
+        })
+    }
+
+    /// Read the samples stored in the accelerometer FIFO into `buffer`
+    ///
+    /// The samples are returned oldest first, in mg and with the
+    /// accelerometer calibration applied like in
+    /// [`accel_data()`](Lsm303agr::accel_data).
+    /// Returns the number of samples read, which is limited by the length of
+    /// the buffer.
+    ///
+    /// The samples are read in a single burst, so this costs two bus
+    /// transactions regardless of the number of samples.
+    pub fn fifo_data(&mut self, buffer: &mut [Measurement]) -> Result<usize, Error<CommE, PinE>> {
+        let len = usize::from(self.fifo_status()?.len).min(buffer.len());
+        if len == 0 {
+            return Ok(0);
+        }
+        let mut data = [0; FIFO_CAPACITY * 6];
+        let data = &mut data[..len * 6];
+        // With the FIFO enabled, the address rolls back from OUT_Z_H_A to
+        // OUT_X_L_A, so the samples follow each other.
+        self.iface.read_accel_registers(Register::OUT_X_L_A, data)?;
+        for (sample, bytes) in buffer.iter_mut().zip(data.chunks_exact(6)) {
+            let unscaled = self.unscale_accel_data((
+                u16::from_le_bytes([bytes[0], bytes[1]]),
+                u16::from_le_bytes([bytes[2], bytes[3]]),
+                u16::from_le_bytes([bytes[4], bytes[5]]),
+            ));
+            let measurement = self.scale_accel_data(unscaled);
+            *sample = self.calibrate_accel_data(measurement);
+        }
+        Ok(len)
+    }
+}
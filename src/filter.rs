@@ -0,0 +1,399 @@
+//! Software digital filters for accelerometer and magnetometer samples.
+//!
+//! All filters work on [`Measurement`] as well as on [`UnscaledMeasurement`],
+//! filter each axis independently and run in constant memory. Window sizes
+//! are set with const generics.
+//!
+//! These filters are independent of the hardware high-pass filter of the
+//! accelerometer and the low-pass filter of the magnetometer.
+//!
+//! Filters can be chained with [`Filter::chain()`]:
+//!
+//! ```
+//! use lsm303agr::{filter::{Biquad, Filter, Median}, AccelOutputDataRate, Measurement};
+//!
+//! let odr = AccelOutputDataRate::Hz100;
+//! // Remove spikes, then smooth with a 5 Hz low-pass filter.
+//! let mut filter =
+//!     Median::<3>::new().chain(Biquad::low_pass(5.0, f32::from(odr.frequency_hz())).unwrap());
+//! let filtered: Measurement = filter.update(Measurement { x: 10, y: -20, z: 1000 });
+//! ```
+
+use crate::{Measurement, UnscaledMeasurement};
+
+/// Sample type which can be filtered
+pub trait FilterSample: Copy {
+    /// Get the X, Y and Z values
+    fn to_axes(self) -> [i32; 3];
+    /// Create a sample from X, Y and Z values
+    ///
+    /// Values outside of the range of the sample type are saturated.
+    fn from_axes(axes: [i32; 3]) -> Self;
+}
+
+impl FilterSample for Measurement {
+    fn to_axes(self) -> [i32; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    fn from_axes(axes: [i32; 3]) -> Self {
+        Measurement {
+            x: axes[0],
+            y: axes[1],
+            z: axes[2],
+        }
+    }
+}
+
+impl FilterSample for UnscaledMeasurement {
+    fn to_axes(self) -> [i32; 3] {
+        [i32::from(self.x), i32::from(self.y), i32::from(self.z)]
+    }
+
+    fn from_axes(axes: [i32; 3]) -> Self {
+        let saturate = |v: i32| v.clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16;
+        UnscaledMeasurement {
+            x: saturate(axes[0]),
+            y: saturate(axes[1]),
+            z: saturate(axes[2]),
+        }
+    }
+}
+
+/// Digital filter
+pub trait Filter {
+    /// Filter a new sample and return the filtered sample
+    fn update<T: FilterSample>(&mut self, sample: T) -> T;
+
+    /// Forget all previous samples
+    fn reset(&mut self);
+
+    /// Filter a block of consecutive samples in place
+    fn update_slice<T: FilterSample>(&mut self, samples: &mut [T]) {
+        for sample in samples.iter_mut() {
+            *sample = self.update(*sample);
+        }
+    }
+
+    /// Feed the output of this filter into `next`
+    fn chain<F: Filter>(self, next: F) -> Chain<Self, F>
+    where
+        Self: Sized,
+    {
+        Chain {
+            first: self,
+            second: next,
+        }
+    }
+}
+
+/// Two filters applied one after the other
+///
+/// Created with [`Filter::chain()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Filter, B: Filter> Filter for Chain<A, B> {
+    fn update<T: FilterSample>(&mut self, sample: T) -> T {
+        self.second.update(self.first.update(sample))
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+}
+
+fn round(axes: [f32; 3]) -> [i32; 3] {
+    [
+        libm::roundf(axes[0]) as i32,
+        libm::roundf(axes[1]) as i32,
+        libm::roundf(axes[2]) as i32,
+    ]
+}
+
+/// Moving average over the last `N` samples
+///
+/// Until `N` samples have been seen, the average of the samples so far is
+/// returned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovingAverage<const N: usize> {
+    window: [[i32; 3]; N],
+    sum: [i64; 3],
+    next: usize,
+    len: usize,
+}
+
+impl<const N: usize> MovingAverage<N> {
+    /// Create a new moving average filter
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is 0.
+    pub fn new() -> Self {
+        assert!(N > 0, "the window must hold at least one sample");
+        MovingAverage {
+            window: [[0; 3]; N],
+            sum: [0; 3],
+            next: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for MovingAverage<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Filter for MovingAverage<N> {
+    fn update<T: FilterSample>(&mut self, sample: T) -> T {
+        let axes = sample.to_axes();
+        let oldest = &mut self.window[self.next];
+        for i in 0..3 {
+            if self.len == N {
+                self.sum[i] -= i64::from(oldest[i]);
+            }
+            self.sum[i] += i64::from(axes[i]);
+        }
+        *oldest = axes;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+        let len = self.len as i64;
+        // Division rounding half away from zero
+        let average = |sum: i64| ((sum + sum.signum() * len / 2) / len) as i32;
+        T::from_axes([
+            average(self.sum[0]),
+            average(self.sum[1]),
+            average(self.sum[2]),
+        ])
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// Median of the last `N` samples
+///
+/// Removes isolated spikes while preserving edges. Use an odd `N`; for an
+/// even number of samples the mean of the two middle values is returned.
+/// Until `N` samples have been seen, the median of the samples so far is
+/// returned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Median<const N: usize> {
+    window: [[i32; 3]; N],
+    next: usize,
+    len: usize,
+}
+
+impl<const N: usize> Median<N> {
+    /// Create a new median filter
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is 0.
+    pub fn new() -> Self {
+        assert!(N > 0, "the window must hold at least one sample");
+        Median {
+            window: [[0; 3]; N],
+            next: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for Median<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Filter for Median<N> {
+    fn update<T: FilterSample>(&mut self, sample: T) -> T {
+        self.window[self.next] = sample.to_axes();
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+        let mut median = [0; 3];
+        for (i, median) in median.iter_mut().enumerate() {
+            let mut values = [0; N];
+            for (value, axes) in values.iter_mut().zip(&self.window[..self.len]) {
+                *value = axes[i];
+            }
+            let values = &mut values[..self.len];
+            values.sort_unstable();
+            let mid = self.len / 2;
+            *median = if self.len % 2 == 1 {
+                values[mid]
+            } else {
+                ((i64::from(values[mid - 1]) + i64::from(values[mid])) / 2) as i32
+            };
+        }
+        T::from_axes(median)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// First-order exponential low-pass filter
+///
+/// `y[n] = y[n-1] + alpha * (x[n] - y[n-1])`
+///
+/// The first sample initializes the output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExponentialLowPass {
+    alpha: f32,
+    state: Option<[f32; 3]>,
+}
+
+impl ExponentialLowPass {
+    /// Create a new filter with the given smoothing factor
+    ///
+    /// Returns `None` if `alpha` is not in the range `(0, 1]`.
+    pub fn new(alpha: f32) -> Option<Self> {
+        if alpha > 0.0 && alpha <= 1.0 {
+            Some(ExponentialLowPass { alpha, state: None })
+        } else {
+            None
+        }
+    }
+
+    /// Create a new filter with the given -3 dB cutoff frequency for samples
+    /// taken at `sample_rate_hz`, for example
+    /// [`frequency_hz()`](crate::AccelOutputDataRate::frequency_hz) of the
+    /// current output data rate
+    ///
+    /// Returns `None` if the frequencies are not positive.
+    pub fn from_cutoff(cutoff_hz: f32, sample_rate_hz: f32) -> Option<Self> {
+        if !(cutoff_hz > 0.0 && sample_rate_hz > 0.0) {
+            return None;
+        }
+        let rc = 1.0 / (2.0 * core::f32::consts::PI * cutoff_hz);
+        let dt = 1.0 / sample_rate_hz;
+        Self::new(dt / (rc + dt))
+    }
+
+    /// Smoothing factor
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+}
+
+impl Filter for ExponentialLowPass {
+    fn update<T: FilterSample>(&mut self, sample: T) -> T {
+        let axes = sample.to_axes();
+        let input = [axes[0] as f32, axes[1] as f32, axes[2] as f32];
+        let state = match self.state.as_mut() {
+            Some(state) => {
+                for (state, input) in state.iter_mut().zip(input.iter()) {
+                    *state += self.alpha * (input - *state);
+                }
+                *state
+            }
+            None => *self.state.insert(input),
+        };
+        T::from_axes(round(state))
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// Second-order IIR filter
+///
+/// The coefficients of the low-pass and high-pass constructors follow the
+/// Audio EQ Cookbook with a Butterworth quality factor. The first sample
+/// initializes the filter to its steady state for that input, so there is
+/// no start-up transient.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+    state: Option<[[f32; 2]; 3]>,
+}
+
+impl Biquad {
+    /// Create a new filter from normalized coefficients
+    ///
+    /// `y[n] = b0 x[n] + b1 x[n-1] + b2 x[n-2] - a1 y[n-1] - a2 y[n-2]`
+    pub fn new(b: [f32; 3], a: [f32; 2]) -> Self {
+        Biquad { b, a, state: None }
+    }
+
+    /// Create a low-pass filter with the given -3 dB cutoff frequency for
+    /// samples taken at `sample_rate_hz`
+    ///
+    /// Returns `None` if the cutoff frequency is not between 0 and half the
+    /// sample rate.
+    pub fn low_pass(cutoff_hz: f32, sample_rate_hz: f32) -> Option<Self> {
+        let (cos, alpha) = Self::prewarp(cutoff_hz, sample_rate_hz)?;
+        let a0 = 1.0 + alpha;
+        let b1 = (1.0 - cos) / a0;
+        Some(Self::new(
+            [b1 / 2.0, b1, b1 / 2.0],
+            [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        ))
+    }
+
+    /// Create a high-pass filter with the given -3 dB cutoff frequency for
+    /// samples taken at `sample_rate_hz`
+    ///
+    /// Returns `None` if the cutoff frequency is not between 0 and half the
+    /// sample rate.
+    pub fn high_pass(cutoff_hz: f32, sample_rate_hz: f32) -> Option<Self> {
+        let (cos, alpha) = Self::prewarp(cutoff_hz, sample_rate_hz)?;
+        let a0 = 1.0 + alpha;
+        let b1 = -(1.0 + cos) / a0;
+        Some(Self::new(
+            [-b1 / 2.0, b1, -b1 / 2.0],
+            [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        ))
+    }
+
+    fn prewarp(cutoff_hz: f32, sample_rate_hz: f32) -> Option<(f32, f32)> {
+        if !(cutoff_hz > 0.0 && cutoff_hz < sample_rate_hz / 2.0) {
+            return None;
+        }
+        let omega = 2.0 * core::f32::consts::PI * cutoff_hz / sample_rate_hz;
+        let q = core::f32::consts::FRAC_1_SQRT_2;
+        Some((libm::cosf(omega), libm::sinf(omega) / (2.0 * q)))
+    }
+}
+
+impl Filter for Biquad {
+    fn update<T: FilterSample>(&mut self, sample: T) -> T {
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+        let axes = sample.to_axes();
+        let state = self.state.get_or_insert_with(|| {
+            let gain = (b0 + b1 + b2) / (1.0 + a1 + a2);
+            let mut state = [[0.0; 2]; 3];
+            for (state, x) in state.iter_mut().zip(axes.iter()) {
+                let (x, y) = (*x as f32, *x as f32 * gain);
+                let z2 = b2 * x - a2 * y;
+                *state = [b1 * x - a1 * y + z2, z2];
+            }
+            state
+        });
+        let mut output = [0.0; 3];
+        for ((output, state), x) in output.iter_mut().zip(state.iter_mut()).zip(axes.iter()) {
+            // Transposed direct form II
+            let x = *x as f32;
+            let y = b0 * x + state[0];
+            *state = [b1 * x - a1 * y + state[1], b2 * x - a2 * y];
+            *output = y;
+        }
+        T::from_axes(round(output))
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
@@ -0,0 +1,78 @@
+//! Free-fall detection with interrupt generator 1.
+//!
+//! During a free fall the acceleration on all three axes drops towards zero.
+//! The interrupt generator is configured to trigger when all axes are below
+//! the threshold (AND combination of the low events) for the minimum
+//! duration. The event is latched until it is read with
+//! [`free_fall_detected()`](Lsm303agr::free_fall_detected).
+
+use crate::{
+    interface::{ReadData, WriteData},
+    registers::{CtrlReg5A, Int1CfgA, Int1SrcA},
+    Error, InterruptPin, Lsm303agr, Register,
+};
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Configure interrupt generator 1 for free-fall detection and route it
+    /// to an interrupt pin
+    ///
+    /// A free fall is detected when the acceleration on all axes stays below
+    /// `threshold_mg` for at least `min_duration_ms`. A threshold of around
+    /// 350 mg and a duration of around 30 ms are typical values.
+    ///
+    /// The register values are derived from the current accelerometer scale
+    /// and output data rate, so these must be set before calling this.
+    /// The duration is rounded up to whole output data rate periods.
+    /// This replaces any other configuration of interrupt generator 1, like
+    /// orientation detection.
+    ///
+    /// Returns `Error::NotReady` if the accelerometer is powered down and
+    /// `Error::InvalidInputData` if the threshold or duration cannot be
+    /// represented at the current scale and output data rate.
+    pub fn enable_free_fall_detection(
+        &mut self,
+        threshold_mg: u16,
+        min_duration_ms: u16,
+        pin: InterruptPin,
+    ) -> Result<(), Error<CommE, PinE>> {
+        let threshold = self.interrupt_threshold(threshold_mg)?;
+        let odr = self.accel_odr.ok_or(Error::NotReady)?;
+        let periods_ms = u32::from(min_duration_ms) * u32::from(odr.frequency_hz());
+        let duration = periods_ms / 1000 + u32::from(periods_ms % 1000 != 0);
+        if duration > 0x7F {
+            return Err(Error::InvalidInputData);
+        }
+        let reg5 = CtrlReg5A {
+            d4d_int1: false,
+            lir_int1: true,
+            ..self.ctrl_reg5_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG5_A, reg5)?;
+        self.ctrl_reg5_a = reg5.into();
+        self.write_accel_register(Register::INT1_THS_A, threshold)?;
+        self.write_accel_register(Register::INT1_DURATION_A, duration as u8)?;
+        let cfg = Int1CfgA {
+            aoi: true,
+            zlie: true,
+            ylie: true,
+            xlie: true,
+            ..Default::default()
+        };
+        self.write_accel_register(Register::INT1_CFG_A, cfg.bits())?;
+        self.route_int1_generator(pin)
+    }
+
+    /// Check whether a free fall was detected since the last call and clear
+    /// the latched event
+    ///
+    /// Free-fall detection must have been enabled with
+    /// [`enable_free_fall_detection()`](Lsm303agr::enable_free_fall_detection).
+    pub fn free_fall_detected(&mut self) -> Result<bool, Error<CommE, PinE>> {
+        let src = self.iface.read_accel_register(Register::INT1_SRC_A)?;
+        Ok(Int1SrcA::from(src).ia)
+    }
+}
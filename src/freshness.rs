@@ -0,0 +1,113 @@
+//! Data freshness and overrun accounting.
+//!
+//! The output registers always hold the latest sample, so reading them
+//! without checking the status register returns the same sample again if no
+//! new one is available yet, and silently skips samples that were overwritten
+//! before they could be read.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    mode, Error, Lsm303agr, Measurement, Sensor, Status,
+};
+
+/// Freshness of a sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataFreshness {
+    /// The sample is new since the last read
+    New,
+    /// No new sample was available, so this sample has already been read
+    Stale,
+    /// The sample is new, but at least one sample was overwritten since the
+    /// last read
+    Overrun,
+}
+
+/// Measurement together with its freshness
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FreshMeasurement {
+    /// Measurement
+    pub data: Measurement,
+    /// Freshness of the measurement
+    pub freshness: DataFreshness,
+}
+
+/// Number of overruns observed per sensor
+///
+/// An overrun means that at least one sample was lost. The counters are
+/// updated by [`accel_data_checked()`](Lsm303agr::accel_data_checked),
+/// [`mag_data_checked()`](Lsm303agr::mag_data_checked) and the sample
+/// streams. They wrap around on overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OverrunCounters {
+    /// Accelerometer overruns
+    pub accel: u32,
+    /// Magnetometer overruns
+    pub mag: u32,
+}
+
+fn freshness(status: Status) -> DataFreshness {
+    if status.xyz_overrun {
+        DataFreshness::Overrun
+    } else if status.xyz_new_data {
+        DataFreshness::New
+    } else {
+        DataFreshness::Stale
+    }
+}
+
+impl<DI, MODE> Lsm303agr<DI, MODE> {
+    /// Overrun counters
+    pub fn overrun_counters(&self) -> OverrunCounters {
+        self.overruns
+    }
+
+    /// Reset the overrun counters to zero
+    pub fn reset_overrun_counters(&mut self) {
+        self.overruns = OverrunCounters::default();
+    }
+
+    pub(crate) fn count_overrun(&mut self, sensor: Sensor) {
+        let counter = match sensor {
+            Sensor::Accelerometer => &mut self.overruns.accel,
+            Sensor::Magnetometer => &mut self.overruns.mag,
+        };
+        *counter = counter.wrapping_add(1);
+    }
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Accelerometer data together with its freshness
+    ///
+    /// Reads the status and then the data like
+    /// [`accel_data()`](Lsm303agr::accel_data).
+    /// Overruns are added to the [`overrun_counters()`](Lsm303agr::overrun_counters).
+    pub fn accel_data_checked(&mut self) -> Result<FreshMeasurement, Error<CommE, PinE>> {
+        let freshness = freshness(self.accel_status()?);
+        let data = self.accel_data()?;
+        if freshness == DataFreshness::Overrun {
+            self.count_overrun(Sensor::Accelerometer);
+        }
+        Ok(FreshMeasurement { data, freshness })
+    }
+}
+
+impl<DI, CommE, PinE> Lsm303agr<DI, mode::MagContinuous>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Magnetometer data in nT together with its freshness
+    ///
+    /// Overruns are added to the [`overrun_counters()`](Lsm303agr::overrun_counters).
+    pub fn mag_data_checked(&mut self) -> Result<FreshMeasurement, Error<CommE, PinE>> {
+        let freshness = freshness(self.mag_status()?);
+        let data = self.mag_output()?;
+        if freshness == DataFreshness::Overrun {
+            self.count_overrun(Sensor::Magnetometer);
+        }
+        Ok(FreshMeasurement { data, freshness })
+    }
+}
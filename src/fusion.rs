@@ -0,0 +1,450 @@
+//! Attitude and heading reference system (AHRS) sensor fusion.
+//!
+//! Accelerometer and magnetometer measurements, optionally together with
+//! the angular rate from an external gyroscope, are fused into an
+//! orientation quaternion with the Madgwick or Mahony algorithm. Without a
+//! gyroscope the orientation is computed directly from the accelerometer
+//! and magnetometer and low-pass filtered.
+//!
+//! The earth frame has its X axis pointing to magnetic north, its Y axis
+//! pointing west and its Z axis pointing up. The accelerometer and
+//! magnetometer axes must be aligned, which can be ensured with
+//! [`AxisOrientation`](crate::AxisOrientation).
+
+use crate::Measurement;
+
+/// Default time constant of the low-pass filter used without gyroscope in
+/// seconds
+const DEFAULT_SMOOTHING_S: f32 = 0.1;
+
+/// Orientation quaternion
+///
+/// Rotates vectors from the sensor frame into the earth frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    /// Scalar part
+    pub w: f32,
+    /// X component of the vector part
+    pub x: f32,
+    /// Y component of the vector part
+    pub y: f32,
+    /// Z component of the vector part
+    pub z: f32,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion {
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+}
+
+impl Quaternion {
+    /// Euler angles of the orientation
+    pub fn euler_angles(&self) -> EulerAngles {
+        let Quaternion { w, x, y, z } = *self;
+        let sin_pitch = (2.0 * (x * z - w * y)).clamp(-1.0, 1.0);
+        EulerAngles {
+            roll: libm::atan2f(2.0 * (w * x + y * z), 1.0 - 2.0 * (x * x + y * y)).to_degrees(),
+            pitch: libm::asinf(sin_pitch).to_degrees(),
+            yaw: -libm::atan2f(2.0 * (w * z + x * y), 1.0 - 2.0 * (y * y + z * z)).to_degrees(),
+        }
+    }
+
+    fn normalized(self) -> Self {
+        let norm = libm::sqrtf(self.dot(&self));
+        if norm == 0.0 {
+            return Quaternion::default();
+        }
+        Quaternion {
+            w: self.w / norm,
+            x: self.x / norm,
+            y: self.y / norm,
+            z: self.z / norm,
+        }
+    }
+
+    fn dot(&self, other: &Quaternion) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Quaternion of the rotation matrix with the given rows
+    fn from_rows(r: [[f32; 3]; 3]) -> Self {
+        let trace = r[0][0] + r[1][1] + r[2][2];
+        let q = if trace > 0.0 {
+            let s = 2.0 * libm::sqrtf(trace + 1.0);
+            Quaternion {
+                w: s / 4.0,
+                x: (r[2][1] - r[1][2]) / s,
+                y: (r[0][2] - r[2][0]) / s,
+                z: (r[1][0] - r[0][1]) / s,
+            }
+        } else if r[0][0] > r[1][1] && r[0][0] > r[2][2] {
+            let s = 2.0 * libm::sqrtf(1.0 + r[0][0] - r[1][1] - r[2][2]);
+            Quaternion {
+                w: (r[2][1] - r[1][2]) / s,
+                x: s / 4.0,
+                y: (r[0][1] + r[1][0]) / s,
+                z: (r[0][2] + r[2][0]) / s,
+            }
+        } else if r[1][1] > r[2][2] {
+            let s = 2.0 * libm::sqrtf(1.0 + r[1][1] - r[0][0] - r[2][2]);
+            Quaternion {
+                w: (r[0][2] - r[2][0]) / s,
+                x: (r[0][1] + r[1][0]) / s,
+                y: s / 4.0,
+                z: (r[1][2] + r[2][1]) / s,
+            }
+        } else {
+            let s = 2.0 * libm::sqrtf(1.0 + r[2][2] - r[0][0] - r[1][1]);
+            Quaternion {
+                w: (r[1][0] - r[0][1]) / s,
+                x: (r[0][2] + r[2][0]) / s,
+                y: (r[1][2] + r[2][1]) / s,
+                z: s / 4.0,
+            }
+        };
+        q.normalized()
+    }
+}
+
+/// Euler angles in degrees
+///
+/// The angles are applied in yaw, pitch, roll order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EulerAngles {
+    /// Rotation around the X axis, positive when the Y axis points upwards
+    /// (-180° to 180°)
+    pub roll: f32,
+    /// Angle of the X axis above the horizontal plane (-90° to 90°)
+    pub pitch: f32,
+    /// Heading of the X axis from magnetic north, positive towards east
+    /// (-180° to 180°)
+    pub yaw: f32,
+}
+
+/// Sensor fusion algorithm used with a gyroscope
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FusionAlgorithm {
+    /// Madgwick gradient descent filter
+    Madgwick {
+        /// Gain of the accelerometer/magnetometer correction in rad/s
+        beta: f32,
+    },
+    /// Mahony complementary filter
+    Mahony {
+        /// Proportional gain
+        kp: f32,
+        /// Integral gain, which compensates the gyroscope bias
+        ki: f32,
+    },
+}
+
+impl Default for FusionAlgorithm {
+    fn default() -> Self {
+        FusionAlgorithm::Madgwick { beta: 0.1 }
+    }
+}
+
+/// Attitude and heading reference system
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ahrs {
+    algorithm: FusionAlgorithm,
+    smoothing_s: f32,
+    q: Quaternion,
+    integral_error: [f32; 3],
+    initialized: bool,
+}
+
+impl Default for Ahrs {
+    fn default() -> Self {
+        Self::new(FusionAlgorithm::default())
+    }
+}
+
+impl Ahrs {
+    /// Create a new AHRS using the given algorithm when a gyroscope
+    /// measurement is available
+    pub fn new(algorithm: FusionAlgorithm) -> Self {
+        Ahrs {
+            algorithm,
+            smoothing_s: DEFAULT_SMOOTHING_S,
+            q: Quaternion::default(),
+            integral_error: [0.0; 3],
+            initialized: false,
+        }
+    }
+
+    /// Set the time constant in seconds of the low-pass filter used when no
+    /// gyroscope measurement is available (default 0.1 s)
+    pub fn with_smoothing(self, time_constant_s: f32) -> Self {
+        Ahrs {
+            smoothing_s: time_constant_s,
+            ..self
+        }
+    }
+
+    /// Current orientation
+    pub fn quaternion(&self) -> Quaternion {
+        self.q
+    }
+
+    /// Current orientation as Euler angles
+    pub fn euler_angles(&self) -> EulerAngles {
+        self.q.euler_angles()
+    }
+
+    /// Reset the orientation so that it is initialized again from the next
+    /// measurements
+    pub fn reset(&mut self) {
+        self.q = Quaternion::default();
+        self.integral_error = [0.0; 3];
+        self.initialized = false;
+    }
+
+    /// Update the orientation
+    ///
+    /// `accel` is an accelerometer measurement in any unit (for example mg),
+    /// `mag` a magnetometer measurement in any unit (for example nT),
+    /// `gyro` the angular rate around the sensor axes in rad/s if available
+    /// and `dt` the time since the last update in seconds.
+    ///
+    /// The first update initializes the orientation from the accelerometer
+    /// and magnetometer. Measurements with zero magnitude are ignored.
+    ///
+    /// Returns the updated orientation.
+    pub fn update(
+        &mut self,
+        accel: Measurement,
+        mag: Measurement,
+        gyro: Option<[f32; 3]>,
+        dt: f32,
+    ) -> Quaternion {
+        let a = normalize(to_vector(accel));
+        let m = normalize(to_vector(mag));
+        if !self.initialized {
+            if let (Some(a), Some(m)) = (a, m) {
+                if let Some(q) = orientation_from(a, m) {
+                    self.q = q;
+                    self.initialized = true;
+                }
+            }
+            return self.q;
+        }
+        match (gyro, self.algorithm) {
+            (Some(g), FusionAlgorithm::Madgwick { beta }) => self.madgwick(g, a, m, beta, dt),
+            (Some(g), FusionAlgorithm::Mahony { kp, ki }) => self.mahony(g, a, m, kp, ki, dt),
+            (None, _) => {
+                if let (Some(a), Some(m)) = (a, m) {
+                    if let Some(target) = orientation_from(a, m) {
+                        self.smooth(target, dt);
+                    }
+                }
+            }
+        }
+        self.q
+    }
+
+    fn smooth(&mut self, target: Quaternion, dt: f32) {
+        let alpha = dt / (self.smoothing_s + dt);
+        // q and -q are the same orientation: interpolate along the short way.
+        let sign = if self.q.dot(&target) < 0.0 { -1.0 } else { 1.0 };
+        let q = self.q;
+        self.q = Quaternion {
+            w: q.w + alpha * (sign * target.w - q.w),
+            x: q.x + alpha * (sign * target.x - q.x),
+            y: q.y + alpha * (sign * target.y - q.y),
+            z: q.z + alpha * (sign * target.z - q.z),
+        }
+        .normalized();
+    }
+
+    fn madgwick(
+        &mut self,
+        g: [f32; 3],
+        a: Option<[f32; 3]>,
+        m: Option<[f32; 3]>,
+        beta: f32,
+        dt: f32,
+    ) {
+        let Quaternion {
+            w: q0,
+            x: q1,
+            y: q2,
+            z: q3,
+        } = self.q;
+        // Rate of change of the quaternion from the gyroscope
+        let mut q_dot = [
+            0.5 * (-q1 * g[0] - q2 * g[1] - q3 * g[2]),
+            0.5 * (q0 * g[0] + q2 * g[2] - q3 * g[1]),
+            0.5 * (q0 * g[1] - q1 * g[2] + q3 * g[0]),
+            0.5 * (q0 * g[2] + q1 * g[1] - q2 * g[0]),
+        ];
+        if let (Some([ax, ay, az]), Some([mx, my, mz])) = (a, m) {
+            // Reference direction of the earth magnetic field
+            let h = rotate(self.q, [mx, my, mz]);
+            let bx = libm::sqrtf(h[0] * h[0] + h[1] * h[1]);
+            let bz = h[2];
+            // Objective function and its gradient
+            let f = [
+                2.0 * (q1 * q3 - q0 * q2) - ax,
+                2.0 * (q0 * q1 + q2 * q3) - ay,
+                1.0 - 2.0 * (q1 * q1 + q2 * q2) - az,
+                2.0 * bx * (0.5 - q2 * q2 - q3 * q3) + 2.0 * bz * (q1 * q3 - q0 * q2) - mx,
+                2.0 * bx * (q1 * q2 - q0 * q3) + 2.0 * bz * (q0 * q1 + q2 * q3) - my,
+                2.0 * bx * (q0 * q2 + q1 * q3) + 2.0 * bz * (0.5 - q1 * q1 - q2 * q2) - mz,
+            ];
+            let jacobian = [
+                [-2.0 * q2, 2.0 * q3, -2.0 * q0, 2.0 * q1],
+                [2.0 * q1, 2.0 * q0, 2.0 * q3, 2.0 * q2],
+                [0.0, -4.0 * q1, -4.0 * q2, 0.0],
+                [
+                    -2.0 * bz * q2,
+                    2.0 * bz * q3,
+                    -4.0 * bx * q2 - 2.0 * bz * q0,
+                    -4.0 * bx * q3 + 2.0 * bz * q1,
+                ],
+                [
+                    -2.0 * bx * q3 + 2.0 * bz * q1,
+                    2.0 * bx * q2 + 2.0 * bz * q0,
+                    2.0 * bx * q1 + 2.0 * bz * q3,
+                    -2.0 * bx * q0 + 2.0 * bz * q2,
+                ],
+                [
+                    2.0 * bx * q2,
+                    2.0 * bx * q3 - 4.0 * bz * q1,
+                    2.0 * bx * q0 - 4.0 * bz * q2,
+                    2.0 * bx * q1,
+                ],
+            ];
+            let mut step = [0.0; 4];
+            for (row, f) in jacobian.iter().zip(f.iter()) {
+                for (s, j) in step.iter_mut().zip(row.iter()) {
+                    *s += j * f;
+                }
+            }
+            let norm = libm::sqrtf(step.iter().map(|s| s * s).sum());
+            if norm > 0.0 {
+                for (q_dot, s) in q_dot.iter_mut().zip(step.iter()) {
+                    *q_dot -= beta * s / norm;
+                }
+            }
+        }
+        self.integrate(q_dot, dt);
+    }
+
+    fn mahony(
+        &mut self,
+        g: [f32; 3],
+        a: Option<[f32; 3]>,
+        m: Option<[f32; 3]>,
+        kp: f32,
+        ki: f32,
+        dt: f32,
+    ) {
+        let mut g = g;
+        if let (Some(a), Some(m)) = (a, m) {
+            let q = self.q;
+            // Estimated directions of gravity and magnetic field
+            let up = rotate_back(q, [0.0, 0.0, 1.0]);
+            let h = rotate(q, m);
+            let b = [libm::sqrtf(h[0] * h[0] + h[1] * h[1]), 0.0, h[2]];
+            let field = rotate_back(q, b);
+            // Error is the cross product between measured and estimated
+            // directions.
+            let ea = cross(a, up);
+            let em = cross(m, field);
+            let error = [ea[0] + em[0], ea[1] + em[1], ea[2] + em[2]];
+            for i in 0..3 {
+                self.integral_error[i] += ki * error[i] * dt;
+                g[i] += kp * error[i] + self.integral_error[i];
+            }
+        }
+        let Quaternion {
+            w: q0,
+            x: q1,
+            y: q2,
+            z: q3,
+        } = self.q;
+        let q_dot = [
+            0.5 * (-q1 * g[0] - q2 * g[1] - q3 * g[2]),
+            0.5 * (q0 * g[0] + q2 * g[2] - q3 * g[1]),
+            0.5 * (q0 * g[1] - q1 * g[2] + q3 * g[0]),
+            0.5 * (q0 * g[2] + q1 * g[1] - q2 * g[0]),
+        ];
+        self.integrate(q_dot, dt);
+    }
+
+    fn integrate(&mut self, q_dot: [f32; 4], dt: f32) {
+        self.q = Quaternion {
+            w: self.q.w + q_dot[0] * dt,
+            x: self.q.x + q_dot[1] * dt,
+            y: self.q.y + q_dot[2] * dt,
+            z: self.q.z + q_dot[3] * dt,
+        }
+        .normalized();
+    }
+}
+
+/// Orientation computed directly from normalized accelerometer and
+/// magnetometer vectors
+fn orientation_from(a: [f32; 3], m: [f32; 3]) -> Option<Quaternion> {
+    // Rows of the rotation matrix are the earth axes in sensor coordinates.
+    let west = normalize(cross(a, m))?;
+    let north = cross(west, a);
+    Some(Quaternion::from_rows([north, west, a]))
+}
+
+/// Rotate a vector from the sensor frame into the earth frame
+fn rotate(q: Quaternion, v: [f32; 3]) -> [f32; 3] {
+    let Quaternion { w, x, y, z } = q;
+    [
+        (1.0 - 2.0 * (y * y + z * z)) * v[0]
+            + 2.0 * (x * y - w * z) * v[1]
+            + 2.0 * (x * z + w * y) * v[2],
+        2.0 * (x * y + w * z) * v[0]
+            + (1.0 - 2.0 * (x * x + z * z)) * v[1]
+            + 2.0 * (y * z - w * x) * v[2],
+        2.0 * (x * z - w * y) * v[0]
+            + 2.0 * (y * z + w * x) * v[1]
+            + (1.0 - 2.0 * (x * x + y * y)) * v[2],
+    ]
+}
+
+/// Rotate a vector from the earth frame into the sensor frame
+fn rotate_back(q: Quaternion, v: [f32; 3]) -> [f32; 3] {
+    rotate(
+        Quaternion {
+            w: q.w,
+            x: -q.x,
+            y: -q.y,
+            z: -q.z,
+        },
+        v,
+    )
+}
+
+fn to_vector(m: Measurement) -> [f32; 3] {
+    [m.x as f32, m.y as f32, m.z as f32]
+}
+
+fn normalize(v: [f32; 3]) -> Option<[f32; 3]> {
+    let norm = libm::sqrtf(v[0] * v[0] + v[1] * v[1] + v[2] * v[2]);
+    if norm == 0.0 {
+        None
+    } else {
+        Some([v[0] / norm, v[1] / norm, v[2] / norm])
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
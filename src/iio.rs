@@ -0,0 +1,326 @@
+//! Linux IIO backend.
+//!
+//! When the kernel `st_accel` and `st_magn` drivers own the LSM303AGR, the
+//! device cannot be accessed through `/dev/i2c-*`. Instead, the kernel
+//! exposes the accelerometer and the magnetometer as two IIO devices in
+//! `/sys/bus/iio/devices/iio:deviceN`, named `lsm303agr_accel` and
+//! `lsm303agr_magn`. [`IioDevice`] reads the raw values, scales and sampling
+//! frequencies of those devices and converts them into the same
+//! [`Measurement`] units as the driver: mg for the accelerometer and nT for
+//! the magnetometer.
+//!
+//! This module is only available with the `iio` feature.
+//!
+//! ```no_run
+//! use lsm303agr::{iio::IioDevice, AccelOutputDataRate};
+//!
+//! let mut sensor = IioDevice::find().unwrap();
+//! sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+//! let data = sensor.accel_data().unwrap();
+//! println!("Acceleration: x {} y {} z {}", data.x, data.y, data.z);
+//! ```
+
+use crate::{
+    AccelOutputDataRate, AccelScale, MagOutputDataRate, Measurement, Sensor, UnscaledMeasurement,
+};
+use core::convert::TryFrom;
+use std::{
+    fmt, format, fs, io,
+    path::{Path, PathBuf},
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Default location of the IIO devices
+pub const IIO_DEVICES_DIR: &str = "/sys/bus/iio/devices";
+/// IIO device name of the accelerometer
+pub const ACCEL_DEVICE_NAME: &str = "lsm303agr_accel";
+/// IIO device name of the magnetometer
+pub const MAG_DEVICE_NAME: &str = "lsm303agr_magn";
+
+/// Standard gravity in m/s²
+const STANDARD_GRAVITY: f64 = 9.80665;
+/// nT per gauss
+const NT_PER_GAUSS: f64 = 100_000.0;
+
+/// IIO backend error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum IioError {
+    /// Error accessing a sysfs attribute
+    Io {
+        /// Path of the attribute
+        path: PathBuf,
+        /// Underlying error
+        error: io::Error,
+    },
+    /// The content of a sysfs attribute could not be parsed
+    Parse {
+        /// Path of the attribute
+        path: PathBuf,
+    },
+    /// No IIO device of the sensor was found
+    NotFound(Sensor),
+    /// The requested setting is not supported by the kernel driver
+    Unsupported,
+}
+
+impl fmt::Display for IioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IioError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            IioError::Parse { path } => write!(f, "{}: invalid value", path.display()),
+            IioError::NotFound(Sensor::Accelerometer) => {
+                write!(f, "no {} IIO device found", ACCEL_DEVICE_NAME)
+            }
+            IioError::NotFound(Sensor::Magnetometer) => {
+                write!(f, "no {} IIO device found", MAG_DEVICE_NAME)
+            }
+            IioError::Unsupported => write!(f, "setting not supported by the kernel driver"),
+        }
+    }
+}
+
+impl std::error::Error for IioError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IioError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// LSM303AGR accessed through the Linux IIO sysfs interface
+#[derive(Debug, Clone, PartialEq)]
+pub struct IioDevice {
+    accel_dir: PathBuf,
+    mag_dir: PathBuf,
+}
+
+impl IioDevice {
+    /// Create a new instance using the given accelerometer and magnetometer
+    /// IIO device directories, for example `/sys/bus/iio/devices/iio:device0`
+    pub fn new(accel_dir: impl Into<PathBuf>, mag_dir: impl Into<PathBuf>) -> Self {
+        IioDevice {
+            accel_dir: accel_dir.into(),
+            mag_dir: mag_dir.into(),
+        }
+    }
+
+    /// Find the accelerometer and magnetometer in `/sys/bus/iio/devices`
+    pub fn find() -> Result<Self, IioError> {
+        Self::find_in(IIO_DEVICES_DIR)
+    }
+
+    /// Find the accelerometer and magnetometer IIO devices by their names
+    /// in the given directory
+    ///
+    /// If there are several devices of the same kind, the one with the
+    /// lowest device number is used.
+    pub fn find_in(devices_dir: impl AsRef<Path>) -> Result<Self, IioError> {
+        let devices_dir = devices_dir.as_ref();
+        let entries = fs::read_dir(devices_dir).map_err(|error| IioError::Io {
+            path: devices_dir.into(),
+            error,
+        })?;
+        let mut dirs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        dirs.sort_by_key(|dir| device_number(dir));
+        let find = |name: &str| {
+            dirs.iter()
+                .find(|dir| {
+                    fs::read_to_string(dir.join("name"))
+                        .map(|content| content.trim() == name)
+                        .unwrap_or(false)
+                })
+                .cloned()
+        };
+        let accel_dir = find(ACCEL_DEVICE_NAME).ok_or(IioError::NotFound(Sensor::Accelerometer))?;
+        let mag_dir = find(MAG_DEVICE_NAME).ok_or(IioError::NotFound(Sensor::Magnetometer))?;
+        Ok(Self::new(accel_dir, mag_dir))
+    }
+
+    /// Accelerometer IIO device directory
+    pub fn accel_dir(&self) -> &Path {
+        &self.accel_dir
+    }
+
+    /// Magnetometer IIO device directory
+    pub fn mag_dir(&self) -> &Path {
+        &self.mag_dir
+    }
+
+    /// Get measured acceleration in mg
+    pub fn accel_data(&self) -> Result<Measurement, IioError> {
+        let scale: f64 = read_value(&self.accel_dir.join("in_accel_scale"))?;
+        let raw = self.accel_data_raw()?;
+        let to_mg = |raw: i32| round(f64::from(raw) * scale / STANDARD_GRAVITY * 1000.0);
+        Ok(Measurement {
+            x: to_mg(raw[0]),
+            y: to_mg(raw[1]),
+            z: to_mg(raw[2]),
+        })
+    }
+
+    /// Get raw acceleration as reported by the kernel driver
+    pub fn accel_data_unscaled(&self) -> Result<UnscaledMeasurement, IioError> {
+        let raw = self.accel_data_raw()?;
+        unscaled(&self.accel_dir, "accel", raw)
+    }
+
+    /// Get measured magnetic field in nT
+    pub fn mag_data(&self) -> Result<Measurement, IioError> {
+        let raw = self.mag_data_raw()?;
+        let mut axes = [0; 3];
+        for (i, axis) in ["x", "y", "z"].iter().enumerate() {
+            let scale = self.mag_scale(axis)?;
+            axes[i] = round(f64::from(raw[i]) * scale * NT_PER_GAUSS);
+        }
+        Ok(Measurement {
+            x: axes[0],
+            y: axes[1],
+            z: axes[2],
+        })
+    }
+
+    /// Get raw magnetic field as reported by the kernel driver
+    pub fn mag_data_unscaled(&self) -> Result<UnscaledMeasurement, IioError> {
+        let raw = self.mag_data_raw()?;
+        unscaled(&self.mag_dir, "magn", raw)
+    }
+
+    /// Get the accelerometer sampling frequency in Hz
+    pub fn accel_sampling_frequency(&self) -> Result<f32, IioError> {
+        read_value(&self.accel_dir.join("sampling_frequency"))
+    }
+
+    /// Get the magnetometer sampling frequency in Hz
+    pub fn mag_sampling_frequency(&self) -> Result<f32, IioError> {
+        read_value(&self.mag_dir.join("sampling_frequency"))
+    }
+
+    /// Set the accelerometer output data rate
+    ///
+    /// The kernel driver selects the power mode, so the rates only available
+    /// in low-power mode may be rejected.
+    pub fn set_accel_odr(&mut self, odr: AccelOutputDataRate) -> Result<(), IioError> {
+        write_attribute(
+            &self.accel_dir.join("sampling_frequency"),
+            &odr.frequency_hz().to_string(),
+        )
+    }
+
+    /// Set the magnetometer output data rate
+    pub fn set_mag_odr(&mut self, odr: MagOutputDataRate) -> Result<(), IioError> {
+        write_attribute(
+            &self.mag_dir.join("sampling_frequency"),
+            &odr.frequency_hz().to_string(),
+        )
+    }
+
+    /// Set the accelerometer full scale
+    ///
+    /// The scale is selected among the ones listed in
+    /// `in_accel_scale_available`, which the kernel driver lists from the
+    /// smallest (±2g) to the largest (±16g) full scale.
+    ///
+    /// Returns `IioError::Unsupported` if the kernel driver does not list
+    /// four scales.
+    pub fn set_accel_scale(&mut self, scale: AccelScale) -> Result<(), IioError> {
+        let path = self.accel_dir.join("in_accel_scale_available");
+        let content = read_attribute(&path)?;
+        let mut available = content
+            .split_whitespace()
+            .map(|value| value.parse::<f64>().map(|v| (v, value)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| IioError::Parse { path })?;
+        if available.len() != 4 {
+            return Err(IioError::Unsupported);
+        }
+        available.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let index = match scale {
+            AccelScale::G2 => 0,
+            AccelScale::G4 => 1,
+            AccelScale::G8 => 2,
+            AccelScale::G16 => 3,
+        };
+        write_attribute(&self.accel_dir.join("in_accel_scale"), available[index].1)
+    }
+
+    fn accel_data_raw(&self) -> Result<[i32; 3], IioError> {
+        read_axes(&self.accel_dir, "accel")
+    }
+
+    fn mag_data_raw(&self) -> Result<[i32; 3], IioError> {
+        read_axes(&self.mag_dir, "magn")
+    }
+
+    /// Scale of a magnetometer axis in gauss
+    ///
+    /// Depending on the kernel version, the scale is either shared by all
+    /// axes or given per axis.
+    fn mag_scale(&self, axis: &str) -> Result<f64, IioError> {
+        let per_axis = self.mag_dir.join(format!("in_magn_{}_scale", axis));
+        if per_axis.exists() {
+            read_value(&per_axis)
+        } else {
+            read_value(&self.mag_dir.join("in_magn_scale"))
+        }
+    }
+}
+
+fn device_number(dir: &Path) -> u32 {
+    dir.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("iio:device"))
+        .and_then(|number| number.parse().ok())
+        .unwrap_or(u32::MAX)
+}
+
+fn read_attribute(path: &Path) -> Result<String, IioError> {
+    fs::read_to_string(path).map_err(|error| IioError::Io {
+        path: path.into(),
+        error,
+    })
+}
+
+fn write_attribute(path: &Path, value: &str) -> Result<(), IioError> {
+    fs::write(path, value).map_err(|error| IioError::Io {
+        path: path.into(),
+        error,
+    })
+}
+
+fn read_value<T: core::str::FromStr>(path: &Path) -> Result<T, IioError> {
+    read_attribute(path)?
+        .trim()
+        .parse()
+        .map_err(|_| IioError::Parse { path: path.into() })
+}
+
+fn read_axes(dir: &Path, channel: &str) -> Result<[i32; 3], IioError> {
+    let mut raw = [0; 3];
+    for (i, axis) in ["x", "y", "z"].iter().enumerate() {
+        raw[i] = read_value(&dir.join(format!("in_{}_{}_raw", channel, axis)))?;
+    }
+    Ok(raw)
+}
+
+fn unscaled(dir: &Path, channel: &str, raw: [i32; 3]) -> Result<UnscaledMeasurement, IioError> {
+    let mut axes = [0; 3];
+    for (i, axis) in ["x", "y", "z"].iter().enumerate() {
+        axes[i] = i16::try_from(raw[i]).map_err(|_| IioError::Parse {
+            path: dir.join(format!("in_{}_{}_raw", channel, axis)),
+        })?;
+    }
+    Ok(UnscaledMeasurement {
+        x: axes[0],
+        y: axes[1],
+        z: axes[2],
+    })
+}
+
+fn round(value: f64) -> i32 {
+    libm::round(value) as i32
+}
@@ -0,0 +1,192 @@
+//! Shock and impact logging.
+//!
+//! Interrupt generator 2 detects accelerations above a threshold on any axis
+//! and signals them on the INT2 pin. The FIFO runs in stream-to-FIFO mode
+//! triggered by INT2: it continuously keeps the latest samples until the
+//! impact happens and then stops once it is full, so that it holds a window
+//! of samples around the impact which can be read afterwards.
+//!
+//! At high output data rates like `AccelOutputDataRate::Khz1_344` the whole
+//! window only spans a few tens of milliseconds, so use `AccelScale::G16` to
+//! avoid clipping the peak of strong impacts.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    registers::{CtrlReg5A, CtrlReg6A, FifoCtrlRegA, Int2CfgA, Int2SrcA},
+    AccelOutputDataRate, Error, FifoMode, Lsm303agr, Measurement, Register, FIFO_CAPACITY,
+};
+use embedded_hal::blocking::delay::DelayUs;
+
+/// Impact logger
+///
+/// Configure the device with
+/// [`enable_impact_logger()`](Lsm303agr::enable_impact_logger) and poll for
+/// impacts with [`read_impact()`](Lsm303agr::read_impact).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImpactLogger {
+    threshold_mg: u16,
+}
+
+impl ImpactLogger {
+    /// Create a new impact logger for accelerations above `threshold_mg`
+    /// on any axis
+    ///
+    /// The interrupt generator rounds the threshold to its resolution (186 mg
+    /// at ±16g).
+    pub fn new(threshold_mg: u16) -> Self {
+        ImpactLogger { threshold_mg }
+    }
+
+    /// Impact threshold in mg
+    pub fn threshold_mg(&self) -> u16 {
+        self.threshold_mg
+    }
+
+    /// Analyze a window of consecutive accelerometer measurements in mg
+    /// taken at the given output data rate
+    ///
+    /// The trigger is the first sample above the threshold on any axis.
+    /// At most `FIFO_CAPACITY` samples are considered.
+    /// Returns `None` if no sample is above the threshold.
+    pub fn analyze(&self, samples: &[Measurement], odr: AccelOutputDataRate) -> Option<Impact> {
+        let samples = &samples[..samples.len().min(FIFO_CAPACITY)];
+        let threshold = i32::from(self.threshold_mg);
+        let over = |m: &Measurement| {
+            m.x.abs() > threshold || m.y.abs() > threshold || m.z.abs() > threshold
+        };
+        let trigger_index = samples.iter().position(over)?;
+        let (peak, peak_magnitude_mg) = samples
+            .iter()
+            .map(|m| (*m, magnitude(m)))
+            .max_by_key(|(_, magnitude)| *magnitude)?;
+        let samples_over = samples.iter().filter(|m| over(m)).count() as u32;
+        let mut window = [Measurement::default(); FIFO_CAPACITY];
+        window[..samples.len()].copy_from_slice(samples);
+        Some(Impact {
+            peak,
+            peak_magnitude_mg,
+            duration_us: samples_over * 1_000_000 / u32::from(odr.frequency_hz()),
+            trigger_index,
+            window,
+            len: samples.len(),
+        })
+    }
+}
+
+fn magnitude(m: &Measurement) -> u32 {
+    let (x, y, z) = (f64::from(m.x), f64::from(m.y), f64::from(m.z));
+    libm::round(libm::sqrt(x * x + y * y + z * z)) as u32
+}
+
+/// Recorded impact
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Impact {
+    /// Acceleration vector with the highest magnitude in mg. This gives the
+    /// direction of the impact.
+    pub peak: Measurement,
+    /// Magnitude of the peak acceleration vector in mg
+    pub peak_magnitude_mg: u32,
+    /// Time spent above the threshold on any axis in microseconds
+    pub duration_us: u32,
+    /// Index of the first sample above the threshold in the window
+    pub trigger_index: usize,
+    window: [Measurement; FIFO_CAPACITY],
+    len: usize,
+}
+
+impl Impact {
+    /// All samples recorded around the impact in mg, oldest first
+    pub fn window(&self) -> &[Measurement] {
+        &self.window[..self.len]
+    }
+
+    /// Samples recorded before the trigger in mg
+    pub fn pre_trigger(&self) -> &[Measurement] {
+        &self.window[..self.trigger_index]
+    }
+
+    /// Samples recorded from the trigger on in mg
+    pub fn post_trigger(&self) -> &[Measurement] {
+        &self.window[self.trigger_index..self.len]
+    }
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Configure interrupt generator 2, the INT2 pin and the FIFO for impact
+    /// logging
+    ///
+    /// The accelerometer scale and output data rate must be set before
+    /// calling this. This replaces any other FIFO configuration.
+    ///
+    /// Returns `Error::NotReady` if the accelerometer is powered down and
+    /// `Error::InvalidInputData` if the threshold cannot be represented at
+    /// the current scale.
+    pub fn enable_impact_logger(
+        &mut self,
+        logger: &ImpactLogger,
+    ) -> Result<(), Error<CommE, PinE>> {
+        let threshold = self.interrupt_threshold(logger.threshold_mg)?;
+        if self.accel_odr.is_none() {
+            return Err(Error::NotReady);
+        }
+        let reg5 = CtrlReg5A {
+            d4d_int2: false,
+            lir_int2: true,
+            ..self.ctrl_reg5_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG5_A, reg5)?;
+        self.ctrl_reg5_a = reg5.into();
+        self.write_accel_register(Register::INT2_THS_A, threshold)?;
+        self.write_accel_register(Register::INT2_DURATION_A, 0)?;
+        let cfg = Int2CfgA {
+            zhie: true,
+            yhie: true,
+            xhie: true,
+            ..Default::default()
+        };
+        self.write_accel_register(Register::INT2_CFG_A, cfg.bits())?;
+        let reg6 = CtrlReg6A {
+            i2_int2: true,
+            ..self.ctrl_reg6_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG6_A, reg6)?;
+        self.ctrl_reg6_a = reg6.into();
+        self.configure_fifo(FifoMode::StreamToFifo, 0, true)
+    }
+
+    /// Check for an impact and read it if one happened
+    ///
+    /// If an impact was detected, this waits until the post-trigger part of
+    /// the window has been recorded, reads the window from the FIFO and
+    /// re-arms the FIFO for the next impact.
+    ///
+    /// Returns `Error::NotReady` if the accelerometer is powered down.
+    pub fn read_impact<D: DelayUs<u32>>(
+        &mut self,
+        logger: &ImpactLogger,
+        delay: &mut D,
+    ) -> Result<Option<Impact>, Error<CommE, PinE>> {
+        let odr = self.accel_odr.ok_or(Error::NotReady)?;
+        let src = self.iface.read_accel_register(Register::INT2_SRC_A)?;
+        if !Int2SrcA::from(src).ia {
+            return Ok(None);
+        }
+        delay.delay_us(FIFO_CAPACITY as u32 * 1_000_000 / u32::from(odr.frequency_hz()));
+        let mut window = [Measurement::default(); FIFO_CAPACITY];
+        let len = self.fifo_data(&mut window)?;
+        // Going through bypass mode empties the FIFO and restarts streaming.
+        let fifo_ctrl = self.fifo_ctrl_reg_a.bits;
+        let bypass = FifoCtrlRegA {
+            fm: 0,
+            ..fifo_ctrl.into()
+        };
+        self.write_accel_register(Register::FIFO_CTRL_REG_A, bypass.bits())?;
+        self.write_accel_register(Register::FIFO_CTRL_REG_A, fifo_ctrl)?;
+        Ok(logger.analyze(&window[..len], odr))
+    }
+}
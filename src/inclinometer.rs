@@ -0,0 +1,197 @@
+//! Pitch, roll and tilt computation from accelerometer data.
+//!
+//! The angles are only meaningful while the device is not accelerating, that
+//! is, when the accelerometer only measures gravity. The magnitude of the
+//! acceleration is compared against 1g to detect this.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    AxisOrientation, Error, Lsm303agr, Measurement,
+};
+
+/// Default tolerance around 1g in mg for the measurement to count as steady
+const DEFAULT_TOLERANCE_MG: u16 = 100;
+
+/// Inclination in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Inclination {
+    /// Angle of the X axis above the horizontal plane (-90° to 90°)
+    pub pitch: f32,
+    /// Rotation around the X axis, positive when the Y axis points upwards
+    /// (-180° to 180°)
+    pub roll: f32,
+    /// Angle between the Z axis and the vertical (0° to 180°)
+    pub tilt: f32,
+    /// Deviation of the acceleration magnitude from 1g in mg
+    pub g_deviation: i32,
+    /// Whether the acceleration magnitude is within the tolerance around 1g.
+    /// The angles are unreliable if the device is moving.
+    pub steady: bool,
+}
+
+/// Inclination in centidegrees (hundredths of a degree) computed with
+/// integer arithmetic only
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InclinationFixed {
+    /// Angle of the X axis above the horizontal plane (-9000 to 9000)
+    pub pitch: i32,
+    /// Rotation around the X axis, positive when the Y axis points upwards
+    /// (-18000 to 18000)
+    pub roll: i32,
+    /// Angle between the Z axis and the vertical (0 to 18000)
+    pub tilt: i32,
+    /// Deviation of the acceleration magnitude from 1g in mg
+    pub g_deviation: i32,
+    /// Whether the acceleration magnitude is within the tolerance around 1g.
+    /// The angles are unreliable if the device is moving.
+    pub steady: bool,
+}
+
+/// Inclinometer
+///
+/// Computes the inclination of the device from accelerometer measurements in
+/// mg. The device axes are given by the mounting orientation: the Z axis
+/// points upwards when the device lies flat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Inclinometer {
+    orientation: AxisOrientation,
+    tolerance_mg: u16,
+}
+
+impl Default for Inclinometer {
+    fn default() -> Self {
+        Self::new(AxisOrientation::default())
+    }
+}
+
+impl Inclinometer {
+    /// Create a new inclinometer for the given mounting orientation
+    pub fn new(orientation: AxisOrientation) -> Self {
+        Inclinometer {
+            orientation,
+            tolerance_mg: DEFAULT_TOLERANCE_MG,
+        }
+    }
+
+    /// Set the tolerance around 1g in mg for a measurement to count as steady
+    pub fn with_tolerance(self, tolerance_mg: u16) -> Self {
+        Inclinometer {
+            tolerance_mg,
+            ..self
+        }
+    }
+
+    /// Compute the inclination in degrees
+    pub fn compute(&self, measurement: Measurement) -> Inclination {
+        let a = self.orientation.apply(measurement);
+        let (x, y, z) = (a.x as f32, a.y as f32, a.z as f32);
+        let horizontal_yz = libm::sqrtf(y * y + z * z);
+        let horizontal_xy = libm::sqrtf(x * x + y * y);
+        let magnitude = libm::sqrtf(x * x + y * y + z * z);
+        let g_deviation = libm::roundf(magnitude - 1000.0) as i32;
+        Inclination {
+            pitch: libm::atan2f(x, horizontal_yz).to_degrees(),
+            roll: libm::atan2f(y, z).to_degrees(),
+            tilt: libm::atan2f(horizontal_xy, z).to_degrees(),
+            g_deviation,
+            steady: g_deviation.abs() <= i32::from(self.tolerance_mg),
+        }
+    }
+
+    /// Compute the inclination in centidegrees without floating-point
+    /// arithmetic
+    pub fn compute_fixed(&self, measurement: Measurement) -> InclinationFixed {
+        let a = self.orientation.apply(measurement);
+        // Scale up so that the square roots keep sub-mg precision.
+        let (x, y, z) = (
+            i64::from(a.x) << 8,
+            i64::from(a.y) << 8,
+            i64::from(a.z) << 8,
+        );
+        let horizontal_yz = isqrt((y * y + z * z) as u64) as i64;
+        let horizontal_xy = isqrt((x * x + y * y) as u64) as i64;
+        let magnitude = isqrt((x * x + y * y + z * z) as u64) as i64;
+        let g_deviation = ((magnitude + 128) >> 8) as i32 - 1000;
+        InclinationFixed {
+            pitch: atan2_centidegrees(x, horizontal_yz),
+            roll: atan2_centidegrees(y, z),
+            tilt: atan2_centidegrees(horizontal_xy, z),
+            g_deviation,
+            steady: g_deviation.abs() <= i32::from(self.tolerance_mg),
+        }
+    }
+}
+
+/// atan(2^-i) in millidegrees
+const CORDIC_ANGLES: [i64; 16] = [
+    45000, 26565, 14036, 7125, 3576, 1790, 895, 448, 224, 112, 56, 28, 14, 7, 3, 2,
+];
+
+/// Four-quadrant arctangent in centidegrees using CORDIC
+fn atan2_centidegrees(y: i64, x: i64) -> i32 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    if x < 0 {
+        let reflected = atan2_centidegrees(y, -x);
+        return if y >= 0 {
+            18000 - reflected
+        } else {
+            -18000 - reflected
+        };
+    }
+    // Scale up to keep precision during the shifts.
+    let (mut x, mut y) = (x << 8, y << 8);
+    let mut angle = 0;
+    for (i, step) in CORDIC_ANGLES.iter().enumerate() {
+        let (dx, dy) = (y >> i, x >> i);
+        if y > 0 {
+            x += dx;
+            y -= dy;
+            angle += step;
+        } else {
+            x -= dx;
+            y += dy;
+            angle -= step;
+        }
+    }
+    // Round millidegrees to centidegrees.
+    ((angle + 5 * angle.signum()) / 10) as i32
+}
+
+fn isqrt(value: u64) -> u64 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = value;
+    let mut y = x / 2 + 1;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Read the accelerometer and compute the inclination in degrees
+    pub fn inclination(
+        &mut self,
+        inclinometer: &Inclinometer,
+    ) -> Result<Inclination, Error<CommE, PinE>> {
+        let data = self.accel_data()?;
+        Ok(inclinometer.compute(data))
+    }
+
+    /// Read the accelerometer and compute the inclination in centidegrees
+    /// without floating-point arithmetic
+    pub fn inclination_fixed(
+        &mut self,
+        inclinometer: &Inclinometer,
+    ) -> Result<InclinationFixed, Error<CommE, PinE>> {
+        let data = self.accel_data()?;
+        Ok(inclinometer.compute_fixed(data))
+    }
+}
@@ -1,8 +1,14 @@
 //! I2C/SPI interfaces
-
-use crate::{private, BitFlags, Error};
+//!
+//! [`RetryInterface`] wraps the I2C or SPI interface and retries operations
+//! failing with a communication error according to a [`RetryPolicy`], for
+//! example on long I2C cables where NACKs happen occasionally. Create it with
+//! [`Lsm303agr::new_with_i2c_retry()`](crate::Lsm303agr::new_with_i2c_retry)
+//! or [`Lsm303agr::new_with_spi_retry()`](crate::Lsm303agr::new_with_spi_retry).
+
+use crate::{mode, private, BitFlags, Error, Lsm303agr, Register, FIFO_CAPACITY};
 use embedded_hal::{
-    blocking::{i2c, spi},
+    blocking::{delay::DelayUs, i2c, spi},
     digital::v2::OutputPin,
 };
 
@@ -23,6 +29,176 @@ pub struct SpiInterface<SPI, CSXL, CSMAG> {
     pub(crate) cs_mag: CSMAG,
 }
 
+/// Retry policy for operations failing with a communication error
+///
+/// Only `Error::Comm` errors are retried. Register writes and reads of
+/// configuration and status registers can safely be repeated. Reads of the
+/// output data registers and of the interrupt source registers
+/// (`INT1_SRC_A`, `INT2_SRC_A`, `CLICK_SRC_A` and `INT_SOURCE_REG_M`) are not
+/// retried by default because, if a failed read reached the device, a FIFO
+/// sample may have been consumed or a latched interrupt cleared.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts of an operation, including the first one
+    pub attempts: u8,
+    /// Delay between attempts in microseconds
+    pub delay_us: Option<u32>,
+    /// Retry register writes
+    pub writes: bool,
+    /// Retry single register reads, except of interrupt source registers
+    pub reads: bool,
+    /// Retry reads of the output data registers and of the interrupt source
+    /// registers
+    pub data_reads: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            delay_us: None,
+            writes: true,
+            reads: true,
+            data_reads: false,
+        }
+    }
+}
+
+/// Retry statistics
+///
+/// Many recovered operations point to a noisy bus, while failed operations
+/// without recoveries point to a missing or dead sensor.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RetryStats {
+    /// Number of repeated attempts
+    pub retries: u32,
+    /// Number of operations that succeeded after being retried
+    pub recovered: u32,
+    /// Number of operations that failed, after all allowed attempts
+    pub failed: u32,
+}
+
+/// Interface retrying failed operations
+#[derive(Debug)]
+pub struct RetryInterface<DI, D> {
+    pub(crate) iface: DI,
+    pub(crate) delay: D,
+    pub(crate) policy: RetryPolicy,
+    pub(crate) stats: RetryStats,
+}
+
+impl<DI, D> RetryInterface<DI, D>
+where
+    D: DelayUs<u32>,
+{
+    fn attempt<T, CommE, PinE>(
+        &mut self,
+        retry: bool,
+        mut operation: impl FnMut(&mut DI) -> Result<T, Error<CommE, PinE>>,
+    ) -> Result<T, Error<CommE, PinE>> {
+        let attempts = if retry { self.policy.attempts } else { 1 };
+        let mut attempt = 1;
+        loop {
+            match operation(&mut self.iface) {
+                Ok(value) => {
+                    if attempt > 1 {
+                        self.stats.recovered = self.stats.recovered.saturating_add(1);
+                    }
+                    return Ok(value);
+                }
+                Err(Error::Comm(_)) if attempt < attempts => {
+                    attempt += 1;
+                    self.stats.retries = self.stats.retries.saturating_add(1);
+                    if let Some(delay_us) = self.policy.delay_us {
+                        self.delay.delay_us(delay_us);
+                    }
+                }
+                Err(error) => {
+                    self.stats.failed = self.stats.failed.saturating_add(1);
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+impl<DI, D, CommE, PinE> WriteData for RetryInterface<DI, D>
+where
+    DI: WriteData<Error = Error<CommE, PinE>>,
+    D: DelayUs<u32>,
+{
+    type Error = Error<CommE, PinE>;
+
+    fn write_accel_register(&mut self, register: u8, data: u8) -> Result<(), Self::Error> {
+        self.attempt(self.policy.writes, |iface| {
+            iface.write_accel_register(register, data)
+        })
+    }
+
+    fn write_mag_register(&mut self, register: u8, data: u8) -> Result<(), Self::Error> {
+        self.attempt(self.policy.writes, |iface| {
+            iface.write_mag_register(register, data)
+        })
+    }
+}
+
+impl<DI, D, CommE, PinE> ReadData for RetryInterface<DI, D>
+where
+    DI: ReadData<Error = Error<CommE, PinE>>,
+    D: DelayUs<u32>,
+{
+    type Error = Error<CommE, PinE>;
+
+    fn read_accel_register(&mut self, register: u8) -> Result<u8, Self::Error> {
+        // Reading a source register clears a latched interrupt.
+        let retry = match register {
+            Register::INT1_SRC_A | Register::INT2_SRC_A | Register::CLICK_SRC_A => {
+                self.policy.data_reads
+            }
+            _ => self.policy.reads,
+        };
+        self.attempt(retry, |iface| iface.read_accel_register(register))
+    }
+
+    fn read_mag_register(&mut self, register: u8) -> Result<u8, Self::Error> {
+        let retry = match register {
+            Register::INT_SOURCE_REG_M => self.policy.data_reads,
+            _ => self.policy.reads,
+        };
+        self.attempt(retry, |iface| iface.read_mag_register(register))
+    }
+
+    fn read_accel_double_register(&mut self, register: u8) -> Result<u16, Self::Error> {
+        self.attempt(self.policy.data_reads, |iface| {
+            iface.read_accel_double_register(register)
+        })
+    }
+
+    fn read_accel_3_double_registers(
+        &mut self,
+        register: u8,
+    ) -> Result<(u16, u16, u16), Self::Error> {
+        self.attempt(self.policy.data_reads, |iface| {
+            iface.read_accel_3_double_registers(register)
+        })
+    }
+
+    fn read_mag_3_double_registers(
+        &mut self,
+        register: u8,
+    ) -> Result<(u16, u16, u16), Self::Error> {
+        self.attempt(self.policy.data_reads, |iface| {
+            iface.read_mag_3_double_registers(register)
+        })
+    }
+
+    fn read_accel_registers(&mut self, register: u8, data: &mut [u8]) -> Result<(), Self::Error> {
+        self.attempt(self.policy.data_reads, |iface| {
+            iface.read_accel_registers(register, data)
+        })
+    }
+}
+
 /// Write data
 pub trait WriteData: private::Sealed {
     /// Error type
@@ -100,6 +276,10 @@ pub trait ReadData: private::Sealed {
     /// Read 3 u16 magnetometer registers
     fn read_mag_3_double_registers(&mut self, register: u8)
         -> Result<(u16, u16, u16), Self::Error>;
+
+    /// Read consecutive accelerometer registers into `data` with address
+    /// auto-increment, at most a full FIFO
+    fn read_accel_registers(&mut self, register: u8, data: &mut [u8]) -> Result<(), Self::Error>;
 }
 
 impl<I2C, E> ReadData for I2cInterface<I2C>
@@ -133,6 +313,12 @@ where
     ) -> Result<(u16, u16, u16), Self::Error> {
         self.read_3_double_registers(MAG_ADDR, register)
     }
+
+    fn read_accel_registers(&mut self, register: u8, data: &mut [u8]) -> Result<(), Self::Error> {
+        self.i2c
+            .write_read(ACCEL_ADDR, &[register | 0x80], data)
+            .map_err(Error::Comm)
+    }
 }
 
 impl<I2C, E> I2cInterface<I2C>
@@ -221,6 +407,13 @@ where
         self.cs_mag.set_high().map_err(Error::Pin)?;
         result
     }
+
+    fn read_accel_registers(&mut self, register: u8, data: &mut [u8]) -> Result<(), Self::Error> {
+        self.cs_xl.set_low().map_err(Error::Pin)?;
+        let result = self.read_registers(register, data);
+        self.cs_xl.set_high().map_err(Error::Pin)?;
+        result
+    }
 }
 
 impl<SPI, CSXL, CSMAG, CommE, PinE> SpiInterface<SPI, CSXL, CSMAG>
@@ -235,6 +428,17 @@ where
         Ok(value[1])
     }
 
+    fn read_registers(&mut self, register: u8, data: &mut [u8]) -> Result<(), Error<CommE, PinE>> {
+        let mut buffer = [0; 1 + FIFO_CAPACITY * 6];
+        let buffer = buffer
+            .get_mut(..=data.len())
+            .ok_or(Error::InvalidInputData)?;
+        buffer[0] = BitFlags::SPI_RW | BitFlags::SPI_MS | register;
+        let value = self.spi.transfer(buffer).map_err(Error::Comm)?;
+        data.copy_from_slice(&value[1..]);
+        Ok(())
+    }
+
     fn read_double_register(&mut self, register: u8) -> Result<u16, Error<CommE, PinE>> {
         let mut data = [BitFlags::SPI_RW | BitFlags::SPI_MS | register, 0, 0];
         let value = self.spi.transfer(&mut data).map_err(Error::Comm)?;
@@ -262,3 +466,78 @@ where
         ))
     }
 }
+
+impl<I2C, D> Lsm303agr<RetryInterface<I2cInterface<I2C>, D>, mode::MagOneShot> {
+    /// Create new instance of the LSM303AGR device communicating through I2C
+    /// and retrying failed operations according to `policy`.
+    ///
+    /// `delay` is used between attempts if the policy sets a delay.
+    pub fn new_with_i2c_retry(i2c: I2C, policy: RetryPolicy, delay: D) -> Self {
+        Self::new_with_interface(RetryInterface {
+            iface: I2cInterface { i2c },
+            delay,
+            policy,
+            stats: RetryStats::default(),
+        })
+    }
+}
+
+impl<I2C, D, MODE> Lsm303agr<RetryInterface<I2cInterface<I2C>, D>, MODE> {
+    /// Destroy driver instance, return I2C bus and delay.
+    pub fn destroy(self) -> (I2C, D) {
+        (self.iface.iface.i2c, self.iface.delay)
+    }
+}
+
+impl<SPI, CSXL, CSMAG, D>
+    Lsm303agr<RetryInterface<SpiInterface<SPI, CSXL, CSMAG>, D>, mode::MagOneShot>
+{
+    /// Create new instance of the LSM303AGR device communicating through SPI
+    /// and retrying failed operations according to `policy`.
+    ///
+    /// `delay` is used between attempts if the policy sets a delay.
+    pub fn new_with_spi_retry(
+        spi: SPI,
+        chip_select_accel: CSXL,
+        chip_select_mag: CSMAG,
+        policy: RetryPolicy,
+        delay: D,
+    ) -> Self {
+        Self::new_with_interface(RetryInterface {
+            iface: SpiInterface {
+                spi,
+                cs_xl: chip_select_accel,
+                cs_mag: chip_select_mag,
+            },
+            delay,
+            policy,
+            stats: RetryStats::default(),
+        })
+    }
+}
+
+impl<SPI, CSXL, CSMAG, D, MODE> Lsm303agr<RetryInterface<SpiInterface<SPI, CSXL, CSMAG>, D>, MODE> {
+    /// Destroy driver instance, return SPI bus instance, chip select pins and
+    /// delay.
+    pub fn destroy(self) -> (SPI, CSXL, CSMAG, D) {
+        let RetryInterface { iface, delay, .. } = self.iface;
+        (iface.spi, iface.cs_xl, iface.cs_mag, delay)
+    }
+}
+
+impl<DI, D, MODE> Lsm303agr<RetryInterface<DI, D>, MODE> {
+    /// Retry statistics since creation or the last reset
+    pub fn retry_stats(&self) -> RetryStats {
+        self.iface.stats
+    }
+
+    /// Reset the retry statistics
+    pub fn reset_retry_stats(&mut self) {
+        self.iface.stats = RetryStats::default();
+    }
+
+    /// Change the retry policy
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.iface.policy = policy;
+    }
+}
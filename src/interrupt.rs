@@ -0,0 +1,62 @@
+use crate::{
+    interface::{ReadData, WriteData},
+    registers::{CtrlReg3A, CtrlReg6A},
+    AccelScale, Error, Lsm303agr, Register,
+};
+
+/// Interrupt output pin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterruptPin {
+    /// INT1 pin
+    Int1,
+    /// INT2 pin
+    Int2,
+}
+
+/// Interrupt generator threshold resolution in mg/LSB for a scale
+pub(crate) fn threshold_resolution_mg(scale: AccelScale) -> u16 {
+    match scale {
+        AccelScale::G2 => 16,
+        AccelScale::G4 => 32,
+        AccelScale::G8 => 62,
+        AccelScale::G16 => 186,
+    }
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Convert a threshold in mg into the `INT1_THS_A`/`INT2_THS_A` format
+    /// for the current scale.
+    pub(crate) fn interrupt_threshold(&self, threshold_mg: u16) -> Result<u8, Error<CommE, PinE>> {
+        let resolution = u32::from(threshold_resolution_mg(self.get_accel_scale()));
+        let lsb = (u32::from(threshold_mg) + resolution / 2) / resolution;
+        if lsb == 0 || lsb > 0x7F {
+            return Err(Error::InvalidInputData);
+        }
+        Ok(lsb as u8)
+    }
+
+    /// Route interrupt generator 1 to an interrupt pin
+    pub(crate) fn route_int1_generator(
+        &mut self,
+        pin: InterruptPin,
+    ) -> Result<(), Error<CommE, PinE>> {
+        let reg3 = CtrlReg3A {
+            i1_aoi1: pin == InterruptPin::Int1,
+            ..self.ctrl_reg3_a.bits.into()
+        }
+        .bits();
+        let reg6 = CtrlReg6A {
+            i2_int1: pin == InterruptPin::Int2,
+            ..self.ctrl_reg6_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG3_A, reg3)?;
+        self.ctrl_reg3_a = reg3.into();
+        self.write_accel_register(Register::CTRL_REG6_A, reg6)?;
+        self.ctrl_reg6_a = reg6.into();
+        Ok(())
+    }
+}
@@ -7,14 +7,31 @@
 //! This driver allows you to:
 //! - Connect through I2C or SPI. See: [`new_with_i2c()`](Lsm303agr::new_with_i2c).
 //! - Initialize the device. See: [`init()`](Lsm303agr::init).
+//! - Validate and apply a complete configuration at once. See: [`apply_config()`](Lsm303agr::apply_config).
 //! - Accelerometer:
 //!     - Read accelerometer data. See: [`accel_data()`](Lsm303agr::accel_data).
 //!     - Read accelerometer data unscaled. See: [`accel_data()`](Lsm303agr::accel_data_unscaled).
 //!     - Get accelerometer status. See: [`accel_status()`](Lsm303agr::accel_status).
+//!     - Average several accelerometer samples into one low-noise reading. See: [`accel_data_averaged()`](Lsm303agr::accel_data_averaged).
+//!     - Read accelerometer data with new/stale/overrun information. See: [`accel_data_checked()`](Lsm303agr::accel_data_checked).
 //!     - Set accelerometer output data rate. See: [`set_accel_odr()`](Lsm303agr::set_accel_odr).
+//!     - Get accelerometer output data rate. See: [`get_accel_odr()`](Lsm303agr::get_accel_odr).
 //!     - Set accelerometer mode. See: [`set_accel_mode()`](Lsm303agr::set_accel_mode).
 //!     - Set accelerometer scale. See: [`set_accel_scale()`](Lsm303agr::set_accel_scale).
+//!     - Select the accelerometer scale automatically. See: [`accel_data_auto_ranged()`](Lsm303agr::accel_data_auto_ranged).
 //!     - Get accelerometer ID. See: [`accelerometer_id()`](Lsm303agr::accelerometer_id).
+//!     - Run the accelerometer self-test. See: [`accel_self_test()`](Lsm303agr::accel_self_test).
+//!     - Calibrate accelerometer offset and gain. See: [`calibrate_accel_position()`](Lsm303agr::calibrate_accel_position).
+//!     - Apply accelerometer calibration. See: [`set_accel_calibration()`](Lsm303agr::set_accel_calibration).
+//!     - Get pitch, roll and tilt. See: [`inclination()`](Lsm303agr::inclination).
+//!     - Detect portrait/landscape and face-up/face-down orientation. See: [`OrientationDetector`]
+//!       and [`enable_orientation_detection()`](Lsm303agr::enable_orientation_detection).
+//!     - Detect free fall. See: [`enable_free_fall_detection()`](Lsm303agr::enable_free_fall_detection).
+//!     - Read batches of samples from the FIFO. See: [`set_fifo_mode()`](Lsm303agr::set_fifo_mode)
+//!       and [`fifo_data()`](Lsm303agr::fifo_data).
+//!     - Count steps (tested against synthetic traces only). See: [`StepCounter`].
+//!     - Log shocks and impacts. See: [`ImpactLogger`].
+//!     - Analyze vibration: RMS, peak, crest factor and band levels. See: [`VibrationAnalysis`].
 //!     - Get temperature sensor status. See: [`temperature_status()`](Lsm303agr::temperature_status).
 //!     - Get temperature sensor data. See: [`temperature_data()`](Lsm303agr::temperature_data).
 //!     - Get temperature sensor data in celsius. See: [`temperature_celsius()`](Lsm303agr::temperature_celsius).
@@ -23,8 +40,34 @@
 //!     - Change into continuous/one-shot mode. See: [`into_mag_continuous()`](Lsm303agr::into_mag_continuous).
 //!     - Read magnetometer data. See: [`mag_data()`](Lsm303agr::mag_data).
 //!     - Read magnetometer data unscaled. See: [`mag_data()`](Lsm303agr::mag_data_unscaled).
+//!     - Average several magnetometer samples into one low-noise reading. See: [`mag_data_averaged()`](Lsm303agr::mag_data_averaged).
+//!     - Read magnetometer data with new/stale/overrun information. See: [`mag_data_checked()`](Lsm303agr::mag_data_checked).
 //!     - Set magnetometer output data rate. See: [`set_mag_odr()`](Lsm303agr::set_mag_odr).
+//!     - Get magnetometer output data rate. See: [`get_mag_odr()`](Lsm303agr::get_mag_odr).
 //!     - Get magnetometer ID. See: [`magnetometer_id()`](Lsm303agr::magnetometer_id).
+//!     - Run the magnetometer self-test. See: [`mag_self_test()`](Lsm303agr::mag_self_test).
+//!     - Calibrate hard-iron and soft-iron distortions. See: [`MagCalibrator`].
+//!     - Set magnetometer hard-iron offset. See: [`set_mag_offset()`](Lsm303agr::set_mag_offset).
+//!     - Detect magnetic disturbances and saturation. See: [`MagDisturbanceDetector`].
+//! - Filter samples in software with moving average, exponential, median and biquad filters. See: [`filter`].
+//! - Count accelerometer and magnetometer overruns. See: [`overrun_counters()`](Lsm303agr::overrun_counters).
+//! - Stream accelerometer and magnetometer samples paced by their output data rates. See: [`sample_stream()`](Lsm303agr::sample_stream).
+//! - Fuse accelerometer, magnetometer and optional gyroscope data into an orientation. See: [`Ahrs`].
+//! - Store calibration and configuration in a checksummed binary format. See: [`Settings`].
+//! - Verify register writes by reading them back. See: [`set_write_verification()`](Lsm303agr::set_write_verification).
+//! - Detect and restore configuration registers that drifted. See: [`check_config()`](Lsm303agr::check_config).
+//! - Read all registers into typed bitfield structs for diagnostics. See: [`dump_registers()`](Lsm303agr::dump_registers)
+//!   and [`registers`].
+//! - Run the driver against a register-level simulator of the chip (`sim` feature). See: `sim`.
+//! - Describe errors with `Display`, `std::error::Error` (`std` feature) and
+//!   `defmt::Format` (`defmt` feature) and map them to stable numeric codes.
+//!   See: [`Error::code()`].
+//! - Retry operations failing on a noisy bus and count the retries. See: [`new_with_i2c_retry()`](Lsm303agr::new_with_i2c_retry).
+//! - Record register transactions with timestamps and replay them. See: [`trace`].
+//! - Read the sensors through the Linux IIO sysfs interface when the kernel
+//!   drivers own the device (`iio` feature). See: `iio`.
+//! - Inspect, stream, self-test and calibrate the device from the command line
+//!   with the `lsm303agr` tool (`cli` feature), also against the simulator.
 //!
 //! <!-- TODO
 //! [Introductory blog post](TODO)
@@ -109,17 +152,72 @@
 #![no_std]
 #![doc(html_root_url = "https://docs.rs/lsm303agr/0.2.2")]
 
+#[cfg(feature = "std")]
+extern crate std;
+
 use core::marker::PhantomData;
+mod accel_calibration;
 mod accel_mode_and_odr;
+mod auto_range;
+mod averaging;
+mod device_config;
 mod device_impl;
+mod fifo;
+pub mod filter;
+mod free_fall;
+mod freshness;
+mod fusion;
+#[cfg(feature = "iio")]
+pub mod iio;
+mod impact;
+mod inclinometer;
 pub mod interface;
+mod interrupt;
+mod mag_calibration;
+mod mag_disturbance;
 mod mag_mode_change;
 mod magnetometer;
+mod orientation;
+mod pedometer;
+mod persistence;
+pub mod registers;
+mod self_test;
+#[cfg(feature = "sim")]
+pub mod sim;
+mod stream;
+pub mod trace;
 mod types;
+mod verification;
+mod vibration;
+pub use crate::accel_calibration::{
+    AccelCalibration, AccelCalibrationError, AccelCalibrationPosition, AccelCalibrator,
+};
+pub use crate::auto_range::{AutoRangeConfig, AutoRangedMeasurement, AutoRanger};
+pub use crate::averaging::{AveragedAxis, AveragedMeasurement};
+pub use crate::fifo::{FifoMode, FifoStatus, FIFO_CAPACITY};
+pub use crate::freshness::{DataFreshness, FreshMeasurement, OverrunCounters};
+pub use crate::fusion::{Ahrs, EulerAngles, FusionAlgorithm, Quaternion};
+pub use crate::impact::{Impact, ImpactLogger};
+pub use crate::inclinometer::{Inclination, InclinationFixed, Inclinometer};
+pub use crate::interrupt::InterruptPin;
+pub use crate::mag_calibration::{
+    MagCalibration, MagCalibrationError, MagCalibrationReport, MagCalibrator,
+};
+pub use crate::mag_disturbance::{MagDisturbanceDetector, MagFieldStatus};
+pub use crate::orientation::{
+    Orientation, OrientationConfig, OrientationDetector, OrientationMode,
+};
+pub use crate::pedometer::{StepCounter, StepCounterConfig};
+pub use crate::persistence::{Settings, SettingsDecodeError};
+pub use crate::self_test::SelfTestResult;
+pub use crate::stream::{SampleStream, Sensor, StreamEvent};
 pub use crate::types::{
-    mode, AccelMode, AccelOutputDataRate, AccelScale, Error, MagOutputDataRate, Measurement,
-    ModeChangeError, Status, TemperatureStatus, UnscaledMeasurement,
+    mode, AccelMode, AccelOutputDataRate, AccelScale, Axis, AxisOrientation, DeviceConfig, Error,
+    MagOutputDataRate, Measurement, ModeChangeError, Status, TemperatureStatus,
+    UnscaledMeasurement,
 };
+pub use crate::verification::{ConfigDrift, RegisterDrift};
+pub use crate::vibration::{AxisVibration, VibrationAnalysis, VibrationBand, VIBRATION_BANDS};
 mod register_address;
 use crate::register_address::{BitFlags, Register};
 
@@ -129,11 +227,18 @@ pub struct Lsm303agr<DI, MODE> {
     /// Digital interface: I2C or SPI
     iface: DI,
     ctrl_reg1_a: Config,
+    ctrl_reg3_a: Config,
     ctrl_reg4_a: Config,
+    ctrl_reg5_a: Config,
+    ctrl_reg6_a: Config,
+    fifo_ctrl_reg_a: Config,
     cfg_reg_a_m: Config,
     cfg_reg_c_m: Config,
     temp_cfg_reg_a: Config,
     accel_odr: Option<AccelOutputDataRate>,
+    accel_calibration: Option<AccelCalibration>,
+    overruns: OverrunCounters,
+    verify_writes: bool,
     _mag_mode: PhantomData<MODE>,
 }
 
@@ -142,22 +247,6 @@ struct Config {
     bits: u8,
 }
 
-impl Config {
-    fn with_high(self, mask: u8) -> Self {
-        Config {
-            bits: self.bits | mask,
-        }
-    }
-    fn with_low(self, mask: u8) -> Self {
-        Config {
-            bits: self.bits & !mask,
-        }
-    }
-    fn is_high(self, mask: u8) -> bool {
-        (self.bits & mask) != 0
-    }
-}
-
 impl From<u8> for Config {
     fn from(bits: u8) -> Self {
         Config { bits }
@@ -165,9 +254,12 @@ impl From<u8> for Config {
 }
 
 mod private {
-    use crate::interface;
+    use crate::{interface, trace};
     pub trait Sealed {}
 
     impl<SPI, CSXL, CSMAG> Sealed for interface::SpiInterface<SPI, CSXL, CSMAG> {}
     impl<I2C> Sealed for interface::I2cInterface<I2C> {}
+    impl<DI, D> Sealed for interface::RetryInterface<DI, D> {}
+    impl<DI, W, C> Sealed for trace::Recorder<DI, W, C> {}
+    impl Sealed for trace::Replayer<'_> {}
 }
@@ -0,0 +1,445 @@
+//! Magnetometer hard-iron and soft-iron calibration.
+//!
+//! The calibrator fits an ellipsoid to the magnetometer samples collected
+//! while the device is rotated in all directions. The fitted ellipsoid is then
+//! mapped back onto a sphere by subtracting the hard-iron offset (ellipsoid
+//! center) and multiplying by the soft-iron correction matrix.
+
+#![allow(clippy::needless_range_loop)]
+
+use crate::{
+    interface::{ReadData, WriteData},
+    Error, Lsm303agr, Measurement, Register, UnscaledMeasurement,
+};
+
+/// Samples are normalized to gauss (1 gauss = 100000 nT) before being
+/// accumulated to keep the sums well conditioned.
+const NORMALIZATION: f64 = 100_000.0;
+/// Magnetometer sensitivity in nT/LSB.
+const NT_PER_LSB: f32 = 150.0;
+/// Number of direction bins used to estimate the coverage.
+const DIRECTION_BINS: u32 = 26;
+/// Minimum number of samples before a fit is attempted.
+const MIN_SAMPLES: u32 = 32;
+/// Minimum fraction of direction bins that must have been visited.
+const MIN_COVERAGE: f32 = 0.5;
+
+/// Magnetometer calibration
+///
+/// Corrected samples are computed as `soft_iron * (sample - hard_iron)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MagCalibration {
+    /// Hard-iron offset in nT (nanotesla)
+    pub hard_iron: [f32; 3],
+    /// Soft-iron correction matrix (row-major)
+    pub soft_iron: [[f32; 3]; 3],
+    /// Local magnetic field strength estimated during calibration in nT
+    pub field_strength: f32,
+}
+
+impl Default for MagCalibration {
+    fn default() -> Self {
+        MagCalibration {
+            hard_iron: [0.0; 3],
+            soft_iron: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            field_strength: 0.0,
+        }
+    }
+}
+
+impl MagCalibration {
+    /// Apply the calibration to a magnetometer measurement in nT
+    pub fn apply(&self, measurement: Measurement) -> Measurement {
+        let v = [
+            measurement.x as f32 - self.hard_iron[0],
+            measurement.y as f32 - self.hard_iron[1],
+            measurement.z as f32 - self.hard_iron[2],
+        ];
+        let w = &self.soft_iron;
+        let row = |i: usize| w[i][0] * v[0] + w[i][1] * v[1] + w[i][2] * v[2];
+        Measurement {
+            x: libm::roundf(row(0)) as i32,
+            y: libm::roundf(row(1)) as i32,
+            z: libm::roundf(row(2)) as i32,
+        }
+    }
+
+    /// Hard-iron offset in the format of the `OFFSET_*_REG_M` registers
+    ///
+    /// See [`Lsm303agr::set_mag_offset()`].
+    pub fn hard_iron_offset(&self) -> UnscaledMeasurement {
+        let lsb = |v: f32| libm::roundf(v / NT_PER_LSB) as i16;
+        UnscaledMeasurement {
+            x: lsb(self.hard_iron[0]),
+            y: lsb(self.hard_iron[1]),
+            z: lsb(self.hard_iron[2]),
+        }
+    }
+
+    /// Calibration to apply to samples read once the hard-iron offset has been
+    /// written to the device with [`Lsm303agr::set_mag_offset()`].
+    ///
+    /// Only the part of the hard-iron offset that the registers cannot
+    /// represent remains.
+    pub fn without_hard_iron_offset(&self) -> MagCalibration {
+        let offset = self.hard_iron_offset();
+        MagCalibration {
+            hard_iron: [
+                self.hard_iron[0] - f32::from(offset.x) * NT_PER_LSB,
+                self.hard_iron[1] - f32::from(offset.y) * NT_PER_LSB,
+                self.hard_iron[2] - f32::from(offset.z) * NT_PER_LSB,
+            ],
+            ..*self
+        }
+    }
+}
+
+/// Magnetometer calibration progress report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MagCalibrationReport {
+    /// Number of samples collected
+    pub samples: u32,
+    /// Fraction of the sphere of directions covered by the samples (0.0 to 1.0)
+    pub coverage: f32,
+    /// Approximate RMS relative deviation of the samples from the fitted
+    /// ellipsoid, if a fit is possible.
+    pub fit_error: Option<f32>,
+}
+
+/// Magnetometer calibration errors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MagCalibrationError {
+    /// Not enough samples have been collected
+    NotEnoughSamples,
+    /// The samples do not cover enough directions
+    InsufficientCoverage,
+    /// The samples do not describe an ellipsoid
+    InvalidFit,
+}
+
+/// Magnetometer calibration accumulator
+///
+/// Feed it with [`mag_data()`](Lsm303agr::mag_data) samples while slowly
+/// rotating the device in all directions, then call
+/// [`solve()`](MagCalibrator::solve). Samples should be collected with the
+/// `OFFSET_*_REG_M` registers cleared.
+///
+/// The calibrator runs in constant memory.
+#[derive(Debug, Clone)]
+pub struct MagCalibrator {
+    dtd: [[f64; 9]; 9],
+    dt1: [f64; 9],
+    min: [i32; 3],
+    max: [i32; 3],
+    directions: u32,
+    samples: u32,
+}
+
+impl Default for MagCalibrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MagCalibrator {
+    /// Create a new empty calibrator
+    pub fn new() -> Self {
+        MagCalibrator {
+            dtd: [[0.0; 9]; 9],
+            dt1: [0.0; 9],
+            min: [i32::MAX; 3],
+            max: [i32::MIN; 3],
+            directions: 0,
+            samples: 0,
+        }
+    }
+
+    /// Add a magnetometer sample in nT
+    pub fn add_sample(&mut self, measurement: Measurement) {
+        let m = [measurement.x, measurement.y, measurement.z];
+        if self.samples > 0 {
+            self.directions |= direction_bin(&m, &self.min, &self.max);
+        }
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(m[i]);
+            self.max[i] = self.max[i].max(m[i]);
+        }
+
+        let x = f64::from(m[0]) / NORMALIZATION;
+        let y = f64::from(m[1]) / NORMALIZATION;
+        let z = f64::from(m[2]) / NORMALIZATION;
+        let d = [
+            x * x,
+            y * y,
+            z * z,
+            2.0 * x * y,
+            2.0 * x * z,
+            2.0 * y * z,
+            2.0 * x,
+            2.0 * y,
+            2.0 * z,
+        ];
+        for i in 0..9 {
+            for j in i..9 {
+                self.dtd[i][j] += d[i] * d[j];
+            }
+            self.dt1[i] += d[i];
+        }
+        self.samples += 1;
+    }
+
+    /// Fraction of the sphere of directions covered by the samples (0.0 to 1.0)
+    ///
+    /// This is an estimate based on the center of the samples seen so far.
+    pub fn coverage(&self) -> f32 {
+        self.directions.count_ones() as f32 / DIRECTION_BINS as f32
+    }
+
+    /// Report the calibration progress and quality
+    pub fn report(&self) -> MagCalibrationReport {
+        MagCalibrationReport {
+            samples: self.samples,
+            coverage: self.coverage(),
+            fit_error: self.fit().ok().map(|(_, error)| error),
+        }
+    }
+
+    /// Solve the calibration from the collected samples
+    pub fn solve(&self) -> Result<MagCalibration, MagCalibrationError> {
+        if self.samples < MIN_SAMPLES {
+            return Err(MagCalibrationError::NotEnoughSamples);
+        }
+        if self.coverage() < MIN_COVERAGE {
+            return Err(MagCalibrationError::InsufficientCoverage);
+        }
+        self.fit().map(|(calibration, _)| calibration)
+    }
+
+    fn fit(&self) -> Result<(MagCalibration, f32), MagCalibrationError> {
+        if self.samples < MIN_SAMPLES {
+            return Err(MagCalibrationError::NotEnoughSamples);
+        }
+        // Least-squares fit of the quadric
+        // a*x² + b*y² + c*z² + 2d*xy + 2e*xz + 2f*yz + 2g*x + 2h*y + 2i*z = 1
+        let mut dtd = self.dtd;
+        for i in 0..9 {
+            for j in 0..i {
+                dtd[i][j] = dtd[j][i];
+            }
+        }
+        let p = solve_linear(dtd, self.dt1).ok_or(MagCalibrationError::InvalidFit)?;
+        let n = f64::from(self.samples);
+        let mut residual = n;
+        for i in 0..9 {
+            residual -= 2.0 * p[i] * self.dt1[i];
+            for j in 0..9 {
+                residual += p[i] * dtd[i][j] * p[j];
+            }
+        }
+        let fit_error = libm::sqrt(residual.max(0.0) / n) / 2.0;
+
+        let a = [[p[0], p[3], p[4]], [p[3], p[1], p[5]], [p[4], p[5], p[2]]];
+        let v = [p[6], p[7], p[8]];
+        let a_inv = invert_3x3(&a).ok_or(MagCalibrationError::InvalidFit)?;
+        let mut center = [0.0; 3];
+        for i in 0..3 {
+            center[i] = -(a_inv[i][0] * v[0] + a_inv[i][1] * v[1] + a_inv[i][2] * v[2]);
+        }
+        let mut k = 1.0;
+        for i in 0..3 {
+            for j in 0..3 {
+                k += center[i] * a[i][j] * center[j];
+            }
+        }
+        if k <= 0.0 {
+            return Err(MagCalibrationError::InvalidFit);
+        }
+        let mut m = a;
+        for row in m.iter_mut() {
+            for value in row.iter_mut() {
+                *value /= k;
+            }
+        }
+
+        let (eigenvalues, eigenvectors) = symmetric_eigen_3x3(m);
+        if eigenvalues.iter().any(|&l| l <= 0.0) {
+            return Err(MagCalibrationError::InvalidFit);
+        }
+        // The field strength is the radius of the sphere with the same volume
+        // as the ellipsoid.
+        let radius = libm::cbrt(1.0 / libm::sqrt(eigenvalues[0] * eigenvalues[1] * eigenvalues[2]));
+        let mut soft_iron = [[0.0; 3]; 3];
+        for (i, row) in soft_iron.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                let mut sum = 0.0;
+                for (l, eigenvalue) in eigenvalues.iter().enumerate() {
+                    sum += eigenvectors[i][l] * libm::sqrt(*eigenvalue) * eigenvectors[j][l];
+                }
+                *value = (sum * radius) as f32;
+            }
+        }
+
+        let calibration = MagCalibration {
+            hard_iron: [
+                (center[0] * NORMALIZATION) as f32,
+                (center[1] * NORMALIZATION) as f32,
+                (center[2] * NORMALIZATION) as f32,
+            ],
+            soft_iron,
+            field_strength: (radius * NORMALIZATION) as f32,
+        };
+        Ok((calibration, fit_error as f32))
+    }
+}
+
+/// Classify the direction of a sample relative to the center of the
+/// bounding box seen so far into one of 26 bins (faces, edges and corners of
+/// a cube). Returns the bin as a bit mask.
+fn direction_bin(m: &[i32; 3], min: &[i32; 3], max: &[i32; 3]) -> u32 {
+    let mut d = [0i64; 3];
+    for i in 0..3 {
+        d[i] = 2 * i64::from(m[i]) - i64::from(min[i]) - i64::from(max[i]);
+    }
+    let norm2: i64 = d.iter().map(|v| v * v).sum();
+    if norm2 == 0 {
+        return 0;
+    }
+    // A component counts if its absolute value is above half the magnitude.
+    let mut index = 0;
+    for v in d.iter() {
+        let sign = if 4 * v * v > norm2 { v.signum() } else { 0 };
+        index = index * 3 + (sign + 1) as u32;
+    }
+    // index 13 is the (0, 0, 0) cell which cannot happen.
+    let bin = if index > 13 { index - 1 } else { index };
+    1 << bin
+}
+
+/// Solve `a * x = b` using Gaussian elimination with partial pivoting.
+fn solve_linear(mut a: [[f64; 9]; 9], mut b: [f64; 9]) -> Option<[f64; 9]> {
+    for col in 0..9 {
+        let mut pivot = col;
+        for row in col + 1..9 {
+            if libm::fabs(a[row][col]) > libm::fabs(a[pivot][col]) {
+                pivot = row;
+            }
+        }
+        if libm::fabs(a[pivot][col]) < 1e-15 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..9 {
+            let factor = a[row][col] / a[col][col];
+            for k in col..9 {
+                a[row][k] -= factor * a[col][k];
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = [0.0; 9];
+    for row in (0..9).rev() {
+        let mut sum = b[row];
+        for k in row + 1..9 {
+            sum -= a[row][k] * x[k];
+        }
+        x[row] = sum / a[row][row];
+    }
+    Some(x)
+}
+
+fn invert_3x3(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let c00 = m[1][1] * m[2][2] - m[1][2] * m[2][1];
+    let c01 = m[1][2] * m[2][0] - m[1][0] * m[2][2];
+    let c02 = m[1][0] * m[2][1] - m[1][1] * m[2][0];
+    let det = m[0][0] * c00 + m[0][1] * c01 + m[0][2] * c02;
+    if libm::fabs(det) < 1e-30 {
+        return None;
+    }
+    let inv = 1.0 / det;
+    Some([
+        [
+            c00 * inv,
+            (m[0][2] * m[2][1] - m[0][1] * m[2][2]) * inv,
+            (m[0][1] * m[1][2] - m[0][2] * m[1][1]) * inv,
+        ],
+        [
+            c01 * inv,
+            (m[0][0] * m[2][2] - m[0][2] * m[2][0]) * inv,
+            (m[0][2] * m[1][0] - m[0][0] * m[1][2]) * inv,
+        ],
+        [
+            c02 * inv,
+            (m[0][1] * m[2][0] - m[0][0] * m[2][1]) * inv,
+            (m[0][0] * m[1][1] - m[0][1] * m[1][0]) * inv,
+        ],
+    ])
+}
+
+/// Eigen-decomposition of a symmetric 3x3 matrix using Jacobi rotations.
+///
+/// Returns the eigenvalues and a matrix with the eigenvectors as columns.
+fn symmetric_eigen_3x3(mut a: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..50 {
+        let off = a[0][1] * a[0][1] + a[0][2] * a[0][2] + a[1][2] * a[1][2];
+        if off < 1e-30 {
+            break;
+        }
+        for &(p, q) in &[(0, 1), (0, 2), (1, 2)] {
+            if libm::fabs(a[p][q]) < 1e-300 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (libm::fabs(theta) + libm::sqrt(theta * theta + 1.0));
+            let c = 1.0 / libm::sqrt(t * t + 1.0);
+            let s = t * c;
+            for k in 0..3 {
+                let akp = a[k][p];
+                let akq = a[k][q];
+                a[k][p] = c * akp - s * akq;
+                a[k][q] = s * akp + c * akq;
+            }
+            for k in 0..3 {
+                let apk = a[p][k];
+                let aqk = a[q][k];
+                a[p][k] = c * apk - s * aqk;
+                a[q][k] = s * apk + c * aqk;
+            }
+            for row in v.iter_mut() {
+                let vkp = row[p];
+                let vkq = row[q];
+                row[p] = c * vkp - s * vkq;
+                row[q] = s * vkp + c * vkq;
+            }
+        }
+    }
+    ([a[0][0], a[1][1], a[2][2]], v)
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Set the magnetometer hard-iron offset registers
+    ///
+    /// The offset is subtracted by the device from the measured data.
+    /// See [`MagCalibration::hard_iron_offset()`].
+    pub fn set_mag_offset(
+        &mut self,
+        offset: UnscaledMeasurement,
+    ) -> Result<(), Error<CommE, PinE>> {
+        let registers = [
+            (Register::OFFSET_X_REG_L_M, offset.x),
+            (Register::OFFSET_Y_REG_L_M, offset.y),
+            (Register::OFFSET_Z_REG_L_M, offset.z),
+        ];
+        for &(register, value) in registers.iter() {
+            let [low, high] = value.to_le_bytes();
+            self.write_mag_register(register, low)?;
+            self.write_mag_register(register + 1, high)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+//! Magnetometer saturation and disturbance detection.
+//!
+//! Near motors, speakers or steel the magnetic field measured by the
+//! magnetometer is no longer the earth's field, so a compass heading computed
+//! from it is wrong. Such disturbances are detected by comparing the
+//! measurement with the expected local field: its strength and its dip
+//! angle, which is the angle between the field and the horizontal plane
+//! given by the accelerometer.
+//!
+//! The accelerometer and magnetometer axes must be aligned, which can be
+//! ensured with [`AxisOrientation`](crate::AxisOrientation).
+
+use crate::{
+    interface::{ReadData, WriteData},
+    mode, Error, Lsm303agr, MagCalibration, Measurement,
+};
+
+/// Largest magnetometer output in nT (about 50 gauss)
+const MAG_FULL_SCALE_NT: f32 = 32767.0 * 150.0;
+/// Percentage of the full scale from which a measurement counts as saturated
+const SATURATION_PERCENT: f32 = 95.0;
+const DEFAULT_FIELD_TOLERANCE_PERCENT: u8 = 15;
+const DEFAULT_DIP_TOLERANCE_DEG: f32 = 10.0;
+const DEFAULT_ACCEL_TOLERANCE_MG: u16 = 100;
+
+/// Result of checking a magnetometer measurement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MagFieldStatus {
+    /// Strength of the measured field in nT
+    pub field_strength: f32,
+    /// Dip angle of the measured field in degrees, positive when the field
+    /// points downwards
+    ///
+    /// `None` if no accelerometer measurement was given or if the device was
+    /// accelerating, so that the accelerometer did not give the vertical.
+    pub dip_angle: Option<f32>,
+    /// At least one axis is close to the ±50 gauss limit of the sensor
+    pub saturated: bool,
+    /// The field strength differs from the expected one by more than the
+    /// tolerance
+    pub strength_disturbed: bool,
+    /// The dip angle differs from the expected one by more than the tolerance
+    pub dip_disturbed: bool,
+    /// The measurement can be trusted for heading computation: it is neither
+    /// saturated nor disturbed
+    pub trustworthy: bool,
+}
+
+/// Magnetometer disturbance detector
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MagDisturbanceDetector {
+    calibration: Option<MagCalibration>,
+    field_strength: f32,
+    field_tolerance_percent: u8,
+    dip_angle: Option<f32>,
+    dip_tolerance: f32,
+    accel_tolerance_mg: u16,
+}
+
+impl MagDisturbanceDetector {
+    /// Create a new detector expecting a local field with the given strength
+    /// in nT
+    ///
+    /// The dip angle is not checked until it is set with
+    /// [`with_dip_angle()`](MagDisturbanceDetector::with_dip_angle) or
+    /// [`learn()`](MagDisturbanceDetector::learn).
+    pub fn new(field_strength: f32) -> Self {
+        MagDisturbanceDetector {
+            calibration: None,
+            field_strength,
+            field_tolerance_percent: DEFAULT_FIELD_TOLERANCE_PERCENT,
+            dip_angle: None,
+            dip_tolerance: DEFAULT_DIP_TOLERANCE_DEG,
+            accel_tolerance_mg: DEFAULT_ACCEL_TOLERANCE_MG,
+        }
+    }
+
+    /// Create a new detector expecting the field strength estimated during
+    /// calibration
+    ///
+    /// The calibration is applied to the measurements before checking the
+    /// field strength and dip angle.
+    pub fn from_calibration(calibration: &MagCalibration) -> Self {
+        MagDisturbanceDetector {
+            calibration: Some(*calibration),
+            ..Self::new(calibration.field_strength)
+        }
+    }
+
+    /// Set the allowed deviation from the expected field strength in percent
+    /// (default 15%)
+    pub fn with_field_tolerance(self, percent: u8) -> Self {
+        MagDisturbanceDetector {
+            field_tolerance_percent: percent,
+            ..self
+        }
+    }
+
+    /// Set the expected dip angle in degrees, positive when the field points
+    /// downwards as in the northern hemisphere
+    pub fn with_dip_angle(self, degrees: f32) -> Self {
+        MagDisturbanceDetector {
+            dip_angle: Some(degrees),
+            ..self
+        }
+    }
+
+    /// Set the allowed deviation from the expected dip angle in degrees
+    /// (default 10°)
+    pub fn with_dip_tolerance(self, degrees: f32) -> Self {
+        MagDisturbanceDetector {
+            dip_tolerance: degrees,
+            ..self
+        }
+    }
+
+    /// Set the tolerance around 1g in mg for the accelerometer measurement to
+    /// be used as the vertical (default 100 mg)
+    pub fn with_accel_tolerance(self, tolerance_mg: u16) -> Self {
+        MagDisturbanceDetector {
+            accel_tolerance_mg: tolerance_mg,
+            ..self
+        }
+    }
+
+    /// Expected field strength in nT
+    pub fn field_strength(&self) -> f32 {
+        self.field_strength
+    }
+
+    /// Expected dip angle in degrees
+    pub fn dip_angle(&self) -> Option<f32> {
+        self.dip_angle
+    }
+
+    /// Take the field strength and dip angle of measurements taken at rest in
+    /// an undisturbed place as the expected values
+    ///
+    /// The dip angle is only learned if the device is at rest according to
+    /// the accelerometer.
+    pub fn learn(&mut self, mag: Measurement, accel: Measurement) {
+        let field = self.field(mag);
+        self.field_strength = norm(field);
+        if let Some(dip) = self.measure_dip(field, accel) {
+            self.dip_angle = Some(dip);
+        }
+    }
+
+    /// Check a magnetometer measurement in nT, optionally together with an
+    /// accelerometer measurement in mg taken at the same time
+    pub fn check(&self, mag: Measurement, accel: Option<Measurement>) -> MagFieldStatus {
+        let limit = MAG_FULL_SCALE_NT * SATURATION_PERCENT / 100.0;
+        let saturated = [mag.x, mag.y, mag.z]
+            .iter()
+            .any(|v| libm::fabsf(*v as f32) >= limit);
+        let field = self.field(mag);
+        let field_strength = norm(field);
+        let tolerance = self.field_strength * f32::from(self.field_tolerance_percent) / 100.0;
+        let strength_disturbed = libm::fabsf(field_strength - self.field_strength) > tolerance;
+        let dip_angle = accel.and_then(|accel| self.measure_dip(field, accel));
+        let dip_disturbed = match (dip_angle, self.dip_angle) {
+            (Some(measured), Some(expected)) => {
+                libm::fabsf(measured - expected) > self.dip_tolerance
+            }
+            _ => false,
+        };
+        MagFieldStatus {
+            field_strength,
+            dip_angle,
+            saturated,
+            strength_disturbed,
+            dip_disturbed,
+            trustworthy: !saturated && !strength_disturbed && !dip_disturbed,
+        }
+    }
+
+    fn field(&self, mag: Measurement) -> [f32; 3] {
+        let mag = match &self.calibration {
+            Some(calibration) => calibration.apply(mag),
+            None => mag,
+        };
+        [mag.x as f32, mag.y as f32, mag.z as f32]
+    }
+
+    fn measure_dip(&self, field: [f32; 3], accel: Measurement) -> Option<f32> {
+        // At rest the accelerometer measures 1g pointing upwards.
+        let up = [accel.x as f32, accel.y as f32, accel.z as f32];
+        let g = norm(up);
+        let strength = norm(field);
+        if libm::fabsf(g - 1000.0) > f32::from(self.accel_tolerance_mg) || strength == 0.0 {
+            return None;
+        }
+        let down_component = -(field[0] * up[0] + field[1] * up[1] + field[2] * up[2]) / g;
+        let sin_dip = (down_component / strength).clamp(-1.0, 1.0);
+        Some(libm::asinf(sin_dip).to_degrees())
+    }
+}
+
+fn norm(v: [f32; 3]) -> f32 {
+    libm::sqrtf(v[0] * v[0] + v[1] * v[1] + v[2] * v[2])
+}
+
+impl<DI, CommE, PinE> Lsm303agr<DI, mode::MagContinuous>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Read the magnetometer and the accelerometer and check whether the
+    /// magnetic field can be trusted
+    pub fn check_mag_field(
+        &mut self,
+        detector: &MagDisturbanceDetector,
+    ) -> Result<MagFieldStatus, Error<CommE, PinE>> {
+        let mag = self.mag_data()?;
+        let accel = self.accel_data()?;
+        Ok(detector.check(mag, Some(accel)))
+    }
+}
@@ -1,6 +1,8 @@
 use crate::{
     interface::{ReadData, WriteData},
-    mode, Error, Lsm303agr, ModeChangeError, PhantomData, Register,
+    mode,
+    registers::CfgRegAM,
+    Error, Lsm303agr, ModeChangeError, PhantomData, Register,
 };
 
 impl<DI, CommE, PinE> Lsm303agr<DI, mode::MagOneShot>
@@ -11,17 +13,28 @@ where
     pub fn into_mag_continuous(
         mut self,
     ) -> Result<Lsm303agr<DI, mode::MagContinuous>, ModeChangeError<CommE, PinE, Self>> {
-        let cfg = self.cfg_reg_a_m.bits & 0xFC;
-        match self.iface.write_mag_register(Register::CFG_REG_A_M, cfg) {
+        let cfg = CfgRegAM {
+            md: CfgRegAM::MD_CONTINUOUS,
+            ..self.cfg_reg_a_m.bits.into()
+        }
+        .bits();
+        match self.write_mag_register(Register::CFG_REG_A_M, cfg) {
             Err(error) => Err(ModeChangeError { error, dev: self }),
             Ok(_) => Ok(Lsm303agr {
                 iface: self.iface,
                 ctrl_reg1_a: self.ctrl_reg1_a,
+                ctrl_reg3_a: self.ctrl_reg3_a,
                 ctrl_reg4_a: self.ctrl_reg4_a,
+                ctrl_reg5_a: self.ctrl_reg5_a,
+                ctrl_reg6_a: self.ctrl_reg6_a,
+                fifo_ctrl_reg_a: self.fifo_ctrl_reg_a,
                 cfg_reg_a_m: cfg.into(),
                 cfg_reg_c_m: self.cfg_reg_c_m,
                 temp_cfg_reg_a: self.temp_cfg_reg_a,
-                accel_odr: None,
+                accel_odr: self.accel_odr,
+                accel_calibration: self.accel_calibration,
+                overruns: self.overruns,
+                verify_writes: self.verify_writes,
                 _mag_mode: PhantomData,
             }),
         }
@@ -39,17 +52,28 @@ where
     pub fn into_mag_one_shot(
         mut self,
     ) -> Result<Lsm303agr<DI, mode::MagOneShot>, ModeChangeError<CommE, PinE, Self>> {
-        let cfg = self.cfg_reg_a_m.bits | 0x3;
-        match self.iface.write_mag_register(Register::CFG_REG_A_M, cfg) {
+        let cfg = CfgRegAM {
+            md: CfgRegAM::MD_IDLE,
+            ..self.cfg_reg_a_m.bits.into()
+        }
+        .bits();
+        match self.write_mag_register(Register::CFG_REG_A_M, cfg) {
             Err(error) => Err(ModeChangeError { error, dev: self }),
             Ok(_) => Ok(Lsm303agr {
                 iface: self.iface,
                 ctrl_reg1_a: self.ctrl_reg1_a,
+                ctrl_reg3_a: self.ctrl_reg3_a,
                 ctrl_reg4_a: self.ctrl_reg4_a,
+                ctrl_reg5_a: self.ctrl_reg5_a,
+                ctrl_reg6_a: self.ctrl_reg6_a,
+                fifo_ctrl_reg_a: self.fifo_ctrl_reg_a,
                 cfg_reg_a_m: cfg.into(),
                 cfg_reg_c_m: self.cfg_reg_c_m,
                 temp_cfg_reg_a: self.temp_cfg_reg_a,
-                accel_odr: None,
+                accel_odr: self.accel_odr,
+                accel_calibration: self.accel_calibration,
+                overruns: self.overruns,
+                verify_writes: self.verify_writes,
                 _mag_mode: PhantomData,
             }),
         }
@@ -1,6 +1,8 @@
 use crate::{
     interface::{ReadData, WriteData},
-    mode, Error, Lsm303agr, MagOutputDataRate, Measurement, Register, UnscaledMeasurement,
+    mode,
+    registers::CfgRegAM,
+    Error, Lsm303agr, MagOutputDataRate, Measurement, Register, UnscaledMeasurement,
 };
 
 impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
@@ -9,18 +11,36 @@ where
 {
     /// Set magnetometer output data rate
     pub fn set_mag_odr(&mut self, odr: MagOutputDataRate) -> Result<(), Error<CommE, PinE>> {
-        let cfg = self.cfg_reg_a_m.bits & 0xF3; // !(3 << 2);
-        let mask = match odr {
-            MagOutputDataRate::Hz10 => 0,
-            MagOutputDataRate::Hz20 => 1 << 2,
-            MagOutputDataRate::Hz50 => 2 << 2,
-            MagOutputDataRate::Hz100 => 3 << 2,
+        let cfg = CfgRegAM {
+            odr: mag_odr_bits(odr),
+            ..self.cfg_reg_a_m.bits.into()
         };
-        self.iface
-            .write_mag_register(Register::CFG_REG_A_M, cfg | mask)?;
-        self.cfg_reg_a_m = (cfg | mask).into();
+        self.write_mag_register(Register::CFG_REG_A_M, cfg.bits())?;
+        self.cfg_reg_a_m = cfg.bits().into();
         Ok(())
     }
+
+    /// Get the magnetometer output data rate
+    pub fn get_mag_odr(&self) -> MagOutputDataRate {
+        match CfgRegAM::from(self.cfg_reg_a_m.bits).odr {
+            0 => MagOutputDataRate::Hz10,
+            1 => MagOutputDataRate::Hz20,
+            2 => MagOutputDataRate::Hz50,
+            _ => MagOutputDataRate::Hz100,
+        }
+    }
+
+    /// Read the magnetometer output registers and scale them to nT
+    pub(crate) fn mag_output(&mut self) -> Result<Measurement, Error<CommE, PinE>> {
+        let data = self
+            .iface
+            .read_mag_3_double_registers(Register::OUTX_L_REG_M)?;
+        Ok(Measurement {
+            x: scale_measurement(data.0 as i16),
+            y: scale_measurement(data.1 as i16),
+            z: scale_measurement(data.2 as i16),
+        })
+    }
 }
 
 impl<DI, CommE, PinE> Lsm303agr<DI, mode::MagContinuous>
@@ -33,13 +53,7 @@ where
     ///
     /// If you need the raw unscaled measurement see [`Lsm303agr::mag_data_unscaled`].
     pub fn mag_data(&mut self) -> Result<Measurement, Error<CommE, PinE>> {
-        let unscaled = self.mag_data_unscaled()?;
-
-        Ok(Measurement {
-            x: scale_measurement(unscaled.x),
-            y: scale_measurement(unscaled.y),
-            z: scale_measurement(unscaled.z),
-        })
+        self.mag_output()
     }
 
     /// Unscaled magnetometer data
@@ -83,17 +97,30 @@ where
                 z: data.2 as i16,
             })
         } else {
-            let cfg = self.iface.read_mag_register(Register::CFG_REG_A_M)?;
-            if (cfg & 0x3) != 0x1 {
+            let cfg = CfgRegAM::from(self.iface.read_mag_register(Register::CFG_REG_A_M)?);
+            if cfg.md != CfgRegAM::MD_SINGLE {
                 // start one-shot measurement
-                let cfg = (self.cfg_reg_a_m.bits & 0xFC) | 0x1;
-                self.iface.write_mag_register(Register::CFG_REG_A_M, cfg)?;
+                let cfg = CfgRegAM {
+                    md: CfgRegAM::MD_SINGLE,
+                    ..self.cfg_reg_a_m.bits.into()
+                };
+                self.write_mag_register(Register::CFG_REG_A_M, cfg.bits())?;
             }
             Err(nb::Error::WouldBlock)
         }
     }
 }
 
+/// `ODR` field value of `CFG_REG_A_M`
+pub(crate) fn mag_odr_bits(odr: MagOutputDataRate) -> u8 {
+    match odr {
+        MagOutputDataRate::Hz10 => 0,
+        MagOutputDataRate::Hz20 => 1,
+        MagOutputDataRate::Hz50 => 2,
+        MagOutputDataRate::Hz100 => 3,
+    }
+}
+
 const SCALING_FACTOR: i32 = 150;
 
 fn scale_measurement(unscaled: i16) -> i32 {
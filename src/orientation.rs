@@ -0,0 +1,262 @@
+//! Portrait/landscape and face-up/face-down orientation detection.
+//!
+//! The detection follows the 6D/4D position recognition of the
+//! accelerometer interrupt generator: a position is recognized when exactly
+//! one axis exceeds the threshold. While the device is between positions
+//! (no axis or several axes above the threshold) the last position is kept,
+//! which provides the hysteresis. The position must be held for more than
+//! `duration` samples before it is reported.
+//!
+//! The same [`OrientationConfig`] can drive the software detector and the
+//! interrupt generator, so that both give the same results.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    registers::{CtrlReg5A, Int1CfgA, Int1SrcA},
+    AxisOrientation, Error, InterruptPin, Lsm303agr, Measurement, Register,
+};
+
+/// Device orientation
+///
+/// The device is held with the Z axis pointing at the user, and the Y axis
+/// pointing up in portrait orientation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Orientation {
+    /// Y axis pointing up
+    PortraitUp,
+    /// Y axis pointing down
+    PortraitDown,
+    /// X axis pointing up (rotated 90° counterclockwise from portrait)
+    LandscapeLeft,
+    /// X axis pointing down (rotated 90° clockwise from portrait)
+    LandscapeRight,
+    /// Z axis pointing up
+    FaceUp,
+    /// Z axis pointing down
+    FaceDown,
+}
+
+impl Orientation {
+    fn from_axis(axis: usize, positive: bool) -> Self {
+        match (axis, positive) {
+            (0, true) => Orientation::LandscapeLeft,
+            (0, false) => Orientation::LandscapeRight,
+            (1, true) => Orientation::PortraitUp,
+            (1, false) => Orientation::PortraitDown,
+            (_, true) => Orientation::FaceUp,
+            (_, false) => Orientation::FaceDown,
+        }
+    }
+}
+
+/// Orientation detection mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrientationMode {
+    /// Detect all six orientations
+    SixD,
+    /// Detect only portrait and landscape orientations (Z axis ignored)
+    FourD,
+}
+
+/// Orientation detection configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientationConfig {
+    /// Acceleration threshold in mg an axis must exceed
+    ///
+    /// The interrupt generator rounds it to its resolution (16 mg at ±2g,
+    /// 32 mg at ±4g, 62 mg at ±8g and 186 mg at ±16g). Use a multiple of the
+    /// resolution to get identical results on both paths.
+    pub threshold_mg: u16,
+    /// Number of samples (output data rate periods) a position must be held
+    /// before it is reported (0 to 127)
+    pub duration: u8,
+    /// Detection mode
+    pub mode: OrientationMode,
+    /// Sensor mounting orientation
+    pub axes: AxisOrientation,
+}
+
+impl Default for OrientationConfig {
+    fn default() -> Self {
+        OrientationConfig {
+            threshold_mg: 800,
+            duration: 0,
+            mode: OrientationMode::SixD,
+            axes: AxisOrientation::default(),
+        }
+    }
+}
+
+impl OrientationConfig {
+    /// Position recognized from values in device axes, if any: exactly one
+    /// axis must be above the threshold.
+    fn position(&self, values: [i32; 3], threshold: i32) -> Option<Orientation> {
+        let axes = match self.mode {
+            OrientationMode::SixD => 3,
+            OrientationMode::FourD => 2,
+        };
+        let mut above = values[..axes]
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.abs() > threshold);
+        match (above.next(), above.next()) {
+            (Some((axis, value)), None) => Some(Orientation::from_axis(axis, *value > 0)),
+            _ => None,
+        }
+    }
+}
+
+/// Orientation detector
+///
+/// Feed it with accelerometer measurements with
+/// [`update()`](OrientationDetector::update) or with the positions reported
+/// by the interrupt generator with
+/// [`update_position()`](OrientationDetector::update_position).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientationDetector {
+    config: OrientationConfig,
+    current: Option<Orientation>,
+    candidate: Option<Orientation>,
+    count: u8,
+}
+
+impl OrientationDetector {
+    /// Create a new orientation detector
+    pub fn new(config: OrientationConfig) -> Self {
+        OrientationDetector {
+            config,
+            current: None,
+            candidate: None,
+            count: 0,
+        }
+    }
+
+    /// Detection configuration
+    pub fn config(&self) -> &OrientationConfig {
+        &self.config
+    }
+
+    /// Current orientation, if one has been detected
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.current
+    }
+
+    /// Update the detector with an accelerometer measurement in mg
+    ///
+    /// Returns the current orientation.
+    pub fn update(&mut self, measurement: Measurement) -> Option<Orientation> {
+        let m = self.config.axes.apply(measurement);
+        match self
+            .config
+            .position([m.x, m.y, m.z], i32::from(self.config.threshold_mg))
+        {
+            Some(position) if self.current != Some(position) => {
+                if self.candidate == Some(position) {
+                    self.count = self.count.saturating_add(1);
+                } else {
+                    self.candidate = Some(position);
+                    self.count = 1;
+                }
+                if self.count > self.config.duration {
+                    self.current = Some(position);
+                }
+            }
+            _ => {
+                self.candidate = None;
+                self.count = 0;
+            }
+        }
+        self.current
+    }
+
+    /// Update the detector with a position reported by the interrupt
+    /// generator (see [`Lsm303agr::accel_orientation()`])
+    ///
+    /// Returns the current orientation.
+    pub fn update_position(&mut self, position: Option<Orientation>) -> Option<Orientation> {
+        if position.is_some() {
+            self.current = position;
+        }
+        self.current
+    }
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Configure interrupt generator 1 for 6D/4D position recognition and
+    /// route it to an interrupt pin
+    ///
+    /// The accelerometer scale must be set before calling this.
+    /// This replaces any other configuration of interrupt generator 1, like
+    /// free-fall detection.
+    ///
+    /// Returns `Error::InvalidInputData` if the threshold or duration cannot
+    /// be represented at the current scale.
+    pub fn enable_orientation_detection(
+        &mut self,
+        config: &OrientationConfig,
+        pin: InterruptPin,
+    ) -> Result<(), Error<CommE, PinE>> {
+        let threshold = self.interrupt_threshold(config.threshold_mg)?;
+        if config.duration > 0x7F {
+            return Err(Error::InvalidInputData);
+        }
+        let reg5 = CtrlReg5A {
+            d4d_int1: match config.mode {
+                OrientationMode::SixD => false,
+                OrientationMode::FourD => true,
+            },
+            lir_int1: false,
+            ..self.ctrl_reg5_a.bits.into()
+        }
+        .bits();
+        self.write_accel_register(Register::CTRL_REG5_A, reg5)?;
+        self.ctrl_reg5_a = reg5.into();
+        self.write_accel_register(Register::INT1_THS_A, threshold)?;
+        self.write_accel_register(Register::INT1_DURATION_A, config.duration)?;
+        let cfg = Int1CfgA {
+            aoi: true,
+            six_d: true,
+            zhie: true,
+            zlie: true,
+            yhie: true,
+            ylie: true,
+            xhie: true,
+            xlie: true,
+        };
+        self.write_accel_register(Register::INT1_CFG_A, cfg.bits())?;
+        self.route_int1_generator(pin)
+    }
+
+    /// Position currently recognized by the interrupt generator configured
+    /// with [`enable_orientation_detection()`](Lsm303agr::enable_orientation_detection)
+    ///
+    /// Returns `None` while the device is between positions.
+    pub fn accel_orientation(
+        &mut self,
+        config: &OrientationConfig,
+    ) -> Result<Option<Orientation>, Error<CommE, PinE>> {
+        let src = Int1SrcA::from(self.iface.read_accel_register(Register::INT1_SRC_A)?);
+        if !src.ia {
+            return Ok(None);
+        }
+        let axis = |high: bool, low: bool| {
+            if high {
+                1
+            } else if low {
+                -1
+            } else {
+                0
+            }
+        };
+        let sensor = Measurement {
+            x: axis(src.xh, src.xl),
+            y: axis(src.yh, src.yl),
+            z: axis(src.zh, src.zl),
+        };
+        let m = config.axes.apply(sensor);
+        Ok(config.position([m.x, m.y, m.z], 0))
+    }
+}
@@ -0,0 +1,245 @@
+//! Software step counter.
+//!
+//! The device has no hardware pedometer, so steps are detected in the
+//! accelerometer data:
+//! 1. The acceleration magnitude is computed, which makes the detection
+//!    independent of how the device is worn.
+//! 2. The magnitude is smoothed with a low-pass filter and the slowly
+//!    varying gravity component is removed with a second, slower one.
+//! 3. A step is a peak of the resulting signal above a threshold which
+//!    adapts to the recent peak heights.
+//! 4. Steps are only counted once several of them have occurred in a row at
+//!    a plausible cadence, which rejects isolated movements.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    AccelOutputDataRate, Error, Lsm303agr, Measurement, FIFO_CAPACITY,
+};
+
+/// Cutoff frequency of the smoothing filter in Hz
+const SMOOTHING_CUTOFF_HZ: f32 = 4.0;
+/// Cutoff frequency of the gravity filter in Hz
+const GRAVITY_CUTOFF_HZ: f32 = 0.3;
+/// Fraction of the average peak height used as threshold
+const THRESHOLD_RATIO: f32 = 0.4;
+
+/// Step counter configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepCounterConfig {
+    /// Minimum peak height in mg for a step
+    pub min_threshold_mg: u16,
+    /// Minimum time between two steps in milliseconds
+    pub min_interval_ms: u16,
+    /// Maximum time between two steps in milliseconds. After this the
+    /// walking sequence is considered finished.
+    pub max_interval_ms: u16,
+    /// Number of consecutive steps at a valid cadence required before steps
+    /// are counted
+    pub regulation_steps: u8,
+}
+
+impl Default for StepCounterConfig {
+    fn default() -> Self {
+        StepCounterConfig {
+            min_threshold_mg: 80,
+            min_interval_ms: 250,
+            max_interval_ms: 2000,
+            regulation_steps: 4,
+        }
+    }
+}
+
+/// Step counter
+///
+/// Feed it with all accelerometer measurements in mg at the output data rate
+/// given on creation, either one by one with
+/// [`update()`](StepCounter::update), in batches with
+/// [`update_batch()`](StepCounter::update_batch) or directly from the FIFO
+/// with [`update_step_counter()`](Lsm303agr::update_step_counter).
+/// An output data rate of 25 Hz to 100 Hz is recommended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepCounter {
+    config: StepCounterConfig,
+    smoothing_alpha: f32,
+    gravity_alpha: f32,
+    min_interval: u32,
+    max_interval: u32,
+    smoothed: f32,
+    gravity: f32,
+    initialized: bool,
+    threshold: f32,
+    average_peak: Option<f32>,
+    peak: Option<(f32, u32)>,
+    last_step: Option<u32>,
+    pending: u8,
+    counting: bool,
+    steps: u32,
+    sample: u32,
+}
+
+impl StepCounter {
+    /// Create a new step counter for measurements at the given output data
+    /// rate
+    pub fn new(odr: AccelOutputDataRate, config: StepCounterConfig) -> Self {
+        let odr_hz = f32::from(odr.frequency_hz());
+        let samples = |ms: u16| u32::from(ms) * u32::from(odr.frequency_hz()) / 1000;
+        StepCounter {
+            config,
+            smoothing_alpha: low_pass_alpha(SMOOTHING_CUTOFF_HZ, odr_hz),
+            gravity_alpha: low_pass_alpha(GRAVITY_CUTOFF_HZ, odr_hz),
+            min_interval: samples(config.min_interval_ms),
+            max_interval: samples(config.max_interval_ms),
+            smoothed: 0.0,
+            gravity: 0.0,
+            initialized: false,
+            threshold: f32::from(config.min_threshold_mg),
+            average_peak: None,
+            peak: None,
+            last_step: None,
+            pending: 0,
+            counting: false,
+            steps: 0,
+            sample: 0,
+        }
+    }
+
+    /// Step counter configuration
+    pub fn config(&self) -> &StepCounterConfig {
+        &self.config
+    }
+
+    /// Number of steps counted
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Whether a walking sequence is in progress, that is, steps are
+    /// currently being counted
+    pub fn is_walking(&self) -> bool {
+        self.counting
+    }
+
+    /// Reset the step count
+    pub fn reset_steps(&mut self) {
+        self.steps = 0;
+    }
+
+    /// Update the step counter with an accelerometer measurement in mg
+    ///
+    /// Returns the number of steps added to the count. Since steps are only
+    /// counted after a valid walking sequence has been recognized, this can
+    /// be more than one.
+    pub fn update(&mut self, measurement: Measurement) -> u32 {
+        let (x, y, z) = (
+            measurement.x as f32,
+            measurement.y as f32,
+            measurement.z as f32,
+        );
+        let magnitude = libm::sqrtf(x * x + y * y + z * z);
+        if !self.initialized {
+            self.smoothed = magnitude;
+            self.gravity = magnitude;
+            self.initialized = true;
+        }
+        self.smoothed += self.smoothing_alpha * (magnitude - self.smoothed);
+        self.gravity += self.gravity_alpha * (magnitude - self.gravity);
+        let signal = self.smoothed - self.gravity;
+        self.sample = self.sample.wrapping_add(1);
+
+        if let Some(last) = self.last_step {
+            if self.sample.wrapping_sub(last) > self.max_interval {
+                self.end_sequence();
+            }
+        }
+
+        match self.peak {
+            None if signal > self.threshold => {
+                self.peak = Some((signal, self.sample));
+                0
+            }
+            Some((height, _)) if signal > height => {
+                self.peak = Some((signal, self.sample));
+                0
+            }
+            Some((height, at)) if signal < 0.0 => {
+                self.peak = None;
+                self.step(height, at)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Update the step counter with consecutive accelerometer measurements
+    /// in mg
+    ///
+    /// Returns the number of steps added to the count.
+    pub fn update_batch(&mut self, measurements: &[Measurement]) -> u32 {
+        measurements.iter().map(|m| self.update(*m)).sum()
+    }
+
+    fn step(&mut self, height: f32, at: u32) -> u32 {
+        if let Some(last) = self.last_step {
+            if at.wrapping_sub(last) < self.min_interval {
+                // Too fast for walking or running, e.g. shaking.
+                self.end_sequence();
+                return 0;
+            }
+        }
+        self.last_step = Some(at);
+        let average = match self.average_peak {
+            Some(average) => average * 0.75 + height * 0.25,
+            None => height,
+        };
+        self.average_peak = Some(average);
+        self.threshold = f32::from(self.config.min_threshold_mg).max(average * THRESHOLD_RATIO);
+        if self.counting {
+            self.steps += 1;
+            return 1;
+        }
+        self.pending += 1;
+        if self.pending >= self.config.regulation_steps {
+            let counted = u32::from(self.pending);
+            self.steps += counted;
+            self.counting = true;
+            self.pending = 0;
+            counted
+        } else {
+            0
+        }
+    }
+
+    fn end_sequence(&mut self) {
+        self.last_step = None;
+        self.pending = 0;
+        self.counting = false;
+        self.average_peak = None;
+        self.threshold = f32::from(self.config.min_threshold_mg);
+    }
+}
+
+/// Smoothing factor of a first-order low-pass filter
+fn low_pass_alpha(cutoff_hz: f32, odr_hz: f32) -> f32 {
+    1.0 - libm::expf(-2.0 * core::f32::consts::PI * cutoff_hz / odr_hz)
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Read all samples stored in the accelerometer FIFO and feed them to
+    /// the step counter
+    ///
+    /// The FIFO must have been enabled in stream mode with
+    /// [`set_fifo_mode()`](Lsm303agr::set_fifo_mode) and read before it
+    /// overruns, so that no samples are lost.
+    ///
+    /// Returns the number of steps added to the count.
+    pub fn update_step_counter(
+        &mut self,
+        counter: &mut StepCounter,
+    ) -> Result<u32, Error<CommE, PinE>> {
+        let mut buffer = [Measurement::default(); FIFO_CAPACITY];
+        let len = self.fifo_data(&mut buffer)?;
+        Ok(counter.update_batch(&buffer[..len]))
+    }
+}
@@ -0,0 +1,232 @@
+//! Binary encoding of calibration and configuration for persistent storage.
+//!
+//! The encoding has a fixed size of [`Settings::ENCODED_LEN`] bytes. All
+//! values are little-endian:
+//!
+//! | Offset | Size | Content                                                 |
+//! |--------|------|---------------------------------------------------------|
+//! | 0      | 4    | Magic: `L3AG`                                           |
+//! | 4      | 1    | Format version: 1                                       |
+//! | 5      | 1    | Flags: bit 0 accelerometer calibration present, bit 1 magnetometer calibration present |
+//! | 6      | 2    | Reserved (0)                                            |
+//! | 8      | 1    | Accelerometer ODR (0: none, 1-10: `Hz1` to `Khz5_376LowPower`) |
+//! | 9      | 1    | Accelerometer mode (0: power down, 1: low power, 2: normal, 3: high resolution) |
+//! | 10     | 1    | Accelerometer scale (0-3: `G2` to `G16`)                 |
+//! | 11     | 1    | Magnetometer ODR (0-3: `Hz10` to `Hz100`)                |
+//! | 12     | 4    | Reserved (0)                                            |
+//! | 16     | 24   | Accelerometer offset (3 × f32) and scale (3 × f32)       |
+//! | 40     | 52   | Magnetometer hard-iron (3 × f32), soft-iron (9 × f32, row-major) and field strength (f32) |
+//! | 92     | 32   | Reserved (0)                                            |
+//! | 124    | 4    | CRC-32 (IEEE 802.3) of bytes 0 to 123                   |
+//!
+//! Calibration sections whose flag is not set are zero.
+
+use crate::{
+    AccelCalibration, AccelMode, AccelOutputDataRate, AccelScale, DeviceConfig, MagCalibration,
+    MagOutputDataRate,
+};
+
+const MAGIC: [u8; 4] = *b"L3AG";
+const VERSION: u8 = 1;
+const FLAG_ACCEL_CALIBRATION: u8 = 1;
+const FLAG_MAG_CALIBRATION: u8 = 1 << 1;
+const CONFIG_OFFSET: usize = 8;
+const ACCEL_CALIBRATION_OFFSET: usize = 16;
+const MAG_CALIBRATION_OFFSET: usize = 40;
+const CRC_OFFSET: usize = 124;
+
+const ACCEL_ODRS: [AccelOutputDataRate; 10] = [
+    AccelOutputDataRate::Hz1,
+    AccelOutputDataRate::Hz10,
+    AccelOutputDataRate::Hz25,
+    AccelOutputDataRate::Hz50,
+    AccelOutputDataRate::Hz100,
+    AccelOutputDataRate::Hz200,
+    AccelOutputDataRate::Hz400,
+    AccelOutputDataRate::Khz1_344,
+    AccelOutputDataRate::Khz1_620LowPower,
+    AccelOutputDataRate::Khz5_376LowPower,
+];
+const ACCEL_MODES: [AccelMode; 4] = [
+    AccelMode::PowerDown,
+    AccelMode::LowPower,
+    AccelMode::Normal,
+    AccelMode::HighResolution,
+];
+const ACCEL_SCALES: [AccelScale; 4] = [
+    AccelScale::G2,
+    AccelScale::G4,
+    AccelScale::G8,
+    AccelScale::G16,
+];
+const MAG_ODRS: [MagOutputDataRate; 4] = [
+    MagOutputDataRate::Hz10,
+    MagOutputDataRate::Hz20,
+    MagOutputDataRate::Hz50,
+    MagOutputDataRate::Hz100,
+];
+
+/// Persistent settings: device configuration and calibration
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Settings {
+    /// Device configuration
+    pub config: DeviceConfig,
+    /// Accelerometer calibration
+    pub accel_calibration: Option<AccelCalibration>,
+    /// Magnetometer calibration
+    pub mag_calibration: Option<MagCalibration>,
+}
+
+/// Errors decoding persisted settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingsDecodeError {
+    /// The data does not have the expected length
+    InvalidLength,
+    /// The data does not start with the expected magic bytes
+    InvalidMagic,
+    /// The format version is not supported
+    UnsupportedVersion(u8),
+    /// The checksum does not match the data
+    InvalidChecksum,
+    /// A field contains an invalid value
+    InvalidValue,
+}
+
+impl Settings {
+    /// Length of the binary encoding in bytes
+    pub const ENCODED_LEN: usize = 128;
+
+    /// Encode the settings
+    pub fn encode(&self) -> [u8; Settings::ENCODED_LEN] {
+        let mut data = [0; Settings::ENCODED_LEN];
+        data[..4].copy_from_slice(&MAGIC);
+        data[4] = VERSION;
+
+        let config = &self.config;
+        data[CONFIG_OFFSET] = match config.accel_odr {
+            None => 0,
+            Some(odr) => index_of(&ACCEL_ODRS, odr) + 1,
+        };
+        data[CONFIG_OFFSET + 1] = index_of(&ACCEL_MODES, config.accel_mode);
+        data[CONFIG_OFFSET + 2] = index_of(&ACCEL_SCALES, config.accel_scale);
+        data[CONFIG_OFFSET + 3] = index_of(&MAG_ODRS, config.mag_odr);
+
+        if let Some(calibration) = &self.accel_calibration {
+            data[5] |= FLAG_ACCEL_CALIBRATION;
+            let values = calibration.offset.iter().chain(calibration.scale.iter());
+            write_f32s(&mut data[ACCEL_CALIBRATION_OFFSET..], values);
+        }
+        if let Some(calibration) = &self.mag_calibration {
+            data[5] |= FLAG_MAG_CALIBRATION;
+            let values = calibration
+                .hard_iron
+                .iter()
+                .chain(calibration.soft_iron.iter().flatten())
+                .chain(core::iter::once(&calibration.field_strength));
+            write_f32s(&mut data[MAG_CALIBRATION_OFFSET..], values);
+        }
+
+        let crc = crc32(&data[..CRC_OFFSET]);
+        data[CRC_OFFSET..].copy_from_slice(&crc.to_le_bytes());
+        data
+    }
+
+    /// Decode settings encoded with [`encode()`](Settings::encode)
+    pub fn decode(data: &[u8]) -> Result<Self, SettingsDecodeError> {
+        if data.len() != Settings::ENCODED_LEN {
+            return Err(SettingsDecodeError::InvalidLength);
+        }
+        if data[..4] != MAGIC {
+            return Err(SettingsDecodeError::InvalidMagic);
+        }
+        if data[4] != VERSION {
+            return Err(SettingsDecodeError::UnsupportedVersion(data[4]));
+        }
+        if crc32(&data[..CRC_OFFSET]) != read_u32(&data[CRC_OFFSET..]) {
+            return Err(SettingsDecodeError::InvalidChecksum);
+        }
+
+        let flags = data[5];
+        if flags & !(FLAG_ACCEL_CALIBRATION | FLAG_MAG_CALIBRATION) != 0 {
+            return Err(SettingsDecodeError::InvalidValue);
+        }
+        let config = DeviceConfig {
+            accel_odr: match data[CONFIG_OFFSET] {
+                0 => None,
+                index => Some(lookup(&ACCEL_ODRS, index - 1)?),
+            },
+            accel_mode: lookup(&ACCEL_MODES, data[CONFIG_OFFSET + 1])?,
+            accel_scale: lookup(&ACCEL_SCALES, data[CONFIG_OFFSET + 2])?,
+            mag_odr: lookup(&MAG_ODRS, data[CONFIG_OFFSET + 3])?,
+        };
+
+        let accel_calibration = if flags & FLAG_ACCEL_CALIBRATION != 0 {
+            let v = read_f32s::<6>(&data[ACCEL_CALIBRATION_OFFSET..]);
+            Some(AccelCalibration {
+                offset: [v[0], v[1], v[2]],
+                scale: [v[3], v[4], v[5]],
+            })
+        } else {
+            None
+        };
+        let mag_calibration = if flags & FLAG_MAG_CALIBRATION != 0 {
+            let v = read_f32s::<13>(&data[MAG_CALIBRATION_OFFSET..]);
+            Some(MagCalibration {
+                hard_iron: [v[0], v[1], v[2]],
+                soft_iron: [[v[3], v[4], v[5]], [v[6], v[7], v[8]], [v[9], v[10], v[11]]],
+                field_strength: v[12],
+            })
+        } else {
+            None
+        };
+
+        Ok(Settings {
+            config,
+            accel_calibration,
+            mag_calibration,
+        })
+    }
+}
+
+fn index_of<T: PartialEq>(values: &[T], value: T) -> u8 {
+    values.iter().position(|v| *v == value).unwrap_or(0) as u8
+}
+
+fn lookup<T: Copy>(values: &[T], index: u8) -> Result<T, SettingsDecodeError> {
+    values
+        .get(usize::from(index))
+        .cloned()
+        .ok_or(SettingsDecodeError::InvalidValue)
+}
+
+fn write_f32s<'a>(data: &mut [u8], values: impl Iterator<Item = &'a f32>) {
+    for (chunk, value) in data.chunks_exact_mut(4).zip(values) {
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn read_f32s<const N: usize>(data: &[u8]) -> [f32; N] {
+    let mut values = [0.0; N];
+    for (value, chunk) in values.iter_mut().zip(data.chunks_exact(4)) {
+        *value = f32::from_bits(read_u32(chunk));
+    }
+    values
+}
+
+/// CRC-32 (IEEE 802.3, reflected, polynomial 0xEDB88320)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
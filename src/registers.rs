@@ -0,0 +1,772 @@
+//! Typed register map.
+//!
+//! Every accelerometer and magnetometer register with bitfields has a struct
+//! here with one public field per bitfield, named after the datasheet. Single
+//! bits are `bool` and wider fields are `u8`, right-aligned. Reserved bits
+//! read as and are written as zero.
+//!
+//! [`Lsm303agr::dump_registers()`] reads the whole register map at once for
+//! diagnostics, here against the simulator (`sim` feature):
+//!
+//! ```
+//! # #[cfg(feature = "sim")]
+//! # {
+//! use lsm303agr::{registers::CtrlReg1A, sim::Simulator, AccelOutputDataRate, Lsm303agr};
+//!
+//! let sim = Simulator::new();
+//! let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+//! sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+//! let dump = sensor.dump_registers().unwrap();
+//! assert_eq!(dump.accel.who_am_i_a, 0x33);
+//! assert_eq!(
+//!     dump.accel.ctrl_reg1_a,
+//!     CtrlReg1A {
+//!         odr: 4,
+//!         lp_en: false,
+//!         z_en: true,
+//!         y_en: true,
+//!         x_en: true,
+//!     }
+//! );
+//! assert_eq!(dump.accel.ctrl_reg1_a.bits(), 0x47);
+//! # }
+//! ```
+
+use crate::{
+    interface::{ReadData, WriteData},
+    Error, Lsm303agr, Register, UnscaledMeasurement,
+};
+
+/// Conversion between a bitfield and its value
+trait Field: Sized {
+    fn decode(bits: u8, msb: u8, lsb: u8) -> Self;
+    fn encode(self, msb: u8, lsb: u8) -> u8;
+}
+
+fn field_mask(msb: u8, lsb: u8) -> u8 {
+    (0xFF >> (7 - msb)) & (0xFF << lsb)
+}
+
+impl Field for bool {
+    fn decode(bits: u8, msb: u8, lsb: u8) -> Self {
+        (bits & field_mask(msb, lsb)) != 0
+    }
+
+    fn encode(self, msb: u8, lsb: u8) -> u8 {
+        if self {
+            field_mask(msb, lsb)
+        } else {
+            0
+        }
+    }
+}
+
+impl Field for u8 {
+    fn decode(bits: u8, msb: u8, lsb: u8) -> Self {
+        (bits & field_mask(msb, lsb)) >> lsb
+    }
+
+    fn encode(self, msb: u8, lsb: u8) -> u8 {
+        (self << lsb) & field_mask(msb, lsb)
+    }
+}
+
+/// Define register structs
+///
+/// Fields are given as `name: type = [msb:lsb]`, or `[bit]` for a single bit.
+macro_rules! registers {
+    (@lsb $msb:literal) => { $msb };
+    (@lsb $msb:literal $lsb:literal) => { $lsb };
+    ($(
+        $(#[$meta:meta])*
+        $name:ident = $address:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field:ident: $ty:ty = [$msb:literal $(: $lsb:literal)?],
+            )*
+        }
+    )*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+            pub struct $name {
+                $(
+                    $(#[$field_meta])*
+                    pub $field: $ty,
+                )*
+            }
+
+            impl $name {
+                /// Register address
+                pub const ADDRESS: u8 = Register::$address;
+
+                /// Decode a register value
+                pub fn from_bits(bits: u8) -> Self {
+                    $name {
+                        $(
+                            $field: Field::decode(
+                                bits,
+                                $msb,
+                                registers!(@lsb $msb $($lsb)?),
+                            ),
+                        )*
+                    }
+                }
+
+                /// Encode into a register value
+                ///
+                /// Field values wider than the field are truncated.
+                pub fn bits(self) -> u8 {
+                    0 $(| Field::encode(self.$field, $msb, registers!(@lsb $msb $($lsb)?)))*
+                }
+            }
+
+            impl From<u8> for $name {
+                fn from(bits: u8) -> Self {
+                    Self::from_bits(bits)
+                }
+            }
+
+            impl From<$name> for u8 {
+                fn from(register: $name) -> Self {
+                    register.bits()
+                }
+            }
+        )*
+    };
+}
+
+registers! {
+    /// `STATUS_REG_AUX_A`: Temperature data status
+    StatusRegAuxA = STATUS_REG_AUX_A {
+        /// Temperature data overrun
+        tor: bool = [6],
+        /// New temperature data available
+        tda: bool = [2],
+    }
+
+    /// `TEMP_CFG_REG_A`: Temperature sensor configuration
+    TempCfgRegA = TEMP_CFG_REG_A {
+        /// Temperature sensor enable, `0b11` to enable
+        temp_en: u8 = [7:6],
+    }
+
+    /// `CTRL_REG1_A`: Accelerometer output data rate and axes
+    CtrlReg1A = CTRL_REG1_A {
+        /// Output data rate, 0 is power-down
+        odr: u8 = [7:4],
+        /// Low-power mode enable
+        lp_en: bool = [3],
+        /// Z axis enable
+        z_en: bool = [2],
+        /// Y axis enable
+        y_en: bool = [1],
+        /// X axis enable
+        x_en: bool = [0],
+    }
+
+    /// `CTRL_REG2_A`: Accelerometer high-pass filter
+    CtrlReg2A = CTRL_REG2_A {
+        /// High-pass filter mode
+        hpm: u8 = [7:6],
+        /// High-pass filter cutoff frequency
+        hpcf: u8 = [5:4],
+        /// Filtered data selection
+        fds: bool = [3],
+        /// High-pass filter enabled for click
+        hpclick: bool = [2],
+        /// High-pass filter enabled for interrupt 2
+        hpis2: bool = [1],
+        /// High-pass filter enabled for interrupt 1
+        hpis1: bool = [0],
+    }
+
+    /// `CTRL_REG3_A`: INT1 pin routing
+    CtrlReg3A = CTRL_REG3_A {
+        /// Click interrupt on INT1
+        i1_click: bool = [7],
+        /// Interrupt generator 1 on INT1
+        i1_aoi1: bool = [6],
+        /// Interrupt generator 2 on INT1
+        i1_aoi2: bool = [5],
+        /// Data ready 1 on INT1
+        i1_drdy1: bool = [4],
+        /// Data ready 2 on INT1
+        i1_drdy2: bool = [3],
+        /// FIFO watermark on INT1
+        i1_wtm: bool = [2],
+        /// FIFO overrun on INT1
+        i1_overrun: bool = [1],
+    }
+
+    /// `CTRL_REG4_A`: Accelerometer scale, resolution and self-test
+    CtrlReg4A = CTRL_REG4_A {
+        /// Block data update
+        bdu: bool = [7],
+        /// Big endian data selection
+        ble: bool = [6],
+        /// Full scale selection
+        fs: u8 = [5:4],
+        /// High-resolution mode enable
+        hr: bool = [3],
+        /// Self-test mode selection, 0 is disabled
+        st: u8 = [2:1],
+        /// 3-wire SPI enable
+        spi_enable: bool = [0],
+    }
+
+    /// `CTRL_REG5_A`: Boot, FIFO and interrupt latching
+    CtrlReg5A = CTRL_REG5_A {
+        /// Reboot accelerometer memory content
+        boot: bool = [7],
+        /// FIFO enable
+        fifo_en: bool = [6],
+        /// Latch interrupt 1 request
+        lir_int1: bool = [3],
+        /// 4D detection on interrupt 1
+        d4d_int1: bool = [2],
+        /// Latch interrupt 2 request
+        lir_int2: bool = [1],
+        /// 4D detection on interrupt 2
+        d4d_int2: bool = [0],
+    }
+
+    /// `CTRL_REG6_A`: INT2 pin routing
+    CtrlReg6A = CTRL_REG6_A {
+        /// Click interrupt on INT2
+        i2_click_en: bool = [7],
+        /// Interrupt generator 1 on INT2
+        i2_int1: bool = [6],
+        /// Interrupt generator 2 on INT2
+        i2_int2: bool = [5],
+        /// Boot on INT2
+        boot_i2: bool = [4],
+        /// Activity interrupt on INT2
+        p2_act: bool = [3],
+        /// Interrupts active low
+        h_lactive: bool = [1],
+    }
+
+    /// `STATUS_REG_A`: Accelerometer data status
+    StatusRegA = STATUS_REG_A {
+        /// X, Y and Z data overrun
+        zyxor: bool = [7],
+        /// Z data overrun
+        zor: bool = [6],
+        /// Y data overrun
+        yor: bool = [5],
+        /// X data overrun
+        xor: bool = [4],
+        /// New X, Y and Z data available
+        zyxda: bool = [3],
+        /// New Z data available
+        zda: bool = [2],
+        /// New Y data available
+        yda: bool = [1],
+        /// New X data available
+        xda: bool = [0],
+    }
+
+    /// `FIFO_CTRL_REG_A`: FIFO mode and watermark
+    FifoCtrlRegA = FIFO_CTRL_REG_A {
+        /// FIFO mode selection
+        fm: u8 = [7:6],
+        /// Trigger selection, INT2 if set
+        tr: bool = [5],
+        /// FIFO watermark level
+        fth: u8 = [4:0],
+    }
+
+    /// `FIFO_SRC_REG_A`: FIFO status
+    FifoSrcRegA = FIFO_SRC_REG_A {
+        /// FIFO content exceeds the watermark level
+        wtm: bool = [7],
+        /// FIFO is full and a sample was overwritten
+        ovrn_fifo: bool = [6],
+        /// FIFO is empty
+        empty: bool = [5],
+        /// Number of unread samples
+        fss: u8 = [4:0],
+    }
+
+    /// `INT1_CFG_A`: Interrupt generator 1 configuration
+    Int1CfgA = INT1_CFG_A {
+        /// AND combination of interrupt events
+        aoi: bool = [7],
+        /// 6-direction detection
+        six_d: bool = [6],
+        /// Z high event enable
+        zhie: bool = [5],
+        /// Z low event enable
+        zlie: bool = [4],
+        /// Y high event enable
+        yhie: bool = [3],
+        /// Y low event enable
+        ylie: bool = [2],
+        /// X high event enable
+        xhie: bool = [1],
+        /// X low event enable
+        xlie: bool = [0],
+    }
+
+    /// `INT1_SRC_A`: Interrupt generator 1 source
+    ///
+    /// Reading the register clears a latched interrupt.
+    Int1SrcA = INT1_SRC_A {
+        /// Interrupt active
+        ia: bool = [6],
+        /// Z high event
+        zh: bool = [5],
+        /// Z low event
+        zl: bool = [4],
+        /// Y high event
+        yh: bool = [3],
+        /// Y low event
+        yl: bool = [2],
+        /// X high event
+        xh: bool = [1],
+        /// X low event
+        xl: bool = [0],
+    }
+
+    /// `INT1_THS_A`: Interrupt generator 1 threshold
+    Int1ThsA = INT1_THS_A {
+        /// Threshold, the LSB depends on the full scale
+        ths: u8 = [6:0],
+    }
+
+    /// `INT1_DURATION_A`: Interrupt generator 1 duration
+    Int1DurationA = INT1_DURATION_A {
+        /// Minimum event duration in samples
+        d: u8 = [6:0],
+    }
+
+    /// `INT2_CFG_A`: Interrupt generator 2 configuration
+    Int2CfgA = INT2_CFG_A {
+        /// AND combination of interrupt events
+        aoi: bool = [7],
+        /// 6-direction detection
+        six_d: bool = [6],
+        /// Z high event enable
+        zhie: bool = [5],
+        /// Z low event enable
+        zlie: bool = [4],
+        /// Y high event enable
+        yhie: bool = [3],
+        /// Y low event enable
+        ylie: bool = [2],
+        /// X high event enable
+        xhie: bool = [1],
+        /// X low event enable
+        xlie: bool = [0],
+    }
+
+    /// `INT2_SRC_A`: Interrupt generator 2 source
+    ///
+    /// Reading the register clears a latched interrupt.
+    Int2SrcA = INT2_SRC_A {
+        /// Interrupt active
+        ia: bool = [6],
+        /// Z high event
+        zh: bool = [5],
+        /// Z low event
+        zl: bool = [4],
+        /// Y high event
+        yh: bool = [3],
+        /// Y low event
+        yl: bool = [2],
+        /// X high event
+        xh: bool = [1],
+        /// X low event
+        xl: bool = [0],
+    }
+
+    /// `INT2_THS_A`: Interrupt generator 2 threshold
+    Int2ThsA = INT2_THS_A {
+        /// Threshold, the LSB depends on the full scale
+        ths: u8 = [6:0],
+    }
+
+    /// `INT2_DURATION_A`: Interrupt generator 2 duration
+    Int2DurationA = INT2_DURATION_A {
+        /// Minimum event duration in samples
+        d: u8 = [6:0],
+    }
+
+    /// `CLICK_CFG_A`: Click detection configuration
+    ClickCfgA = CLICK_CFG_A {
+        /// Double click on Z enable
+        zd: bool = [5],
+        /// Single click on Z enable
+        zs: bool = [4],
+        /// Double click on Y enable
+        yd: bool = [3],
+        /// Single click on Y enable
+        ys: bool = [2],
+        /// Double click on X enable
+        xd: bool = [1],
+        /// Single click on X enable
+        xs: bool = [0],
+    }
+
+    /// `CLICK_SRC_A`: Click detection source
+    ClickSrcA = CLICK_SRC_A {
+        /// Interrupt active
+        ia: bool = [6],
+        /// Double click detected
+        dclick: bool = [5],
+        /// Single click detected
+        sclick: bool = [4],
+        /// Click sign, negative if set
+        sign: bool = [3],
+        /// Click on Z detected
+        z: bool = [2],
+        /// Click on Y detected
+        y: bool = [1],
+        /// Click on X detected
+        x: bool = [0],
+    }
+
+    /// `CLICK_THS_A`: Click threshold
+    ClickThsA = CLICK_THS_A {
+        /// Latch click interrupt
+        lir_click: bool = [7],
+        /// Click threshold, the LSB depends on the full scale
+        ths: u8 = [6:0],
+    }
+
+    /// `TIME_LIMIT_A`: Click time limit
+    TimeLimitA = TIME_LIMIT_A {
+        /// Maximum click duration in samples
+        tli: u8 = [6:0],
+    }
+
+    /// `ACT_THS_A`: Sleep-to-wake activation threshold
+    ActThsA = ACT_THS_A {
+        /// Threshold, the LSB depends on the full scale
+        acth: u8 = [6:0],
+    }
+
+    /// `CFG_REG_A_M`: Magnetometer mode and output data rate
+    CfgRegAM = CFG_REG_A_M {
+        /// Temperature compensation enable
+        comp_temp_en: bool = [7],
+        /// Reboot magnetometer memory content
+        reboot: bool = [6],
+        /// Reset the configuration and user registers
+        soft_rst: bool = [5],
+        /// Low-power mode enable
+        lp: bool = [4],
+        /// Output data rate
+        odr: u8 = [3:2],
+        /// Mode: 0 continuous, 1 single, 2 and 3 idle
+        md: u8 = [1:0],
+    }
+
+    /// `CFG_REG_B_M`: Magnetometer offset cancellation and filter
+    CfgRegBM = CFG_REG_B_M {
+        /// Offset cancellation in single measurement mode
+        off_canc_one_shot: bool = [4],
+        /// Interrupt checked after hard-iron correction
+        int_on_data_off: bool = [3],
+        /// Set pulse frequency, only at power-on if set
+        set_freq: bool = [2],
+        /// Offset cancellation enable
+        off_canc: bool = [1],
+        /// Digital low-pass filter enable
+        lpf: bool = [0],
+    }
+
+    /// `CFG_REG_C_M`: Magnetometer interface and self-test
+    CfgRegCM = CFG_REG_C_M {
+        /// Data ready interrupt on the INT_MAG_PIN
+        int_mag_pin: bool = [6],
+        /// Disable the I2C interface
+        i2c_dis: bool = [5],
+        /// Block data update
+        bdu: bool = [4],
+        /// Big endian data selection
+        ble: bool = [3],
+        /// Self-test enable
+        self_test: bool = [1],
+        /// Data ready on the INT_MAG pin
+        int_mag: bool = [0],
+    }
+
+    /// `INT_CTRL_REG_M`: Magnetometer interrupt configuration
+    IntCtrlRegM = INT_CTRL_REG_M {
+        /// X axis interrupt enable
+        xien: bool = [7],
+        /// Y axis interrupt enable
+        yien: bool = [6],
+        /// Z axis interrupt enable
+        zien: bool = [5],
+        /// Interrupt active high
+        iea: bool = [2],
+        /// Latch interrupt request
+        iel: bool = [1],
+        /// Interrupt enable
+        ien: bool = [0],
+    }
+
+    /// `INT_SOURCE_REG_M`: Magnetometer interrupt source
+    ///
+    /// Reading the register clears a latched interrupt.
+    IntSourceRegM = INT_SOURCE_REG_M {
+        /// X exceeds the positive threshold
+        p_th_s_x: bool = [7],
+        /// Y exceeds the positive threshold
+        p_th_s_y: bool = [6],
+        /// Z exceeds the positive threshold
+        p_th_s_z: bool = [5],
+        /// X exceeds the negative threshold
+        n_th_s_x: bool = [4],
+        /// Y exceeds the negative threshold
+        n_th_s_y: bool = [3],
+        /// Z exceeds the negative threshold
+        n_th_s_z: bool = [2],
+        /// Internal measurement range overflow
+        mroi: bool = [1],
+        /// Interrupt active
+        int: bool = [0],
+    }
+
+    /// `STATUS_REG_M`: Magnetometer data status
+    StatusRegM = STATUS_REG_M {
+        /// X, Y and Z data overrun
+        zyxor: bool = [7],
+        /// Z data overrun
+        zor: bool = [6],
+        /// Y data overrun
+        yor: bool = [5],
+        /// X data overrun
+        xor: bool = [4],
+        /// New X, Y and Z data available
+        zyxda: bool = [3],
+        /// New Z data available
+        zda: bool = [2],
+        /// New Y data available
+        yda: bool = [1],
+        /// New X data available
+        xda: bool = [0],
+    }
+}
+
+impl CfgRegAM {
+    /// `md` value of continuous mode
+    pub const MD_CONTINUOUS: u8 = 0b00;
+    /// `md` value of single measurement mode
+    pub const MD_SINGLE: u8 = 0b01;
+    /// `md` value of idle mode
+    pub const MD_IDLE: u8 = 0b11;
+}
+
+/// Accelerometer registers
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AccelRegisters {
+    /// `STATUS_REG_AUX_A`
+    pub status_reg_aux_a: StatusRegAuxA,
+    /// `OUT_TEMP_L_A` and `OUT_TEMP_H_A`
+    pub out_temp_a: i16,
+    /// `INT_COUNTER_REG_A`
+    pub int_counter_reg_a: u8,
+    /// `WHO_AM_I_A`
+    pub who_am_i_a: u8,
+    /// `TEMP_CFG_REG_A`
+    pub temp_cfg_reg_a: TempCfgRegA,
+    /// `CTRL_REG1_A`
+    pub ctrl_reg1_a: CtrlReg1A,
+    /// `CTRL_REG2_A`
+    pub ctrl_reg2_a: CtrlReg2A,
+    /// `CTRL_REG3_A`
+    pub ctrl_reg3_a: CtrlReg3A,
+    /// `CTRL_REG4_A`
+    pub ctrl_reg4_a: CtrlReg4A,
+    /// `CTRL_REG5_A`
+    pub ctrl_reg5_a: CtrlReg5A,
+    /// `CTRL_REG6_A`
+    pub ctrl_reg6_a: CtrlReg6A,
+    /// `REFERENCE/DATACAPTURE_A`
+    pub reference_a: u8,
+    /// `STATUS_REG_A`
+    pub status_reg_a: StatusRegA,
+    /// `OUT_X_L_A` to `OUT_Z_H_A`
+    pub out_a: UnscaledMeasurement,
+    /// `FIFO_CTRL_REG_A`
+    pub fifo_ctrl_reg_a: FifoCtrlRegA,
+    /// `FIFO_SRC_REG_A`
+    pub fifo_src_reg_a: FifoSrcRegA,
+    /// `INT1_CFG_A`
+    pub int1_cfg_a: Int1CfgA,
+    /// `INT1_SRC_A`
+    pub int1_src_a: Int1SrcA,
+    /// `INT1_THS_A`
+    pub int1_ths_a: Int1ThsA,
+    /// `INT1_DURATION_A`
+    pub int1_duration_a: Int1DurationA,
+    /// `INT2_CFG_A`
+    pub int2_cfg_a: Int2CfgA,
+    /// `INT2_SRC_A`
+    pub int2_src_a: Int2SrcA,
+    /// `INT2_THS_A`
+    pub int2_ths_a: Int2ThsA,
+    /// `INT2_DURATION_A`
+    pub int2_duration_a: Int2DurationA,
+    /// `CLICK_CFG_A`
+    pub click_cfg_a: ClickCfgA,
+    /// `CLICK_SRC_A`
+    pub click_src_a: ClickSrcA,
+    /// `CLICK_THS_A`
+    pub click_ths_a: ClickThsA,
+    /// `TIME_LIMIT_A`
+    pub time_limit_a: TimeLimitA,
+    /// `TIME_LATENCY_A`
+    pub time_latency_a: u8,
+    /// `TIME_WINDOW_A`
+    pub time_window_a: u8,
+    /// `ACT_THS_A`
+    pub act_ths_a: ActThsA,
+    /// `ACT_DUR_A`
+    pub act_dur_a: u8,
+}
+
+/// Magnetometer registers
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MagRegisters {
+    /// `OFFSET_X_REG_L_M` to `OFFSET_Z_REG_H_M`
+    pub offset_m: UnscaledMeasurement,
+    /// `WHO_AM_I_M`
+    pub who_am_i_m: u8,
+    /// `CFG_REG_A_M`
+    pub cfg_reg_a_m: CfgRegAM,
+    /// `CFG_REG_B_M`
+    pub cfg_reg_b_m: CfgRegBM,
+    /// `CFG_REG_C_M`
+    pub cfg_reg_c_m: CfgRegCM,
+    /// `INT_CTRL_REG_M`
+    pub int_ctrl_reg_m: IntCtrlRegM,
+    /// `INT_SOURCE_REG_M`
+    pub int_source_reg_m: IntSourceRegM,
+    /// `INT_THS_L_REG_M` and `INT_THS_H_REG_M`
+    pub int_ths_reg_m: u16,
+    /// `STATUS_REG_M`
+    pub status_reg_m: StatusRegM,
+    /// `OUTX_L_REG_M` to `OUTZ_H_REG_M`
+    pub out_m: UnscaledMeasurement,
+}
+
+/// Content of all accelerometer and magnetometer registers
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RegisterDump {
+    /// Accelerometer registers
+    pub accel: AccelRegisters,
+    /// Magnetometer registers
+    pub mag: MagRegisters,
+}
+
+fn unscaled(data: (u16, u16, u16)) -> UnscaledMeasurement {
+    UnscaledMeasurement {
+        x: data.0 as i16,
+        y: data.1 as i16,
+        z: data.2 as i16,
+    }
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Read all accelerometer and magnetometer registers
+    ///
+    /// Intended for diagnostics. The registers are read like in any other
+    /// access, so reading the source registers clears latched interrupts,
+    /// reading the output registers clears the data available flags and, if
+    /// the FIFO is enabled, reading `OUT_X_L_A` to `OUT_Z_H_A` consumes a
+    /// FIFO sample.
+    pub fn dump_registers(&mut self) -> Result<RegisterDump, Error<CommE, PinE>> {
+        Ok(RegisterDump {
+            accel: self.dump_accel_registers()?,
+            mag: self.dump_mag_registers()?,
+        })
+    }
+
+    fn dump_accel_registers(&mut self) -> Result<AccelRegisters, Error<CommE, PinE>> {
+        let iface = &mut self.iface;
+        let mut read = |register| iface.read_accel_register(register);
+        let registers = AccelRegisters {
+            status_reg_aux_a: read(Register::STATUS_REG_AUX_A)?.into(),
+            out_temp_a: 0,
+            int_counter_reg_a: read(Register::INT_COUNTER_REG_A)?,
+            who_am_i_a: read(Register::WHO_AM_I_A)?,
+            temp_cfg_reg_a: read(Register::TEMP_CFG_REG_A)?.into(),
+            ctrl_reg1_a: read(Register::CTRL_REG1_A)?.into(),
+            ctrl_reg2_a: read(Register::CTRL_REG2_A)?.into(),
+            ctrl_reg3_a: read(Register::CTRL_REG3_A)?.into(),
+            ctrl_reg4_a: read(Register::CTRL_REG4_A)?.into(),
+            ctrl_reg5_a: read(Register::CTRL_REG5_A)?.into(),
+            ctrl_reg6_a: read(Register::CTRL_REG6_A)?.into(),
+            reference_a: read(Register::REFERENCE_A)?,
+            status_reg_a: read(Register::STATUS_REG_A)?.into(),
+            out_a: UnscaledMeasurement::default(),
+            fifo_ctrl_reg_a: read(Register::FIFO_CTRL_REG_A)?.into(),
+            fifo_src_reg_a: read(Register::FIFO_SRC_REG_A)?.into(),
+            int1_cfg_a: read(Register::INT1_CFG_A)?.into(),
+            int1_src_a: read(Register::INT1_SRC_A)?.into(),
+            int1_ths_a: read(Register::INT1_THS_A)?.into(),
+            int1_duration_a: read(Register::INT1_DURATION_A)?.into(),
+            int2_cfg_a: read(Register::INT2_CFG_A)?.into(),
+            int2_src_a: read(Register::INT2_SRC_A)?.into(),
+            int2_ths_a: read(Register::INT2_THS_A)?.into(),
+            int2_duration_a: read(Register::INT2_DURATION_A)?.into(),
+            click_cfg_a: read(Register::CLICK_CFG_A)?.into(),
+            click_src_a: read(Register::CLICK_SRC_A)?.into(),
+            click_ths_a: read(Register::CLICK_THS_A)?.into(),
+            time_limit_a: read(Register::TIME_LIMIT_A)?.into(),
+            time_latency_a: read(Register::TIME_LATENCY_A)?,
+            time_window_a: read(Register::TIME_WINDOW_A)?,
+            act_ths_a: read(Register::ACT_THS_A)?.into(),
+            act_dur_a: read(Register::ACT_DUR_A)?,
+        };
+        // The data registers are read last because reading them clears the
+        // status registers.
+        let out_temp_a = self
+            .iface
+            .read_accel_double_register(Register::OUT_TEMP_L_A)?;
+        let out_a = self
+            .iface
+            .read_accel_3_double_registers(Register::OUT_X_L_A)?;
+        Ok(AccelRegisters {
+            out_temp_a: out_temp_a as i16,
+            out_a: unscaled(out_a),
+            ..registers
+        })
+    }
+
+    fn dump_mag_registers(&mut self) -> Result<MagRegisters, Error<CommE, PinE>> {
+        let offset_m = self
+            .iface
+            .read_mag_3_double_registers(Register::OFFSET_X_REG_L_M)?;
+        let iface = &mut self.iface;
+        let mut read = |register| iface.read_mag_register(register);
+        let registers = MagRegisters {
+            offset_m: unscaled(offset_m),
+            who_am_i_m: read(Register::WHO_AM_I_M)?,
+            cfg_reg_a_m: read(Register::CFG_REG_A_M)?.into(),
+            cfg_reg_b_m: read(Register::CFG_REG_B_M)?.into(),
+            cfg_reg_c_m: read(Register::CFG_REG_C_M)?.into(),
+            int_ctrl_reg_m: read(Register::INT_CTRL_REG_M)?.into(),
+            int_source_reg_m: read(Register::INT_SOURCE_REG_M)?.into(),
+            int_ths_reg_m: u16::from(read(Register::INT_THS_L_REG_M)?)
+                | u16::from(read(Register::INT_THS_H_REG_M)?) << 8,
+            status_reg_m: read(Register::STATUS_REG_M)?.into(),
+            out_m: UnscaledMeasurement::default(),
+        };
+        let out_m = self
+            .iface
+            .read_mag_3_double_registers(Register::OUTX_L_REG_M)?;
+        Ok(MagRegisters {
+            out_m: unscaled(out_m),
+            ..registers
+        })
+    }
+}
@@ -0,0 +1,272 @@
+//! Accelerometer and magnetometer self-tests.
+//!
+//! In self-test mode an electrostatic force (accelerometer) or a current
+//! through an internal coil (magnetometer) deflects the sensor. The procedures
+//! follow the datasheet: the average output with and without self-test is
+//! compared and the difference must lie within the limits given in the
+//! datasheet. The sensor must be kept still during the test.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    registers::{CfgRegAM, CfgRegBM, CfgRegCM, CtrlReg4A},
+    AccelOutputDataRate, Error, Lsm303agr, Measurement, Register, Sensor,
+};
+use embedded_hal::blocking::delay::DelayUs;
+
+/// Accelerometer self-test limits in LSB at ±2g in normal mode
+const ACCEL_LIMITS: (i32, i32) = (17, 360);
+/// Magnetometer self-test limits in LSB
+const MAG_LIMITS: (i32, i32) = (15, 500);
+const ACCEL_SAMPLES: i32 = 5;
+const MAG_SAMPLES: i32 = 50;
+/// mg per LSB at ±2g in normal mode
+const ACCEL_SENSITIVITY: i32 = 4;
+/// nT per LSB
+const MAG_SENSITIVITY: i32 = 150;
+/// ODR 100 Hz, normal mode, all axes enabled
+const ACCEL_SELF_TEST_CTRL_REG1_A: u8 = 0x57;
+/// ODR 100 Hz, continuous mode
+const MAG_SELF_TEST_CFG_REG_A_M: u8 = 3 << 2;
+/// Polling interval at 100 Hz
+const POLL_INTERVAL_US: u32 = 1_250;
+/// Data-ready polls before giving up, 10 periods at 100 Hz
+const MAX_POLLS: u32 = 80;
+
+/// Self-test result
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelfTestResult {
+    /// Sensor tested
+    pub sensor: Sensor,
+    /// Difference between the average output with and without self-test, in
+    /// mg for the accelerometer and in nT for the magnetometer
+    pub difference: Measurement,
+    /// The difference of all axes is within the datasheet limits
+    pub passed: bool,
+}
+
+impl SelfTestResult {
+    fn new(sensor: Sensor, difference: [i32; 3], limits: (i32, i32), sensitivity: i32) -> Self {
+        SelfTestResult {
+            sensor,
+            difference: Measurement {
+                x: difference[0] * sensitivity,
+                y: difference[1] * sensitivity,
+                z: difference[2] * sensitivity,
+            },
+            passed: difference
+                .iter()
+                .all(|d| (limits.0..=limits.1).contains(&d.abs())),
+        }
+    }
+
+    /// Turn a failed self-test into `Error::SelfTestFailed`
+    pub fn check<CommE, PinE>(&self) -> Result<(), Error<CommE, PinE>> {
+        if self.passed {
+            Ok(())
+        } else {
+            Err(Error::SelfTestFailed {
+                sensor: self.sensor,
+                difference: self.difference,
+            })
+        }
+    }
+}
+
+fn to_axes(data: (u16, u16, u16), shift: u8) -> [i32; 3] {
+    [
+        i32::from(data.0 as i16 >> shift),
+        i32::from(data.1 as i16 >> shift),
+        i32::from(data.2 as i16 >> shift),
+    ]
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Run the accelerometer self-test
+    ///
+    /// Takes about 0.3 s. The accelerometer configuration is restored
+    /// afterwards, also if the test fails with an error.
+    ///
+    /// Returns `Error::Timeout` if the accelerometer stops providing new
+    /// data. A result outside of the limits is not an error, see
+    /// [`SelfTestResult::check()`].
+    pub fn accel_self_test<D: DelayUs<u32>>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<SelfTestResult, Error<CommE, PinE>> {
+        let (reg1, reg4, odr) = (self.ctrl_reg1_a, self.ctrl_reg4_a, self.accel_odr);
+        let result = self.run_accel_self_test(delay);
+        let restored = self
+            .write_accel_register(Register::CTRL_REG1_A, reg1.bits)
+            .and_then(|_| self.write_accel_register(Register::CTRL_REG4_A, reg4.bits));
+        self.ctrl_reg1_a = reg1;
+        self.ctrl_reg4_a = reg4;
+        self.accel_odr = odr;
+        let result = result?;
+        restored?;
+        Ok(result)
+    }
+
+    fn run_accel_self_test<D: DelayUs<u32>>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<SelfTestResult, Error<CommE, PinE>> {
+        // ±2g, normal mode
+        let reg4 = CtrlReg4A {
+            bdu: true,
+            ..Default::default()
+        };
+        self.write_accel_register(Register::CTRL_REG4_A, reg4.bits())?;
+        self.ctrl_reg4_a = reg4.bits().into();
+        self.write_accel_register(Register::CTRL_REG1_A, ACCEL_SELF_TEST_CTRL_REG1_A)?;
+        self.ctrl_reg1_a = ACCEL_SELF_TEST_CTRL_REG1_A.into();
+        self.accel_odr = Some(AccelOutputDataRate::Hz100);
+        delay.delay_us(90_000);
+        let without = self.accel_self_test_average(delay)?;
+
+        let reg4 = CtrlReg4A { st: 0b01, ..reg4 };
+        self.write_accel_register(Register::CTRL_REG4_A, reg4.bits())?;
+        self.ctrl_reg4_a = reg4.bits().into();
+        delay.delay_us(90_000);
+        let with = self.accel_self_test_average(delay)?;
+
+        let difference = [
+            with[0] - without[0],
+            with[1] - without[1],
+            with[2] - without[2],
+        ];
+        Ok(SelfTestResult::new(
+            Sensor::Accelerometer,
+            difference,
+            ACCEL_LIMITS,
+            ACCEL_SENSITIVITY,
+        ))
+    }
+
+    /// Average of the samples after the first one, in 10-bit LSB
+    fn accel_self_test_average<D: DelayUs<u32>>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<[i32; 3], Error<CommE, PinE>> {
+        let mut sum = [0; 3];
+        for i in 0..=ACCEL_SAMPLES {
+            let mut polls = 0;
+            while !self.accel_status()?.xyz_new_data {
+                polls += 1;
+                if polls > MAX_POLLS {
+                    return Err(Error::Timeout);
+                }
+                delay.delay_us(POLL_INTERVAL_US);
+            }
+            let data = self
+                .iface
+                .read_accel_3_double_registers(Register::OUT_X_L_A)?;
+            if i > 0 {
+                for (sum, value) in sum.iter_mut().zip(to_axes(data, 6).iter()) {
+                    *sum += value;
+                }
+            }
+        }
+        Ok([
+            sum[0] / ACCEL_SAMPLES,
+            sum[1] / ACCEL_SAMPLES,
+            sum[2] / ACCEL_SAMPLES,
+        ])
+    }
+
+    /// Run the magnetometer self-test
+    ///
+    /// Takes about 1.1 s. The magnetometer configuration is restored
+    /// afterwards, also if the test fails with an error.
+    ///
+    /// Returns `Error::Timeout` if the magnetometer stops providing new data.
+    pub fn mag_self_test<D: DelayUs<u32>>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<SelfTestResult, Error<CommE, PinE>> {
+        let result = self.run_mag_self_test(delay);
+        let restored = self
+            .write_mag_register(Register::CFG_REG_C_M, self.cfg_reg_c_m.bits)
+            .and_then(|_| self.write_mag_register(Register::CFG_REG_B_M, 0))
+            .and_then(|_| self.write_mag_register(Register::CFG_REG_A_M, self.cfg_reg_a_m.bits));
+        let result = result?;
+        restored?;
+        Ok(result)
+    }
+
+    fn run_mag_self_test<D: DelayUs<u32>>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<SelfTestResult, Error<CommE, PinE>> {
+        let cfg_a = CfgRegAM {
+            comp_temp_en: true,
+            ..MAG_SELF_TEST_CFG_REG_A_M.into()
+        };
+        self.write_mag_register(Register::CFG_REG_A_M, cfg_a.bits())?;
+        let cfg_b = CfgRegBM {
+            off_canc: true,
+            ..Default::default()
+        };
+        self.write_mag_register(Register::CFG_REG_B_M, cfg_b.bits())?;
+        let cfg_c = CfgRegCM {
+            bdu: true,
+            ..Default::default()
+        };
+        self.write_mag_register(Register::CFG_REG_C_M, cfg_c.bits())?;
+        delay.delay_us(20_000);
+        let without = self.mag_self_test_average(delay)?;
+
+        let cfg_c = CfgRegCM {
+            self_test: true,
+            ..cfg_c
+        };
+        self.write_mag_register(Register::CFG_REG_C_M, cfg_c.bits())?;
+        delay.delay_us(60_000);
+        let with = self.mag_self_test_average(delay)?;
+
+        let difference = [
+            with[0] - without[0],
+            with[1] - without[1],
+            with[2] - without[2],
+        ];
+        Ok(SelfTestResult::new(
+            Sensor::Magnetometer,
+            difference,
+            MAG_LIMITS,
+            MAG_SENSITIVITY,
+        ))
+    }
+
+    /// Average of the samples after the first one, in LSB
+    fn mag_self_test_average<D: DelayUs<u32>>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<[i32; 3], Error<CommE, PinE>> {
+        let mut sum = [0; 3];
+        for i in 0..=MAG_SAMPLES {
+            let mut polls = 0;
+            while !self.mag_status()?.xyz_new_data {
+                polls += 1;
+                if polls > MAX_POLLS {
+                    return Err(Error::Timeout);
+                }
+                delay.delay_us(POLL_INTERVAL_US);
+            }
+            let data = self
+                .iface
+                .read_mag_3_double_registers(Register::OUTX_L_REG_M)?;
+            if i > 0 {
+                for (sum, value) in sum.iter_mut().zip(to_axes(data, 0).iter()) {
+                    *sum += value;
+                }
+            }
+        }
+        Ok([
+            sum[0] / MAG_SAMPLES,
+            sum[1] / MAG_SAMPLES,
+            sum[2] / MAG_SAMPLES,
+        ])
+    }
+}
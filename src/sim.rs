@@ -0,0 +1,773 @@
+//! Register-level simulator of the LSM303AGR.
+//!
+//! The simulator implements the `embedded-hal` I2C and SPI traits, so the
+//! driver can run against it on the host, for example in tests or while
+//! developing an application without hardware:
+//!
+//! ```
+//! use lsm303agr::{sim::Simulator, AccelOutputDataRate, Lsm303agr, Measurement};
+//!
+//! let sim = Simulator::new();
+//! sim.set_acceleration(Measurement { x: 0, y: 0, z: 1000 });
+//! let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+//! sensor.init().unwrap();
+//! sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+//! sim.advance_us(20_000);
+//! assert!(sensor.accel_status().unwrap().xyz_new_data);
+//! assert_eq!(sensor.accel_data().unwrap(), Measurement { x: 0, y: 0, z: 1000 });
+//! ```
+//!
+//! The simulator keeps a register file with the reset values of the chip and
+//! produces samples of the configured acceleration, magnetic field and
+//! temperature at the configured output data rates, quantized to the
+//! configured mode and scale. The status registers report new data and
+//! overruns like the chip does. The accelerometer FIFO is simulated in FIFO
+//! and stream modes, while stream-to-FIFO mode behaves like stream mode. The
+//! interrupt generators are not simulated: their registers can be written but
+//! never report events. In self-test mode all axes are deflected by a fixed
+//! amount within the datasheet limits.
+//!
+//! Time only passes when [`advance_us()`](Simulator::advance_us) is called,
+//! when waiting with the [`delay()`](Simulator::delay) provider and while bus
+//! transfers take place (by default 22.5 µs per byte like on a 400 kHz I2C
+//! bus), so that polling loops eventually see new data.
+
+use crate::{
+    interface::{ACCEL_ADDR, MAG_ADDR},
+    register_address::{WHO_AM_I_A_VAL, WHO_AM_I_M_VAL},
+    registers::{
+        CfgRegAM, CfgRegCM, CtrlReg1A, CtrlReg4A, CtrlReg5A, FifoCtrlRegA, FifoSrcRegA, StatusRegA,
+        StatusRegAuxA, StatusRegM, TempCfgRegA,
+    },
+    BitFlags, Measurement, Register, FIFO_CAPACITY,
+};
+use core::{cell::RefCell, convert::Infallible};
+use embedded_hal::{
+    blocking::{
+        delay::{DelayMs, DelayUs},
+        i2c, spi,
+    },
+    digital::v2::OutputPin,
+};
+
+const DEFAULT_BYTE_TIME_NS: u32 = 22_500;
+/// Accelerometer deflection in self-test mode in mg
+const ACCEL_SELF_TEST_MG: i32 = 400;
+/// Magnetometer deflection in self-test mode in nT
+const MAG_SELF_TEST_NT: i32 = 15_000;
+
+/// Data-ready bits of `STATUS_REG_A` and `STATUS_REG_M`
+const ALL_DATA_READY: u8 = 0x0F;
+/// Overrun bits of `STATUS_REG_A` and `STATUS_REG_M`
+const ALL_OVERRUN: u8 = 0xF0;
+
+/// Simulator bus error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimError {
+    /// No device answers at the I2C address
+    Nack,
+    /// Not exactly one SPI chip select line is active
+    ChipSelect,
+    /// The transfer contains no register address
+    EmptyTransfer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chip {
+    Accel,
+    Mag,
+}
+
+#[derive(Debug)]
+struct State {
+    accel_regs: [u8; 0x40],
+    mag_regs: [u8; 0x70],
+    acceleration: Measurement,
+    magnetic_field: Measurement,
+    temperature: f32,
+    now_us: u64,
+    next_accel_us: Option<u64>,
+    next_mag_us: Option<u64>,
+    fifo: [[u8; 6]; FIFO_CAPACITY],
+    fifo_start: usize,
+    fifo_len: usize,
+    fifo_overrun: bool,
+    byte_time_ns: u32,
+    cs_accel: bool,
+    cs_mag: bool,
+}
+
+impl State {
+    fn new() -> Self {
+        let mut state = State {
+            accel_regs: [0; 0x40],
+            mag_regs: [0; 0x70],
+            acceleration: Measurement::default(),
+            magnetic_field: Measurement::default(),
+            temperature: 25.0,
+            now_us: 0,
+            next_accel_us: None,
+            next_mag_us: None,
+            fifo: [[0; 6]; FIFO_CAPACITY],
+            fifo_start: 0,
+            fifo_len: 0,
+            fifo_overrun: false,
+            byte_time_ns: DEFAULT_BYTE_TIME_NS,
+            cs_accel: false,
+            cs_mag: false,
+        };
+        state.reset_accel();
+        state.reset_mag();
+        state
+    }
+
+    fn reset_accel(&mut self) {
+        self.accel_regs = [0; 0x40];
+        self.accel_regs[usize::from(Register::WHO_AM_I_A)] = WHO_AM_I_A_VAL;
+        self.accel_regs[usize::from(Register::CTRL_REG1_A)] = 0x07;
+        self.next_accel_us = None;
+        self.clear_fifo();
+    }
+
+    fn reset_mag(&mut self) {
+        self.mag_regs = [0; 0x70];
+        self.mag_regs[usize::from(Register::WHO_AM_I_M)] = WHO_AM_I_M_VAL;
+        self.mag_regs[usize::from(Register::CFG_REG_A_M)] = 0x03;
+        self.next_mag_us = None;
+    }
+
+    fn accel(&self, register: u8) -> u8 {
+        self.accel_regs[usize::from(register)]
+    }
+
+    fn mag(&self, register: u8) -> u8 {
+        self.mag_regs[usize::from(register)]
+    }
+
+    fn register(&mut self, chip: Chip, register: u8) -> Option<&mut u8> {
+        match chip {
+            Chip::Accel => self.accel_regs.get_mut(usize::from(register)),
+            Chip::Mag => self.mag_regs.get_mut(usize::from(register)),
+        }
+    }
+
+    /// Advance the time, producing all samples due until then
+    fn advance(&mut self, us: u64) {
+        let end = self.now_us + us;
+        loop {
+            let accel = self.next_accel_us.filter(|t| *t <= end);
+            let mag = self.next_mag_us.filter(|t| *t <= end);
+            match (accel, mag) {
+                (Some(a), Some(m)) if m < a => self.produce_mag(m),
+                (Some(a), _) => self.produce_accel(a),
+                (None, Some(m)) => self.produce_mag(m),
+                (None, None) => break,
+            }
+        }
+        self.now_us = end;
+    }
+
+    fn advance_bus(&mut self, bytes: usize) {
+        self.advance((bytes as u64 * u64::from(self.byte_time_ns)) / 1000);
+    }
+
+    fn accel_period_us(&self) -> Option<u64> {
+        let reg1 = CtrlReg1A::from(self.accel(Register::CTRL_REG1_A));
+        let low_power = reg1.lp_en;
+        let hz = match reg1.odr {
+            1 => 1,
+            2 => 10,
+            3 => 25,
+            4 => 50,
+            5 => 100,
+            6 => 200,
+            7 => 400,
+            8 if low_power => 1620,
+            9 if low_power => 5376,
+            9 => 1344,
+            _ => return None,
+        };
+        Some(1_000_000 / hz)
+    }
+
+    fn mag_period_us(&self) -> u64 {
+        let hz = match CfgRegAM::from(self.mag(Register::CFG_REG_A_M)).odr {
+            0 => 10,
+            1 => 20,
+            2 => 50,
+            _ => 100,
+        };
+        1_000_000 / hz
+    }
+
+    /// Resolution in bits and sensitivity in mg per LSB
+    fn accel_format(&self) -> (u8, i32) {
+        let reg4 = CtrlReg4A::from(self.accel(Register::CTRL_REG4_A));
+        // ±16g is not twice as coarse as ±8g but 3 times.
+        let hr_sensitivity = match reg4.fs {
+            3 => 12,
+            fs => 1 << fs,
+        };
+        if CtrlReg1A::from(self.accel(Register::CTRL_REG1_A)).lp_en {
+            (8, 16 * hr_sensitivity)
+        } else if reg4.hr {
+            (12, hr_sensitivity)
+        } else {
+            (10, 4 * hr_sensitivity)
+        }
+    }
+
+    fn produce_accel(&mut self, at_us: u64) {
+        self.now_us = at_us;
+        self.next_accel_us = self.accel_period_us().map(|period| at_us + period);
+
+        let (bits, sensitivity) = self.accel_format();
+        let max = (1 << (bits - 1)) - 1;
+        let quantize = |mg: i32| {
+            let raw = (mg + mg.signum() * sensitivity / 2) / sensitivity;
+            ((raw.clamp(-max - 1, max) << (16 - bits)) as i16).to_le_bytes()
+        };
+        let deflection = match CtrlReg4A::from(self.accel(Register::CTRL_REG4_A)).st {
+            0b01 => ACCEL_SELF_TEST_MG,
+            0b10 => -ACCEL_SELF_TEST_MG,
+            _ => 0,
+        };
+        let a = self.acceleration;
+        let a = Measurement {
+            x: a.x + deflection,
+            y: a.y + deflection,
+            z: a.z + deflection,
+        };
+        let (x, y, z) = (quantize(a.x), quantize(a.y), quantize(a.z));
+        let sample = [x[0], x[1], y[0], y[1], z[0], z[1]];
+
+        let status = self.accel(Register::STATUS_REG_A);
+        let overrun = if StatusRegA::from(status).zyxda {
+            ALL_OVERRUN
+        } else {
+            0
+        };
+        self.accel_regs[usize::from(Register::STATUS_REG_A)] = status | ALL_DATA_READY | overrun;
+        if self.fifo_enabled() {
+            self.push_fifo(sample);
+        } else {
+            self.latch_accel(sample);
+        }
+
+        if TempCfgRegA::from(self.accel(Register::TEMP_CFG_REG_A)).temp_en == 0b11 {
+            // 10 bits in normal and high-resolution mode, 8 bits in low-power
+            // mode.
+            let mask = if bits >= 10 { 0xFFC0 } else { 0xFF00 };
+            let raw = libm::roundf((self.temperature - 25.0) * 256.0)
+                .clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16;
+            let [low, high] = (raw as u16 & mask).to_le_bytes();
+            self.accel_regs[usize::from(Register::OUT_TEMP_L_A)] = low;
+            self.accel_regs[usize::from(Register::OUT_TEMP_H_A)] = high;
+            let status = StatusRegAuxA::from(self.accel(Register::STATUS_REG_AUX_A));
+            let status = StatusRegAuxA {
+                tor: status.tda,
+                tda: true,
+            };
+            self.accel_regs[usize::from(Register::STATUS_REG_AUX_A)] = status.bits();
+        }
+    }
+
+    fn latch_accel(&mut self, sample: [u8; 6]) {
+        let start = usize::from(Register::OUT_X_L_A);
+        self.accel_regs[start..start + 6].copy_from_slice(&sample);
+    }
+
+    fn produce_mag(&mut self, at_us: u64) {
+        self.now_us = at_us;
+        let cfg = CfgRegAM::from(self.mag(Register::CFG_REG_A_M));
+        self.next_mag_us = match cfg.md {
+            CfgRegAM::MD_CONTINUOUS => Some(at_us + self.mag_period_us()),
+            _ => {
+                // A single measurement returns to idle mode.
+                let idle = CfgRegAM {
+                    md: CfgRegAM::MD_IDLE,
+                    ..cfg
+                };
+                self.mag_regs[usize::from(Register::CFG_REG_A_M)] = idle.bits();
+                None
+            }
+        };
+        let deflection = if CfgRegCM::from(self.mag(Register::CFG_REG_C_M)).self_test {
+            MAG_SELF_TEST_NT
+        } else {
+            0
+        };
+        let f = self.magnetic_field;
+        let f = Measurement {
+            x: f.x + deflection,
+            y: f.y + deflection,
+            z: f.z + deflection,
+        };
+        let offset =
+            |register: u8| i16::from_le_bytes([self.mag(register), self.mag(register + 1)]) as i32;
+        let quantize = |nt: i32, register: u8| {
+            let raw = (nt + nt.signum() * 75) / 150 - offset(register);
+            (raw.clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16).to_le_bytes()
+        };
+        let x = quantize(f.x, Register::OFFSET_X_REG_L_M);
+        let y = quantize(f.y, Register::OFFSET_Y_REG_L_M);
+        let z = quantize(f.z, Register::OFFSET_Z_REG_L_M);
+        let start = usize::from(Register::OUTX_L_REG_M);
+        self.mag_regs[start..start + 6].copy_from_slice(&[x[0], x[1], y[0], y[1], z[0], z[1]]);
+        let status = self.mag(Register::STATUS_REG_M);
+        let overrun = if StatusRegM::from(status).zyxda {
+            ALL_OVERRUN
+        } else {
+            0
+        };
+        self.mag_regs[usize::from(Register::STATUS_REG_M)] = status | ALL_DATA_READY | overrun;
+    }
+
+    fn fifo_mode(&self) -> u8 {
+        FifoCtrlRegA::from(self.accel(Register::FIFO_CTRL_REG_A)).fm
+    }
+
+    fn fifo_enabled(&self) -> bool {
+        CtrlReg5A::from(self.accel(Register::CTRL_REG5_A)).fifo_en && self.fifo_mode() != 0
+    }
+
+    fn clear_fifo(&mut self) {
+        self.fifo_start = 0;
+        self.fifo_len = 0;
+        self.fifo_overrun = false;
+        self.update_fifo_src();
+    }
+
+    fn push_fifo(&mut self, sample: [u8; 6]) {
+        if self.fifo_len == FIFO_CAPACITY {
+            // FIFO mode stops collecting, stream modes discard the oldest
+            // sample.
+            if self.fifo_mode() == 0b01 {
+                return;
+            }
+            self.fifo_start = (self.fifo_start + 1) % FIFO_CAPACITY;
+            self.fifo_len -= 1;
+        }
+        self.fifo[(self.fifo_start + self.fifo_len) % FIFO_CAPACITY] = sample;
+        self.fifo_len += 1;
+        self.fifo_overrun = self.fifo_len == FIFO_CAPACITY;
+        self.latch_accel(self.fifo[self.fifo_start]);
+        self.update_fifo_src();
+    }
+
+    fn pop_fifo(&mut self) {
+        if self.fifo_len > 0 {
+            self.fifo_start = (self.fifo_start + 1) % FIFO_CAPACITY;
+            self.fifo_len -= 1;
+            self.fifo_overrun = false;
+        }
+        // Without further samples the last one stays in the output registers.
+        let status = if self.fifo_len > 0 {
+            self.latch_accel(self.fifo[self.fifo_start]);
+            ALL_DATA_READY
+        } else {
+            0
+        };
+        self.accel_regs[usize::from(Register::STATUS_REG_A)] = status;
+        self.update_fifo_src();
+    }
+
+    fn update_fifo_src(&mut self) {
+        let watermark = usize::from(FifoCtrlRegA::from(self.accel(Register::FIFO_CTRL_REG_A)).fth);
+        let src = FifoSrcRegA {
+            wtm: self.fifo_len > watermark,
+            ovrn_fifo: self.fifo_overrun,
+            empty: self.fifo_len == 0,
+            fss: self.fifo_len as u8,
+        };
+        self.accel_regs[usize::from(Register::FIFO_SRC_REG_A)] = src.bits();
+    }
+
+    fn is_writable(chip: Chip, register: u8) -> bool {
+        match chip {
+            Chip::Accel => {
+                matches!(register, 0x1E..=0x26 | 0x2E | 0x30 | 0x32..=0x34 | 0x36..=0x38 | 0x3A..=0x3F)
+            }
+            Chip::Mag => matches!(register, 0x45..=0x4A | 0x60..=0x63 | 0x65 | 0x66),
+        }
+    }
+
+    fn write(&mut self, chip: Chip, start: u8, increment: bool, data: &[u8]) {
+        for (i, value) in data.iter().enumerate() {
+            let register = if increment {
+                start.wrapping_add(i as u8)
+            } else {
+                start
+            };
+            if Self::is_writable(chip, register) {
+                if let Some(r) = self.register(chip, register) {
+                    *r = *value;
+                }
+                self.written(chip, register);
+            }
+        }
+    }
+
+    /// Apply the side effects of writing a register
+    fn written(&mut self, chip: Chip, register: u8) {
+        match (chip, register) {
+            (Chip::Accel, Register::CTRL_REG1_A) => {
+                self.next_accel_us = self.accel_period_us().map(|period| self.now_us + period);
+            }
+            (Chip::Accel, Register::CTRL_REG5_A) | (Chip::Accel, Register::FIFO_CTRL_REG_A) => {
+                if !self.fifo_enabled() {
+                    self.clear_fifo();
+                }
+                self.update_fifo_src();
+            }
+            (Chip::Mag, Register::CFG_REG_A_M) => {
+                let cfg = CfgRegAM::from(self.mag(Register::CFG_REG_A_M));
+                if cfg.soft_rst {
+                    self.reset_mag();
+                } else if matches!(cfg.md, CfgRegAM::MD_CONTINUOUS | CfgRegAM::MD_SINGLE) {
+                    self.next_mag_us = Some(self.now_us + self.mag_period_us());
+                } else {
+                    self.next_mag_us = None;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn read(&mut self, chip: Chip, start: u8, increment: bool, buffer: &mut [u8]) {
+        let len = buffer.len();
+        // With the FIFO enabled, the address rolls back from OUT_Z_H_A to
+        // OUT_X_L_A so that consecutive samples can be read in one burst.
+        let rollover = chip == Chip::Accel && increment && self.fifo_enabled();
+        let mut register = start;
+        for value in buffer.iter_mut() {
+            *value = self.register(chip, register).map_or(0, |r| *r);
+            if rollover && register == Register::OUT_Z_H_A {
+                self.pop_fifo();
+                register = Register::OUT_X_L_A;
+            } else if increment {
+                register = register.wrapping_add(1);
+            }
+        }
+        let last = if increment {
+            start.saturating_add(len.saturating_sub(1) as u8)
+        } else {
+            start
+        };
+        let covers = |register: u8| len > 0 && start <= register && register <= last;
+        // Reading the output data consumes it.
+        match chip {
+            Chip::Accel => {
+                if covers(Register::OUT_Z_H_A) && !rollover {
+                    if self.fifo_enabled() {
+                        self.pop_fifo();
+                    } else {
+                        self.accel_regs[usize::from(Register::STATUS_REG_A)] = 0;
+                    }
+                }
+                if covers(Register::OUT_TEMP_H_A) {
+                    self.accel_regs[usize::from(Register::STATUS_REG_AUX_A)] = 0;
+                }
+            }
+            Chip::Mag => {
+                if covers(Register::OUTZ_H_REG_M) {
+                    self.mag_regs[usize::from(Register::STATUS_REG_M)] = 0;
+                }
+            }
+        }
+    }
+
+    fn selected_chip(&self) -> Result<Chip, SimError> {
+        match (self.cs_accel, self.cs_mag) {
+            (true, false) => Ok(Chip::Accel),
+            (false, true) => Ok(Chip::Mag),
+            _ => Err(SimError::ChipSelect),
+        }
+    }
+}
+
+/// Simulated LSM303AGR
+///
+/// The bus and delay providers borrow the simulator, so the simulated
+/// environment can be changed while the driver is using it.
+#[derive(Debug)]
+pub struct Simulator {
+    state: RefCell<State>,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulator {
+    /// Create a new simulator with all registers at their reset values
+    pub fn new() -> Self {
+        Simulator {
+            state: RefCell::new(State::new()),
+        }
+    }
+
+    /// I2C bus with the accelerometer and the magnetometer attached
+    pub fn i2c(&self) -> SimI2c<'_> {
+        SimI2c { sim: self }
+    }
+
+    /// SPI bus together with the accelerometer and magnetometer chip select
+    /// pins
+    pub fn spi(&self) -> (SimSpi<'_>, SimChipSelect<'_>, SimChipSelect<'_>) {
+        (
+            SimSpi { sim: self },
+            SimChipSelect {
+                sim: self,
+                chip: Chip::Accel,
+            },
+            SimChipSelect {
+                sim: self,
+                chip: Chip::Mag,
+            },
+        )
+    }
+
+    /// Delay provider advancing the simulated time
+    pub fn delay(&self) -> SimDelay<'_> {
+        SimDelay { sim: self }
+    }
+
+    /// Set the acceleration in mg
+    pub fn set_acceleration(&self, acceleration: Measurement) {
+        self.state.borrow_mut().acceleration = acceleration;
+    }
+
+    /// Set the magnetic field in nT
+    pub fn set_magnetic_field(&self, field: Measurement) {
+        self.state.borrow_mut().magnetic_field = field;
+    }
+
+    /// Set the temperature in °C
+    pub fn set_temperature(&self, celsius: f32) {
+        self.state.borrow_mut().temperature = celsius;
+    }
+
+    /// Set the time a bus transfer takes per byte in nanoseconds
+    pub fn set_byte_time_ns(&self, ns: u32) {
+        self.state.borrow_mut().byte_time_ns = ns;
+    }
+
+    /// Advance the simulated time
+    pub fn advance_us(&self, us: u64) {
+        self.state.borrow_mut().advance(us);
+    }
+
+    /// Simulated time in microseconds since creation
+    pub fn now_us(&self) -> u64 {
+        self.state.borrow().now_us
+    }
+
+    /// Current value of an accelerometer register
+    ///
+    /// Reading through this method has no side effects.
+    pub fn accel_register(&self, register: u8) -> u8 {
+        self.state
+            .borrow_mut()
+            .register(Chip::Accel, register)
+            .map_or(0, |r| *r)
+    }
+
+    /// Current value of a magnetometer register
+    ///
+    /// Reading through this method has no side effects.
+    pub fn mag_register(&self, register: u8) -> u8 {
+        self.state
+            .borrow_mut()
+            .register(Chip::Mag, register)
+            .map_or(0, |r| *r)
+    }
+
+    /// Reset all registers to their reset values, like a power cycle
+    pub fn reset(&self) {
+        let mut state = self.state.borrow_mut();
+        state.reset_accel();
+        state.reset_mag();
+    }
+}
+
+/// Simulated I2C bus
+///
+/// The accelerometer answers at address 0x19 and the magnetometer at 0x1E.
+/// Accelerometer register addresses increment during multi-byte transfers if
+/// their most significant bit is set, magnetometer addresses always do.
+#[derive(Debug)]
+pub struct SimI2c<'a> {
+    sim: &'a Simulator,
+}
+
+impl SimI2c<'_> {
+    fn chip(address: u8) -> Result<Chip, SimError> {
+        match address {
+            ACCEL_ADDR => Ok(Chip::Accel),
+            MAG_ADDR => Ok(Chip::Mag),
+            _ => Err(SimError::Nack),
+        }
+    }
+
+    fn sub_address(chip: Chip, byte: u8) -> (u8, bool) {
+        match chip {
+            Chip::Accel => (byte & 0x7F, byte & 0x80 != 0),
+            Chip::Mag => (byte & 0x7F, true),
+        }
+    }
+}
+
+impl i2c::Write for SimI2c<'_> {
+    type Error = SimError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        let chip = Self::chip(address)?;
+        let (first, data) = bytes.split_first().ok_or(SimError::EmptyTransfer)?;
+        let (register, increment) = Self::sub_address(chip, *first);
+        let mut state = self.sim.state.borrow_mut();
+        state.write(chip, register, increment, data);
+        state.advance_bus(1 + bytes.len());
+        Ok(())
+    }
+}
+
+impl i2c::WriteRead for SimI2c<'_> {
+    type Error = SimError;
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        let chip = Self::chip(address)?;
+        let (first, data) = bytes.split_first().ok_or(SimError::EmptyTransfer)?;
+        let (register, increment) = Self::sub_address(chip, *first);
+        let mut state = self.sim.state.borrow_mut();
+        state.write(chip, register, increment, data);
+        state.read(chip, register, increment, buffer);
+        state.advance_bus(2 + bytes.len() + buffer.len());
+        Ok(())
+    }
+}
+
+/// Simulated SPI bus
+///
+/// Exactly one of the chip select pins must be low during a transfer.
+#[derive(Debug)]
+pub struct SimSpi<'a> {
+    sim: &'a Simulator,
+}
+
+impl SimSpi<'_> {
+    /// Register address, auto-increment and read flag of a command byte
+    fn command(chip: Chip, byte: u8) -> (u8, bool, bool) {
+        let read = byte & BitFlags::SPI_RW != 0;
+        match chip {
+            Chip::Accel => (byte & 0x3F, byte & BitFlags::SPI_MS != 0, read),
+            Chip::Mag => (byte & 0x7F, true, read),
+        }
+    }
+}
+
+impl spi::Transfer<u8> for SimSpi<'_> {
+    type Error = SimError;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        let mut state = self.sim.state.borrow_mut();
+        let chip = state.selected_chip()?;
+        let (first, data) = words.split_first_mut().ok_or(SimError::EmptyTransfer)?;
+        let (register, increment, read) = Self::command(chip, *first);
+        if read {
+            state.read(chip, register, increment, data);
+        } else {
+            state.write(chip, register, increment, data);
+        }
+        state.advance_bus(words.len());
+        Ok(words)
+    }
+}
+
+impl spi::Write<u8> for SimSpi<'_> {
+    type Error = SimError;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        let mut state = self.sim.state.borrow_mut();
+        let chip = state.selected_chip()?;
+        let (first, data) = words.split_first().ok_or(SimError::EmptyTransfer)?;
+        let (register, increment, _) = Self::command(chip, *first);
+        state.write(chip, register, increment, data);
+        state.advance_bus(words.len());
+        Ok(())
+    }
+}
+
+/// Simulated SPI chip select pin (active low)
+#[derive(Debug)]
+pub struct SimChipSelect<'a> {
+    sim: &'a Simulator,
+    chip: Chip,
+}
+
+impl OutputPin for SimChipSelect<'_> {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.set(true);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.set(false);
+        Ok(())
+    }
+}
+
+impl SimChipSelect<'_> {
+    fn set(&mut self, selected: bool) {
+        let mut state = self.sim.state.borrow_mut();
+        match self.chip {
+            Chip::Accel => state.cs_accel = selected,
+            Chip::Mag => state.cs_mag = selected,
+        }
+    }
+}
+
+/// Delay provider advancing the simulated time
+#[derive(Debug)]
+pub struct SimDelay<'a> {
+    sim: &'a Simulator,
+}
+
+impl DelayUs<u32> for SimDelay<'_> {
+    fn delay_us(&mut self, us: u32) {
+        self.sim.advance_us(u64::from(us));
+    }
+}
+
+impl DelayUs<u16> for SimDelay<'_> {
+    fn delay_us(&mut self, us: u16) {
+        self.sim.advance_us(u64::from(us));
+    }
+}
+
+impl DelayMs<u32> for SimDelay<'_> {
+    fn delay_ms(&mut self, ms: u32) {
+        self.sim.advance_us(u64::from(ms) * 1000);
+    }
+}
+
+impl DelayMs<u16> for SimDelay<'_> {
+    fn delay_ms(&mut self, ms: u16) {
+        self.sim.advance_us(u64::from(ms) * 1000);
+    }
+}
+
+impl DelayMs<u8> for SimDelay<'_> {
+    fn delay_ms(&mut self, ms: u8) {
+        self.sim.advance_us(u64::from(ms) * 1000);
+    }
+}
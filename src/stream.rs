@@ -0,0 +1,222 @@
+//! Streaming of accelerometer and magnetometer samples.
+//!
+//! Instead of polling the status registers in a tight loop, the stream
+//! sleeps for the period implied by the output data rate of each sensor and
+//! only then checks for new data. If the data is not ready yet, it polls
+//! again after an eighth of the period.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    mode, Error, Lsm303agr, Measurement, Status,
+};
+use embedded_hal::blocking::delay::DelayUs;
+
+/// Sensor of a streamed sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Sensor {
+    /// Accelerometer
+    Accelerometer,
+    /// Magnetometer
+    Magnetometer,
+}
+
+/// Sample stream event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamEvent {
+    /// New sample
+    ///
+    /// Accelerometer samples are in mg with the accelerometer calibration
+    /// applied and magnetometer samples are in nT.
+    Sample {
+        /// Sensor which produced the sample
+        sensor: Sensor,
+        /// Per-sensor sequence number, incremented for every sample
+        sequence: u32,
+        /// Measurement
+        data: Measurement,
+    },
+    /// At least one sample was overwritten before it could be read
+    ///
+    /// This is reported right before the next sample of the sensor, which
+    /// has the given sequence number.
+    Dropped {
+        /// Sensor which dropped samples
+        sensor: Sensor,
+        /// Sequence number of the next sample
+        sequence: u32,
+    },
+}
+
+#[derive(Debug)]
+struct Channel {
+    period_us: u32,
+    due_us: u64,
+    sequence: u32,
+}
+
+impl Channel {
+    fn new(frequency_hz: u16) -> Self {
+        Channel {
+            period_us: 1_000_000 / u32::from(frequency_hz),
+            due_us: 0,
+            sequence: 0,
+        }
+    }
+}
+
+/// Sample stream
+///
+/// An endless iterator of [`StreamEvent`]s created with
+/// [`accel_stream()`](Lsm303agr::accel_stream),
+/// [`mag_stream()`](Lsm303agr::mag_stream) or
+/// [`sample_stream()`](Lsm303agr::sample_stream).
+/// Communication errors are returned as items and the stream can be resumed
+/// afterwards. Dropped samples are also added to the
+/// [`overrun_counters()`](Lsm303agr::overrun_counters).
+#[derive(Debug)]
+pub struct SampleStream<'a, DI, MODE, D> {
+    sensor: &'a mut Lsm303agr<DI, MODE>,
+    delay: &'a mut D,
+    accel: Option<Channel>,
+    mag: Option<Channel>,
+    now_us: u64,
+    pending: Option<StreamEvent>,
+}
+
+impl<'a, DI, CommE, PinE, MODE, D> Iterator for SampleStream<'a, DI, MODE, D>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+    D: DelayUs<u32>,
+{
+    type Item = Result<StreamEvent, Error<CommE, PinE>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.take() {
+            return Some(Ok(event));
+        }
+        loop {
+            let (sensor, channel) = match (&mut self.accel, &mut self.mag) {
+                (Some(accel), Some(mag)) if mag.due_us < accel.due_us => {
+                    (Sensor::Magnetometer, mag)
+                }
+                (Some(accel), _) => (Sensor::Accelerometer, accel),
+                (None, Some(mag)) => (Sensor::Magnetometer, mag),
+                (None, None) => return None,
+            };
+            if channel.due_us > self.now_us {
+                self.delay.delay_us((channel.due_us - self.now_us) as u32);
+                self.now_us = channel.due_us;
+            }
+            let status = match read_status(self.sensor, sensor) {
+                Ok(status) => status,
+                Err(e) => return Some(Err(e)),
+            };
+            if !status.xyz_new_data {
+                channel.due_us = self.now_us + u64::from((channel.period_us / 8).max(1));
+                continue;
+            }
+            let data = match sensor {
+                Sensor::Accelerometer => self.sensor.accel_data(),
+                Sensor::Magnetometer => self.sensor.mag_output(),
+            };
+            let data = match data {
+                Ok(data) => data,
+                Err(e) => return Some(Err(e)),
+            };
+            channel.due_us = self.now_us + u64::from(channel.period_us);
+            let sequence = channel.sequence;
+            channel.sequence = sequence.wrapping_add(1);
+            let sample = StreamEvent::Sample {
+                sensor,
+                sequence,
+                data,
+            };
+            if status.xyz_overrun {
+                self.sensor.count_overrun(sensor);
+                self.pending = Some(sample);
+                return Some(Ok(StreamEvent::Dropped { sensor, sequence }));
+            }
+            return Some(Ok(sample));
+        }
+    }
+}
+
+fn read_status<DI, CommE, PinE, MODE>(
+    device: &mut Lsm303agr<DI, MODE>,
+    sensor: Sensor,
+) -> Result<Status, Error<CommE, PinE>>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    match sensor {
+        Sensor::Accelerometer => device.accel_status(),
+        Sensor::Magnetometer => device.mag_status(),
+    }
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Stream accelerometer samples, sleeping between them
+    ///
+    /// Returns `Error::NotReady` if the accelerometer is powered down.
+    pub fn accel_stream<'a, D: DelayUs<u32>>(
+        &'a mut self,
+        delay: &'a mut D,
+    ) -> Result<SampleStream<'a, DI, MODE, D>, Error<CommE, PinE>> {
+        let odr = self.accel_odr.ok_or(Error::NotReady)?;
+        Ok(SampleStream {
+            sensor: self,
+            delay,
+            accel: Some(Channel::new(odr.frequency_hz())),
+            mag: None,
+            now_us: 0,
+            pending: None,
+        })
+    }
+}
+
+impl<DI, CommE, PinE> Lsm303agr<DI, mode::MagContinuous>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Stream magnetometer samples, sleeping between them
+    pub fn mag_stream<'a, D: DelayUs<u32>>(
+        &'a mut self,
+        delay: &'a mut D,
+    ) -> SampleStream<'a, DI, mode::MagContinuous, D> {
+        let mag = Channel::new(self.get_mag_odr().frequency_hz());
+        SampleStream {
+            sensor: self,
+            delay,
+            accel: None,
+            mag: Some(mag),
+            now_us: 0,
+            pending: None,
+        }
+    }
+
+    /// Stream accelerometer and magnetometer samples, sleeping between them
+    ///
+    /// The samples of both sensors are interleaved in the order they become
+    /// available.
+    ///
+    /// Returns `Error::NotReady` if the accelerometer is powered down.
+    pub fn sample_stream<'a, D: DelayUs<u32>>(
+        &'a mut self,
+        delay: &'a mut D,
+    ) -> Result<SampleStream<'a, DI, mode::MagContinuous, D>, Error<CommE, PinE>> {
+        let odr = self.accel_odr.ok_or(Error::NotReady)?;
+        let mag = Channel::new(self.get_mag_odr().frequency_hz());
+        Ok(SampleStream {
+            sensor: self,
+            delay,
+            accel: Some(Channel::new(odr.frequency_hz())),
+            mag: Some(mag),
+            now_us: 0,
+            pending: None,
+        })
+    }
+}
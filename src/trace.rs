@@ -0,0 +1,631 @@
+//! Recording and replaying register transactions.
+//!
+//! A [`Recorder`] wraps the I2C or SPI interface and logs every register
+//! access of the driver together with a timestamp. A [`Replayer`] serves such
+//! a trace back to the driver instead of a device, so that a problem seen on
+//! the hardware can be reproduced on the host, and reports any access that
+//! differs from the recorded one.
+//!
+//! # Trace format
+//!
+//! A trace is text with one transaction per line:
+//!
+//! ```text
+//! <timestamp> <device> <access> <register> <data>...
+//! ```
+//!
+//! - `timestamp`: Time at which the transaction started in microseconds,
+//!   as returned by the clock given to the recorder.
+//! - `device`: `A` for the accelerometer or `M` for the magnetometer.
+//! - `access`: `W` for a write or `R` for a read.
+//! - `register`: Address of the (first) register as two hex digits.
+//! - `data`: Written or read bytes as two hex digits each, in bus order. A
+//!   write has a single byte and a read has 1 to 192 bytes, the latter for
+//!   reading the whole FIFO. A failed
+//!   transaction ends with `!` instead of the read bytes or after the written
+//!   byte.
+//!
+//! Empty lines and lines starting with `#` are ignored, so traces can be
+//! annotated. For example, this is the trace of reading the accelerometer
+//! `WHO_AM_I` register and setting the output data rate to 50 Hz:
+//!
+//! ```text
+//! 1000 A R 0f 33
+//! 1045 A W 20 47
+//! ```
+//!
+//! Recording against the simulator (`sim` feature) and replaying:
+//!
+//! ```
+//! # #[cfg(feature = "sim")]
+//! # {
+//! use lsm303agr::{sim::Simulator, trace::Replayer, AccelOutputDataRate, Lsm303agr};
+//!
+//! let sim = Simulator::new();
+//! let mut trace = String::new();
+//! let mut sensor = Lsm303agr::new_with_i2c_recorder(sim.i2c(), &mut trace, || sim.now_us());
+//! sensor.init().unwrap();
+//! sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+//! sensor.destroy();
+//!
+//! let mut sensor = Lsm303agr::new_with_replayer(Replayer::new(&trace));
+//! sensor.init().unwrap();
+//! sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+//! assert!(sensor.destroy().is_finished());
+//! # }
+//! ```
+
+use crate::{
+    interface::{I2cInterface, ReadData, SpiInterface, WriteData},
+    mode, Error, Lsm303agr, Sensor,
+};
+use core::fmt::{self, Write};
+
+/// Maximum number of bytes of a read, a full FIFO
+const MAX_READ_LEN: usize = crate::FIFO_CAPACITY * 6;
+
+/// Register access of the driver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    /// Read of `len` bytes starting at `register`
+    Read {
+        /// Accessed device
+        sensor: Sensor,
+        /// Register address
+        register: u8,
+        /// Number of read bytes
+        len: u8,
+    },
+    /// Write of `value` to `register`
+    Write {
+        /// Accessed device
+        sensor: Sensor,
+        /// Register address
+        register: u8,
+        /// Written value
+        value: u8,
+    },
+}
+
+/// Replay error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayError {
+    /// The driver access differs from the one recorded at the given line
+    Mismatch {
+        /// Line number in the trace, starting at 1
+        line: usize,
+        /// Recorded access
+        expected: Access,
+        /// Access of the driver
+        actual: Access,
+    },
+    /// The driver made an access after the end of the trace
+    EndOfTrace {
+        /// Access of the driver
+        actual: Access,
+    },
+    /// The line cannot be parsed
+    Malformed {
+        /// Line number in the trace, starting at 1
+        line: usize,
+    },
+    /// The transaction at the given line failed while recording
+    Failed {
+        /// Line number in the trace, starting at 1
+        line: usize,
+    },
+}
+
+/// Interface logging all transactions of the wrapped interface
+///
+/// Create a driver with a recorder with
+/// [`new_with_i2c_recorder()`](crate::Lsm303agr::new_with_i2c_recorder) or
+/// [`new_with_spi_recorder()`](crate::Lsm303agr::new_with_spi_recorder).
+/// The trace is written to any [`core::fmt::Write`] sink, for example a
+/// `String` or a serial port. Lines that cannot be written are lost without
+/// affecting the driver.
+#[derive(Debug)]
+pub struct Recorder<DI, W, C> {
+    pub(crate) iface: DI,
+    pub(crate) sink: W,
+    pub(crate) clock: C,
+}
+
+impl<DI, W, C> Recorder<DI, W, C>
+where
+    W: Write,
+    C: FnMut() -> u64,
+{
+    /// Write a trace line, ignoring errors of the sink
+    fn record(
+        &mut self,
+        timestamp_us: u64,
+        sensor: Sensor,
+        access: char,
+        register: u8,
+        data: &[u8],
+        ok: bool,
+    ) -> fmt::Result {
+        let device = match sensor {
+            Sensor::Accelerometer => 'A',
+            Sensor::Magnetometer => 'M',
+        };
+        write!(
+            self.sink,
+            "{} {} {} {:02x}",
+            timestamp_us, device, access, register
+        )?;
+        for byte in data {
+            write!(self.sink, " {:02x}", byte)?;
+        }
+        if !ok {
+            self.sink.write_str(" !")?;
+        }
+        self.sink.write_char('\n')
+    }
+
+    fn write<E>(
+        &mut self,
+        sensor: Sensor,
+        register: u8,
+        value: u8,
+        write: impl FnOnce(&mut DI) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let timestamp_us = (self.clock)();
+        let result = write(&mut self.iface);
+        let _ = self.record(
+            timestamp_us,
+            sensor,
+            'W',
+            register,
+            &[value],
+            result.is_ok(),
+        );
+        result
+    }
+
+    fn read<T, E, const N: usize>(
+        &mut self,
+        sensor: Sensor,
+        register: u8,
+        read: impl FnOnce(&mut DI) -> Result<T, E>,
+        to_bytes: impl FnOnce(&T) -> [u8; N],
+    ) -> Result<T, E> {
+        let timestamp_us = (self.clock)();
+        let result = read(&mut self.iface);
+        match &result {
+            Ok(value) => {
+                let _ = self.record(timestamp_us, sensor, 'R', register, &to_bytes(value), true);
+            }
+            Err(_) => {
+                let _ = self.record(timestamp_us, sensor, 'R', register, &[], false);
+            }
+        }
+        result
+    }
+}
+
+fn u16_bytes(value: &u16) -> [u8; 2] {
+    value.to_le_bytes()
+}
+
+fn u16_triple_bytes(value: &(u16, u16, u16)) -> [u8; 6] {
+    let (x, y, z) = (
+        value.0.to_le_bytes(),
+        value.1.to_le_bytes(),
+        value.2.to_le_bytes(),
+    );
+    [x[0], x[1], y[0], y[1], z[0], z[1]]
+}
+
+impl<DI, W, C> WriteData for Recorder<DI, W, C>
+where
+    DI: WriteData,
+    W: Write,
+    C: FnMut() -> u64,
+{
+    type Error = DI::Error;
+
+    fn write_accel_register(&mut self, register: u8, data: u8) -> Result<(), Self::Error> {
+        self.write(Sensor::Accelerometer, register, data, |iface| {
+            iface.write_accel_register(register, data)
+        })
+    }
+
+    fn write_mag_register(&mut self, register: u8, data: u8) -> Result<(), Self::Error> {
+        self.write(Sensor::Magnetometer, register, data, |iface| {
+            iface.write_mag_register(register, data)
+        })
+    }
+}
+
+impl<DI, W, C> ReadData for Recorder<DI, W, C>
+where
+    DI: ReadData,
+    W: Write,
+    C: FnMut() -> u64,
+{
+    type Error = DI::Error;
+
+    fn read_accel_register(&mut self, register: u8) -> Result<u8, Self::Error> {
+        self.read(
+            Sensor::Accelerometer,
+            register,
+            |iface| iface.read_accel_register(register),
+            |value| [*value],
+        )
+    }
+
+    fn read_mag_register(&mut self, register: u8) -> Result<u8, Self::Error> {
+        self.read(
+            Sensor::Magnetometer,
+            register,
+            |iface| iface.read_mag_register(register),
+            |value| [*value],
+        )
+    }
+
+    fn read_accel_double_register(&mut self, register: u8) -> Result<u16, Self::Error> {
+        self.read(
+            Sensor::Accelerometer,
+            register,
+            |iface| iface.read_accel_double_register(register),
+            u16_bytes,
+        )
+    }
+
+    fn read_accel_3_double_registers(
+        &mut self,
+        register: u8,
+    ) -> Result<(u16, u16, u16), Self::Error> {
+        self.read(
+            Sensor::Accelerometer,
+            register,
+            |iface| iface.read_accel_3_double_registers(register),
+            u16_triple_bytes,
+        )
+    }
+
+    fn read_mag_3_double_registers(
+        &mut self,
+        register: u8,
+    ) -> Result<(u16, u16, u16), Self::Error> {
+        self.read(
+            Sensor::Magnetometer,
+            register,
+            |iface| iface.read_mag_3_double_registers(register),
+            u16_triple_bytes,
+        )
+    }
+
+    fn read_accel_registers(&mut self, register: u8, data: &mut [u8]) -> Result<(), Self::Error> {
+        let timestamp_us = (self.clock)();
+        let result = self.iface.read_accel_registers(register, data);
+        let bytes: &[u8] = if result.is_ok() { data } else { &[] };
+        let _ = self.record(
+            timestamp_us,
+            Sensor::Accelerometer,
+            'R',
+            register,
+            bytes,
+            result.is_ok(),
+        );
+        result
+    }
+}
+
+/// Parsed trace line
+struct Record {
+    timestamp_us: u64,
+    access: Access,
+    data: [u8; MAX_READ_LEN],
+    ok: bool,
+}
+
+fn parse_record(line: &str) -> Option<Record> {
+    let mut tokens = line.split_whitespace();
+    let timestamp_us = tokens.next()?.parse().ok()?;
+    let sensor = match tokens.next()? {
+        "A" => Sensor::Accelerometer,
+        "M" => Sensor::Magnetometer,
+        _ => return None,
+    };
+    let is_write = match tokens.next()? {
+        "W" => true,
+        "R" => false,
+        _ => return None,
+    };
+    let register = parse_byte(tokens.next()?)?;
+    let mut data = [0; MAX_READ_LEN];
+    let mut len = 0;
+    let mut ok = true;
+    for token in tokens {
+        if !ok {
+            return None;
+        }
+        if token == "!" {
+            ok = false;
+        } else {
+            *data.get_mut(len)? = parse_byte(token)?;
+            len += 1;
+        }
+    }
+    let access = if is_write {
+        if len != 1 {
+            return None;
+        }
+        Access::Write {
+            sensor,
+            register,
+            value: data[0],
+        }
+    } else {
+        if ok && len == 0 || !ok && len != 0 {
+            return None;
+        }
+        Access::Read {
+            sensor,
+            register,
+            len: len as u8,
+        }
+    };
+    Some(Record {
+        timestamp_us,
+        access,
+        data,
+        ok,
+    })
+}
+
+fn parse_byte(token: &str) -> Option<u8> {
+    if token.len() != 2 {
+        return None;
+    }
+    u8::from_str_radix(token, 16).ok()
+}
+
+fn is_record(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && !line.starts_with('#')
+}
+
+/// Interface serving the responses of a recorded trace
+///
+/// Create a driver with a replayer with
+/// [`new_with_replayer()`](crate::Lsm303agr::new_with_replayer). Every access
+/// of the driver must match the next record of the trace, otherwise
+/// `Error::Comm(ReplayError::Mismatch)` is returned. Reads of failed
+/// transactions are not compared by length. Timestamps are not checked but
+/// are available through [`timestamp_us()`](Replayer::timestamp_us).
+#[derive(Debug, Clone)]
+pub struct Replayer<'a> {
+    lines: core::str::Lines<'a>,
+    line: usize,
+    timestamp_us: Option<u64>,
+}
+
+impl<'a> Replayer<'a> {
+    /// Create a new replayer of the given trace
+    pub fn new(trace: &'a str) -> Self {
+        Replayer {
+            lines: trace.lines(),
+            line: 0,
+            timestamp_us: None,
+        }
+    }
+
+    /// Number of the last replayed line, starting at 1 (0 before the first
+    /// transaction)
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Timestamp of the last replayed transaction in microseconds
+    pub fn timestamp_us(&self) -> Option<u64> {
+        self.timestamp_us
+    }
+
+    /// Whether all transactions of the trace have been replayed
+    pub fn is_finished(&self) -> bool {
+        !self.lines.clone().any(is_record)
+    }
+
+    fn next_record(&mut self, actual: Access) -> Result<Record, Error<ReplayError, ()>> {
+        let line = loop {
+            let line = self
+                .lines
+                .next()
+                .ok_or(Error::Comm(ReplayError::EndOfTrace { actual }))?;
+            self.line += 1;
+            if is_record(line) {
+                break line;
+            }
+        };
+        let record =
+            parse_record(line).ok_or(Error::Comm(ReplayError::Malformed { line: self.line }))?;
+        self.timestamp_us = Some(record.timestamp_us);
+        let matches = match (record.access, actual) {
+            (
+                Access::Read {
+                    sensor, register, ..
+                },
+                Access::Read {
+                    sensor: actual_sensor,
+                    register: actual_register,
+                    ..
+                },
+            ) if !record.ok => sensor == actual_sensor && register == actual_register,
+            (expected, actual) => expected == actual,
+        };
+        if !matches {
+            return Err(Error::Comm(ReplayError::Mismatch {
+                line: self.line,
+                expected: record.access,
+                actual,
+            }));
+        }
+        if !record.ok {
+            return Err(Error::Comm(ReplayError::Failed { line: self.line }));
+        }
+        Ok(record)
+    }
+
+    fn write(
+        &mut self,
+        sensor: Sensor,
+        register: u8,
+        value: u8,
+    ) -> Result<(), Error<ReplayError, ()>> {
+        self.next_record(Access::Write {
+            sensor,
+            register,
+            value,
+        })
+        .map(|_| ())
+    }
+
+    fn read(
+        &mut self,
+        sensor: Sensor,
+        register: u8,
+        len: u8,
+    ) -> Result<[u8; MAX_READ_LEN], Error<ReplayError, ()>> {
+        self.next_record(Access::Read {
+            sensor,
+            register,
+            len,
+        })
+        .map(|record| record.data)
+    }
+}
+
+fn u16_triple(data: [u8; MAX_READ_LEN]) -> (u16, u16, u16) {
+    (
+        u16::from_le_bytes([data[0], data[1]]),
+        u16::from_le_bytes([data[2], data[3]]),
+        u16::from_le_bytes([data[4], data[5]]),
+    )
+}
+
+impl WriteData for Replayer<'_> {
+    type Error = Error<ReplayError, ()>;
+
+    fn write_accel_register(&mut self, register: u8, data: u8) -> Result<(), Self::Error> {
+        self.write(Sensor::Accelerometer, register, data)
+    }
+
+    fn write_mag_register(&mut self, register: u8, data: u8) -> Result<(), Self::Error> {
+        self.write(Sensor::Magnetometer, register, data)
+    }
+}
+
+impl ReadData for Replayer<'_> {
+    type Error = Error<ReplayError, ()>;
+
+    fn read_accel_register(&mut self, register: u8) -> Result<u8, Self::Error> {
+        self.read(Sensor::Accelerometer, register, 1)
+            .map(|data| data[0])
+    }
+
+    fn read_mag_register(&mut self, register: u8) -> Result<u8, Self::Error> {
+        self.read(Sensor::Magnetometer, register, 1)
+            .map(|data| data[0])
+    }
+
+    fn read_accel_double_register(&mut self, register: u8) -> Result<u16, Self::Error> {
+        self.read(Sensor::Accelerometer, register, 2)
+            .map(|data| u16::from_le_bytes([data[0], data[1]]))
+    }
+
+    fn read_accel_3_double_registers(
+        &mut self,
+        register: u8,
+    ) -> Result<(u16, u16, u16), Self::Error> {
+        self.read(Sensor::Accelerometer, register, 6)
+            .map(u16_triple)
+    }
+
+    fn read_mag_3_double_registers(
+        &mut self,
+        register: u8,
+    ) -> Result<(u16, u16, u16), Self::Error> {
+        self.read(Sensor::Magnetometer, register, 6).map(u16_triple)
+    }
+
+    fn read_accel_registers(&mut self, register: u8, data: &mut [u8]) -> Result<(), Self::Error> {
+        if data.len() > MAX_READ_LEN {
+            return Err(Error::InvalidInputData);
+        }
+        let record = self.read(Sensor::Accelerometer, register, data.len() as u8)?;
+        data.copy_from_slice(&record[..data.len()]);
+        Ok(())
+    }
+}
+
+impl<I2C, W, C> Lsm303agr<Recorder<I2cInterface<I2C>, W, C>, mode::MagOneShot> {
+    /// Create new instance of the LSM303AGR device communicating through I2C
+    /// and recording all transactions to `sink` with timestamps in
+    /// microseconds from `clock`.
+    pub fn new_with_i2c_recorder(i2c: I2C, sink: W, clock: C) -> Self {
+        Self::new_with_interface(Recorder {
+            iface: I2cInterface { i2c },
+            sink,
+            clock,
+        })
+    }
+}
+
+impl<I2C, W, C, MODE> Lsm303agr<Recorder<I2cInterface<I2C>, W, C>, MODE> {
+    /// Destroy driver instance, return I2C bus and trace sink.
+    pub fn destroy(self) -> (I2C, W) {
+        (self.iface.iface.i2c, self.iface.sink)
+    }
+}
+
+impl<SPI, CSXL, CSMAG, W, C>
+    Lsm303agr<Recorder<SpiInterface<SPI, CSXL, CSMAG>, W, C>, mode::MagOneShot>
+{
+    /// Create new instance of the LSM303AGR device communicating through SPI
+    /// and recording all transactions to `sink` with timestamps in
+    /// microseconds from `clock`.
+    pub fn new_with_spi_recorder(
+        spi: SPI,
+        chip_select_accel: CSXL,
+        chip_select_mag: CSMAG,
+        sink: W,
+        clock: C,
+    ) -> Self {
+        Self::new_with_interface(Recorder {
+            iface: SpiInterface {
+                spi,
+                cs_xl: chip_select_accel,
+                cs_mag: chip_select_mag,
+            },
+            sink,
+            clock,
+        })
+    }
+}
+
+impl<SPI, CSXL, CSMAG, W, C, MODE> Lsm303agr<Recorder<SpiInterface<SPI, CSXL, CSMAG>, W, C>, MODE> {
+    /// Destroy driver instance, return SPI bus instance, chip select pins and
+    /// trace sink.
+    pub fn destroy(self) -> (SPI, CSXL, CSMAG, W) {
+        let Recorder { iface, sink, .. } = self.iface;
+        (iface.spi, iface.cs_xl, iface.cs_mag, sink)
+    }
+}
+
+impl<'a> Lsm303agr<Replayer<'a>, mode::MagOneShot> {
+    /// Create new instance of the LSM303AGR device replaying a recorded trace.
+    pub fn new_with_replayer(replayer: Replayer<'a>) -> Self {
+        Self::new_with_interface(replayer)
+    }
+}
+
+impl<'a, MODE> Lsm303agr<Replayer<'a>, MODE> {
+    /// Destroy driver instance, return replayer.
+    pub fn destroy(self) -> Replayer<'a> {
+        self.iface
+    }
+}
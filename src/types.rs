@@ -1,5 +1,10 @@
+use crate::Sensor;
+use core::fmt;
+
 /// All possible errors in this crate
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum Error<CommE, PinE> {
     /// I²C / SPI communication error
     Comm(CommE),
@@ -7,9 +12,135 @@ pub enum Error<CommE, PinE> {
     Pin(PinE),
     /// Invalid input data provided
     InvalidInputData,
+    /// A register did not hold the written value when read back
+    WriteMismatch {
+        /// Register address
+        register: u8,
+        /// Written value
+        written: u8,
+        /// Value read back
+        read: u8,
+    },
+    /// The device ID read does not belong to an LSM303AGR
+    WrongDeviceId {
+        /// Sensor whose ID was read
+        sensor: Sensor,
+        /// Expected device ID
+        expected: u8,
+        /// Device ID read
+        found: u8,
+    },
+    /// The accelerometer output data rate is not available in the mode
+    IncompatibleOdrMode {
+        /// Accelerometer output data rate
+        odr: AccelOutputDataRate,
+        /// Accelerometer mode
+        mode: AccelMode,
+    },
+    /// The sensor is not ready for the operation, e.g. the accelerometer is
+    /// powered down
+    NotReady,
+    /// The sensor did not provide new data in time
+    Timeout,
+    /// The self-test difference is outside of the datasheet limits
+    SelfTestFailed {
+        /// Sensor tested
+        sensor: Sensor,
+        /// Difference between the average output with and without self-test
+        difference: Measurement,
+    },
 }
 
-/// All possible errors in this crate
+impl<CommE, PinE> Error<CommE, PinE> {
+    /// Numeric code of the error, for example for telemetry
+    ///
+    /// The codes are stable: existing codes never change and new errors get
+    /// new codes.
+    ///
+    /// | Error                 | Code |
+    /// |-----------------------|------|
+    /// | `Comm`                | 1    |
+    /// | `Pin`                 | 2    |
+    /// | `InvalidInputData`    | 3    |
+    /// | `WriteMismatch`       | 4    |
+    /// | `WrongDeviceId`       | 5    |
+    /// | `IncompatibleOdrMode` | 6    |
+    /// | `NotReady`            | 7    |
+    /// | `Timeout`             | 8    |
+    /// | `SelfTestFailed`      | 9    |
+    pub fn code(&self) -> u16 {
+        match self {
+            Error::Comm(_) => 1,
+            Error::Pin(_) => 2,
+            Error::InvalidInputData => 3,
+            Error::WriteMismatch { .. } => 4,
+            Error::WrongDeviceId { .. } => 5,
+            Error::IncompatibleOdrMode { .. } => 6,
+            Error::NotReady => 7,
+            Error::Timeout => 8,
+            Error::SelfTestFailed { .. } => 9,
+        }
+    }
+}
+
+impl<CommE: fmt::Debug, PinE: fmt::Debug> fmt::Display for Error<CommE, PinE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Comm(e) => write!(f, "I2C/SPI communication error: {:?}", e),
+            Error::Pin(e) => write!(f, "chip-select pin error: {:?}", e),
+            Error::InvalidInputData => write!(f, "invalid input data"),
+            Error::WriteMismatch {
+                register,
+                written,
+                read,
+            } => write!(
+                f,
+                "register {:#04x} reads {:#04x} after writing {:#04x}",
+                register, read, written
+            ),
+            Error::WrongDeviceId {
+                sensor,
+                expected,
+                found,
+            } => write!(
+                f,
+                "wrong {} device ID {:#04x}, expected {:#04x}",
+                sensor_name(*sensor),
+                found,
+                expected
+            ),
+            Error::IncompatibleOdrMode { odr, mode } => write!(
+                f,
+                "accelerometer output data rate {:?} is not available in {:?} mode",
+                odr, mode
+            ),
+            Error::NotReady => write!(f, "sensor not ready"),
+            Error::Timeout => write!(f, "timeout waiting for new data"),
+            Error::SelfTestFailed { sensor, difference } => write!(
+                f,
+                "{} self-test failed with difference ({}, {}, {})",
+                sensor_name(*sensor),
+                difference.x,
+                difference.y,
+                difference.z
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<CommE: fmt::Debug, PinE: fmt::Debug> std::error::Error for Error<CommE, PinE> {}
+
+fn sensor_name(sensor: Sensor) -> &'static str {
+    match sensor {
+        Sensor::Accelerometer => "accelerometer",
+        Sensor::Magnetometer => "magnetometer",
+    }
+}
+
+/// Error changing the magnetometer mode
+///
+/// Contains the original device, which is still in the previous mode.
 #[derive(Debug)]
 pub struct ModeChangeError<CommE, PinE, DEV> {
     /// I²C / SPI communication error
@@ -18,6 +149,42 @@ pub struct ModeChangeError<CommE, PinE, DEV> {
     pub dev: DEV,
 }
 
+impl<CommE, PinE, DEV> ModeChangeError<CommE, PinE, DEV> {
+    /// Numeric code of the underlying error, see [`Error::code()`]
+    pub fn code(&self) -> u16 {
+        self.error.code()
+    }
+}
+
+impl<CommE: fmt::Debug, PinE: fmt::Debug, DEV> fmt::Display for ModeChangeError<CommE, PinE, DEV> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "could not change the magnetometer mode: {}", self.error)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<CommE, PinE, DEV> defmt::Format for ModeChangeError<CommE, PinE, DEV>
+where
+    CommE: defmt::Format,
+    PinE: defmt::Format,
+{
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "could not change the magnetometer mode: {}", self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<CommE, PinE, DEV> std::error::Error for ModeChangeError<CommE, PinE, DEV>
+where
+    CommE: fmt::Debug + 'static,
+    PinE: fmt::Debug + 'static,
+    DEV: fmt::Debug,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
 /// Device operation modes
 pub mod mode {
     /// Magnetometer one-shot (single) mode
@@ -30,6 +197,7 @@ pub mod mode {
 
 /// Measurement
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Measurement {
     /// X-axis data.
     pub x: i32,
@@ -50,8 +218,75 @@ pub struct UnscaledMeasurement {
     pub z: i16,
 }
 
+/// Sensor axis with direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    /// Positive X axis
+    PositiveX,
+    /// Negative X axis
+    NegativeX,
+    /// Positive Y axis
+    PositiveY,
+    /// Negative Y axis
+    NegativeY,
+    /// Positive Z axis
+    PositiveZ,
+    /// Negative Z axis
+    NegativeZ,
+}
+
+impl Axis {
+    fn select(self, m: &Measurement) -> i32 {
+        match self {
+            Axis::PositiveX => m.x,
+            Axis::NegativeX => -m.x,
+            Axis::PositiveY => m.y,
+            Axis::NegativeY => -m.y,
+            Axis::PositiveZ => m.z,
+            Axis::NegativeZ => -m.z,
+        }
+    }
+}
+
+/// Mounting orientation of the sensor
+///
+/// Each field gives the sensor axis that points along the corresponding
+/// axis of the device it is mounted in. The default is the identity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisOrientation {
+    /// Sensor axis pointing along the device X axis
+    pub x: Axis,
+    /// Sensor axis pointing along the device Y axis
+    pub y: Axis,
+    /// Sensor axis pointing along the device Z axis
+    pub z: Axis,
+}
+
+impl Default for AxisOrientation {
+    fn default() -> Self {
+        AxisOrientation {
+            x: Axis::PositiveX,
+            y: Axis::PositiveY,
+            z: Axis::PositiveZ,
+        }
+    }
+}
+
+impl AxisOrientation {
+    /// Transform a sensor measurement into device axes
+    pub fn apply(&self, measurement: Measurement) -> Measurement {
+        Measurement {
+            x: self.x.select(&measurement),
+            y: self.y.select(&measurement),
+            z: self.z.select(&measurement),
+        }
+    }
+}
+
 /// Accelerometer output data rate
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum AccelOutputDataRate {
     /// 1 Hz (High-resolution/Normal/Low-power)
     Hz1,
@@ -75,8 +310,28 @@ pub enum AccelOutputDataRate {
     Khz5_376LowPower,
 }
 
+impl AccelOutputDataRate {
+    /// Output data rate in Hz
+    pub fn frequency_hz(self) -> u16 {
+        match self {
+            AccelOutputDataRate::Hz1 => 1,
+            AccelOutputDataRate::Hz10 => 10,
+            AccelOutputDataRate::Hz25 => 25,
+            AccelOutputDataRate::Hz50 => 50,
+            AccelOutputDataRate::Hz100 => 100,
+            AccelOutputDataRate::Hz200 => 200,
+            AccelOutputDataRate::Hz400 => 400,
+            AccelOutputDataRate::Khz1_344 => 1344,
+            AccelOutputDataRate::Khz1_620LowPower => 1620,
+            AccelOutputDataRate::Khz5_376LowPower => 5376,
+        }
+    }
+}
+
 /// Accelerometer mode
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum AccelMode {
     /// Power down
     PowerDown,
@@ -90,6 +345,7 @@ pub enum AccelMode {
 
 /// Accelerometer scaling factor
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AccelScale {
     /// Plus or minus 2g
     G2,
@@ -103,6 +359,7 @@ pub enum AccelScale {
 
 /// Magnetometer output data rate
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MagOutputDataRate {
     /// 10 Hz
     Hz10,
@@ -114,6 +371,43 @@ pub enum MagOutputDataRate {
     Hz100,
 }
 
+impl MagOutputDataRate {
+    /// Output data rate in Hz
+    pub fn frequency_hz(self) -> u16 {
+        match self {
+            MagOutputDataRate::Hz10 => 10,
+            MagOutputDataRate::Hz20 => 20,
+            MagOutputDataRate::Hz50 => 50,
+            MagOutputDataRate::Hz100 => 100,
+        }
+    }
+}
+
+/// Device configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceConfig {
+    /// Accelerometer output data rate (`None` when powered down)
+    pub accel_odr: Option<AccelOutputDataRate>,
+    /// Accelerometer mode
+    pub accel_mode: AccelMode,
+    /// Accelerometer scale
+    pub accel_scale: AccelScale,
+    /// Magnetometer output data rate
+    pub mag_odr: MagOutputDataRate,
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        DeviceConfig {
+            accel_odr: None,
+            accel_mode: AccelMode::PowerDown,
+            accel_scale: AccelScale::G2,
+            mag_odr: MagOutputDataRate::Hz10,
+        }
+    }
+}
+
 /// Data status
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Status {
@@ -0,0 +1,241 @@
+//! Write verification and configuration drift detection.
+//!
+//! Register contents can flip in environments with strong electromagnetic
+//! interference. With [write verification](Lsm303agr::set_write_verification)
+//! enabled, every register write is read back and compared.
+//! [`check_config()`](Lsm303agr::check_config) compares the configuration
+//! registers with the values the driver last wrote to them and can restore
+//! the ones that drifted.
+
+use crate::{
+    interface::{ReadData, WriteData},
+    registers::{CfgRegAM, CtrlReg5A},
+    Config, Error, Lsm303agr, Register, Sensor,
+};
+
+/// Configuration registers whose value the driver keeps
+const CACHED_REGISTERS: [(Sensor, u8); 9] = [
+    (Sensor::Accelerometer, Register::TEMP_CFG_REG_A),
+    (Sensor::Accelerometer, Register::CTRL_REG1_A),
+    (Sensor::Accelerometer, Register::CTRL_REG3_A),
+    (Sensor::Accelerometer, Register::CTRL_REG4_A),
+    (Sensor::Accelerometer, Register::CTRL_REG5_A),
+    (Sensor::Accelerometer, Register::CTRL_REG6_A),
+    (Sensor::Accelerometer, Register::FIFO_CTRL_REG_A),
+    (Sensor::Magnetometer, Register::CFG_REG_A_M),
+    (Sensor::Magnetometer, Register::CFG_REG_C_M),
+];
+
+/// Configuration register whose content differs from the value the driver
+/// last wrote to it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegisterDrift {
+    /// Sensor the register belongs to
+    pub sensor: Sensor,
+    /// Register address, see [`registers`](crate::registers)
+    pub register: u8,
+    /// Value last written by the driver
+    pub expected: u8,
+    /// Value read from the device
+    pub actual: u8,
+}
+
+/// Configuration registers that drifted
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ConfigDrift {
+    registers: [Option<RegisterDrift>; CACHED_REGISTERS.len()],
+    len: usize,
+}
+
+impl ConfigDrift {
+    /// No register drifted
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of registers that drifted
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Registers that drifted
+    pub fn iter(&self) -> impl Iterator<Item = &RegisterDrift> {
+        self.registers[..self.len].iter().flatten()
+    }
+
+    fn push(&mut self, drift: RegisterDrift) {
+        self.registers[self.len] = Some(drift);
+        self.len += 1;
+    }
+}
+
+/// Whether a register read back holds the expected value
+///
+/// Self-clearing bits are ignored. In the magnetometer, single measurement
+/// mode returns to idle mode on its own, so both are equivalent.
+fn holds(register: u8, expected: u8, actual: u8) -> bool {
+    match register {
+        Register::CTRL_REG5_A => {
+            let ignore = |bits| CtrlReg5A {
+                boot: false,
+                ..CtrlReg5A::from(bits)
+            };
+            ignore(expected) == ignore(actual)
+        }
+        Register::CFG_REG_A_M => {
+            let ignore = |bits| CfgRegAM {
+                reboot: false,
+                soft_rst: false,
+                md: match CfgRegAM::from(bits).md {
+                    CfgRegAM::MD_CONTINUOUS => CfgRegAM::MD_CONTINUOUS,
+                    _ => CfgRegAM::MD_IDLE,
+                },
+                ..CfgRegAM::from(bits)
+            };
+            ignore(expected) == ignore(actual)
+        }
+        _ => expected == actual,
+    }
+}
+
+impl<DI, MODE> Lsm303agr<DI, MODE> {
+    /// Enable or disable reading back and comparing every register write
+    ///
+    /// When enabled, a write whose register does not hold the written value
+    /// afterwards returns `Error::WriteMismatch`. This doubles the bus
+    /// traffic of writes. Disabled by default.
+    pub fn set_write_verification(&mut self, enabled: bool) {
+        self.verify_writes = enabled;
+    }
+
+    /// Value the driver last wrote to a configuration register
+    fn cached_mut(&mut self, register: u8) -> Option<&mut Config> {
+        match register {
+            Register::TEMP_CFG_REG_A => Some(&mut self.temp_cfg_reg_a),
+            Register::CTRL_REG1_A => Some(&mut self.ctrl_reg1_a),
+            Register::CTRL_REG3_A => Some(&mut self.ctrl_reg3_a),
+            Register::CTRL_REG4_A => Some(&mut self.ctrl_reg4_a),
+            Register::CTRL_REG5_A => Some(&mut self.ctrl_reg5_a),
+            Register::CTRL_REG6_A => Some(&mut self.ctrl_reg6_a),
+            Register::FIFO_CTRL_REG_A => Some(&mut self.fifo_ctrl_reg_a),
+            Register::CFG_REG_A_M => Some(&mut self.cfg_reg_a_m),
+            Register::CFG_REG_C_M => Some(&mut self.cfg_reg_c_m),
+            _ => None,
+        }
+    }
+}
+
+impl<DI, CommE, PinE, MODE> Lsm303agr<DI, MODE>
+where
+    DI: ReadData<Error = Error<CommE, PinE>> + WriteData<Error = Error<CommE, PinE>>,
+{
+    /// Write an accelerometer register, reading it back if write
+    /// verification is enabled
+    pub(crate) fn write_accel_register(
+        &mut self,
+        register: u8,
+        value: u8,
+    ) -> Result<(), Error<CommE, PinE>> {
+        self.iface.write_accel_register(register, value)?;
+        if self.verify_writes {
+            let read = self.iface.read_accel_register(register)?;
+            check_write(register, value, read)?;
+        }
+        Ok(())
+    }
+
+    /// Write a magnetometer register, reading it back if write verification
+    /// is enabled
+    pub(crate) fn write_mag_register(
+        &mut self,
+        register: u8,
+        value: u8,
+    ) -> Result<(), Error<CommE, PinE>> {
+        self.iface.write_mag_register(register, value)?;
+        if self.verify_writes {
+            let read = self.iface.read_mag_register(register)?;
+            check_write(register, value, read)?;
+        }
+        Ok(())
+    }
+
+    /// Write a configuration register if its cached value differs
+    pub(crate) fn update_accel_register(
+        &mut self,
+        register: u8,
+        value: u8,
+    ) -> Result<(), Error<CommE, PinE>> {
+        if self.cached_mut(register).map(|cached| cached.bits) != Some(value) {
+            self.write_accel_register(register, value)?;
+            if let Some(cached) = self.cached_mut(register) {
+                *cached = value.into();
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a configuration register if its cached value differs
+    pub(crate) fn update_mag_register(
+        &mut self,
+        register: u8,
+        value: u8,
+    ) -> Result<(), Error<CommE, PinE>> {
+        if self.cached_mut(register).map(|cached| cached.bits) != Some(value) {
+            self.write_mag_register(register, value)?;
+            if let Some(cached) = self.cached_mut(register) {
+                *cached = value.into();
+            }
+        }
+        Ok(())
+    }
+
+    /// Compare the configuration registers with the values the driver last
+    /// wrote to them
+    ///
+    /// If `restore` is set, the drifted registers are written again with the
+    /// expected values. The returned drift describes the state before
+    /// restoring.
+    pub fn check_config(&mut self, restore: bool) -> Result<ConfigDrift, Error<CommE, PinE>> {
+        let mut drift = ConfigDrift::default();
+        for &(sensor, register) in CACHED_REGISTERS.iter() {
+            let expected = self.cached_mut(register).map_or(0, |cached| cached.bits);
+            let actual = match sensor {
+                Sensor::Accelerometer => self.iface.read_accel_register(register)?,
+                Sensor::Magnetometer => self.iface.read_mag_register(register)?,
+            };
+            if !holds(register, expected, actual) {
+                drift.push(RegisterDrift {
+                    sensor,
+                    register,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        if restore {
+            for register in drift.iter() {
+                match register.sensor {
+                    Sensor::Accelerometer => {
+                        self.write_accel_register(register.register, register.expected)?
+                    }
+                    Sensor::Magnetometer => {
+                        self.write_mag_register(register.register, register.expected)?
+                    }
+                }
+            }
+        }
+        Ok(drift)
+    }
+}
+
+fn check_write<CommE, PinE>(register: u8, written: u8, read: u8) -> Result<(), Error<CommE, PinE>> {
+    if holds(register, written, read) {
+        Ok(())
+    } else {
+        Err(Error::WriteMismatch {
+            register,
+            written,
+            read,
+        })
+    }
+}
@@ -0,0 +1,149 @@
+//! Vibration analysis of blocks of accelerometer samples.
+//!
+//! The mean of each axis (gravity and any constant acceleration) is removed
+//! before the analysis, so all results describe the vibration only.
+//!
+//! The spectrum is computed with the Goertzel algorithm for every DFT bin,
+//! which needs no buffers but takes time proportional to the square of the
+//! number of samples. Blocks of up to a few hundred samples, for example
+//! collected from the FIFO with [`fifo_data()`](crate::Lsm303agr::fifo_data),
+//! are a good compromise between frequency resolution and computation time.
+
+use crate::{AccelOutputDataRate, Measurement};
+
+/// Number of frequency bands in the spectral summary
+pub const VIBRATION_BANDS: usize = 8;
+
+/// Vibration statistics of one axis in mg
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AxisVibration {
+    /// Root mean square
+    pub rms: f32,
+    /// Largest absolute deviation from the mean
+    pub peak: f32,
+    /// Difference between the largest and the smallest value
+    pub peak_to_peak: f32,
+    /// Ratio of peak to RMS (0 if there is no vibration)
+    pub crest_factor: f32,
+}
+
+/// Vibration level in a frequency band
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct VibrationBand {
+    /// Lower band edge in Hz
+    pub low_hz: f32,
+    /// Upper band edge in Hz
+    pub high_hz: f32,
+    /// RMS in mg of the X, Y and Z axes in this band
+    ///
+    /// The squares of the band RMS values of an axis add up to the square of
+    /// its total RMS.
+    pub rms: [f32; 3],
+}
+
+/// Vibration analysis result
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VibrationAnalysis {
+    /// X axis statistics
+    pub x: AxisVibration,
+    /// Y axis statistics
+    pub y: AxisVibration,
+    /// Z axis statistics
+    pub z: AxisVibration,
+    /// Equal-width frequency bands from 0 Hz to half the output data rate
+    pub bands: [VibrationBand; VIBRATION_BANDS],
+    /// Frequency in Hz with the highest energy summed over all axes
+    pub dominant_hz: f32,
+    /// Frequency resolution in Hz
+    pub resolution_hz: f32,
+}
+
+impl VibrationAnalysis {
+    /// Analyze a block of consecutive accelerometer measurements in mg
+    /// taken at the given output data rate
+    ///
+    /// Returns `None` if there are fewer than 2 samples.
+    pub fn analyze(samples: &[Measurement], odr: AccelOutputDataRate) -> Option<Self> {
+        let n = samples.len();
+        if n < 2 {
+            return None;
+        }
+        let odr_hz = f32::from(odr.frequency_hz());
+        let axis = |i: usize, m: &Measurement| [m.x, m.y, m.z][i] as f32;
+        let mut mean = [0.0; 3];
+        for (i, mean) in mean.iter_mut().enumerate() {
+            *mean = samples.iter().map(|m| axis(i, m)).sum::<f32>() / n as f32;
+        }
+        let mut stats = [AxisVibration::default(); 3];
+        for (i, stats) in stats.iter_mut().enumerate() {
+            let values = || samples.iter().map(|m| axis(i, m) - mean[i]);
+            let rms = libm::sqrtf(values().map(|v| v * v).sum::<f32>() / n as f32);
+            let peak = values().fold(0.0, |peak: f32, v| peak.max(libm::fabsf(v)));
+            let max = values().fold(f32::MIN, f32::max);
+            let min = values().fold(f32::MAX, f32::min);
+            *stats = AxisVibration {
+                rms,
+                peak,
+                peak_to_peak: max - min,
+                crest_factor: if rms > 0.0 { peak / rms } else { 0.0 },
+            };
+        }
+
+        let resolution_hz = odr_hz / n as f32;
+        let band_width = odr_hz / 2.0 / VIBRATION_BANDS as f32;
+        let mut bands = [VibrationBand::default(); VIBRATION_BANDS];
+        for (i, band) in bands.iter_mut().enumerate() {
+            band.low_hz = band_width * i as f32;
+            band.high_hz = band_width * (i + 1) as f32;
+        }
+        let mut dominant = (0.0, 0.0);
+        for k in 1..=n / 2 {
+            let power = goertzel(samples, mean, k);
+            // One-sided spectrum: all bins except the Nyquist one have a
+            // mirrored counterpart.
+            let factor = if 2 * k == n { 1.0 } else { 2.0 };
+            let band = (k * 2 * VIBRATION_BANDS / n).min(VIBRATION_BANDS - 1);
+            for (rms, power) in bands[band].rms.iter_mut().zip(power.iter()) {
+                *rms += factor * power / (n * n) as f32;
+            }
+            let total = power.iter().sum::<f32>();
+            if total > dominant.1 {
+                dominant = (k as f32 * resolution_hz, total);
+            }
+        }
+        for band in bands.iter_mut() {
+            for rms in band.rms.iter_mut() {
+                *rms = libm::sqrtf(*rms);
+            }
+        }
+        Some(VibrationAnalysis {
+            x: stats[0],
+            y: stats[1],
+            z: stats[2],
+            bands,
+            dominant_hz: dominant.0,
+            resolution_hz,
+        })
+    }
+}
+
+/// Squared magnitude of DFT bin `k` of each axis with the mean removed
+fn goertzel(samples: &[Measurement], mean: [f32; 3], k: usize) -> [f32; 3] {
+    let omega = 2.0 * core::f32::consts::PI * k as f32 / samples.len() as f32;
+    let coeff = 2.0 * libm::cosf(omega);
+    let mut s1 = [0.0; 3];
+    let mut s2 = [0.0; 3];
+    for m in samples {
+        let values = [m.x as f32, m.y as f32, m.z as f32];
+        for i in 0..3 {
+            let s = values[i] - mean[i] + coeff * s1[i] - s2[i];
+            s2[i] = s1[i];
+            s1[i] = s;
+        }
+    }
+    let mut power = [0.0; 3];
+    for (i, power) in power.iter_mut().enumerate() {
+        *power = s1[i] * s1[i] + s2[i] * s2[i] - coeff * s1[i] * s2[i];
+    }
+    power
+}
@@ -0,0 +1,239 @@
+mod common;
+use crate::common::{
+    destroy_i2c, new_i2c, BitFlags as BF, Register, ACCEL_ADDR, DEFAULT_CTRL_REG1_A, HZ50,
+};
+use embedded_hal_mock::{delay::MockNoop as Delay, i2c::Transaction as I2cTrans, MockError};
+use lsm303agr::{
+    AccelCalibration, AccelCalibrationError, AccelCalibrationPosition as Position, AccelCalibrator,
+    AccelOutputDataRate, Error, Measurement,
+};
+use std::io::ErrorKind;
+
+fn m(x: i32, y: i32, z: i32) -> Measurement {
+    Measurement { x, y, z }
+}
+
+/// Accelerometer output registers for a measurement in mg in normal mode
+/// with ±2g scale (4 mg/LSB, 10-bit left-justified).
+fn normal_mode_data(x: i16, y: i16, z: i16) -> Vec<u8> {
+    [x, y, z]
+        .iter()
+        .flat_map(|v| ((v / 4) << 6).to_le_bytes().to_vec())
+        .collect()
+}
+
+fn calibrator_with_all_positions() -> AccelCalibrator {
+    // offset (20, -30, 10) mg, gain (1.02, 0.98, 1.0)
+    let mut calibrator = AccelCalibrator::new();
+    calibrator
+        .add_sample(Position::XUp, m(1040, -30, 10))
+        .unwrap();
+    calibrator
+        .add_sample(Position::XDown, m(-1000, -30, 10))
+        .unwrap();
+    calibrator
+        .add_sample(Position::YUp, m(20, 950, 10))
+        .unwrap();
+    calibrator
+        .add_sample(Position::YDown, m(20, -1010, 10))
+        .unwrap();
+    calibrator
+        .add_sample(Position::ZUp, m(20, -30, 1010))
+        .unwrap();
+    calibrator
+        .add_sample(Position::ZDown, m(20, -30, -990))
+        .unwrap();
+    calibrator
+}
+
+#[test]
+fn can_detect_position() {
+    assert_eq!(Position::detect(m(20, 10, 980)), Some(Position::ZUp));
+    assert_eq!(Position::detect(m(-1010, 50, -20)), Some(Position::XDown));
+    assert_eq!(Position::detect(m(700, 700, 0)), None);
+}
+
+#[test]
+fn rejects_sample_in_wrong_position() {
+    let mut calibrator = AccelCalibrator::new();
+    assert_eq!(
+        calibrator.add_sample(Position::XUp, m(0, 0, 1000)),
+        Err(AccelCalibrationError::WrongPosition)
+    );
+    assert_eq!(calibrator.samples(Position::XUp), 0);
+}
+
+#[test]
+fn guides_through_missing_positions() {
+    let mut calibrator = AccelCalibrator::new();
+    assert_eq!(calibrator.next_position(), Some(Position::XUp));
+    calibrator.add_sample(Position::XUp, m(1000, 0, 0)).unwrap();
+    assert_eq!(calibrator.next_position(), Some(Position::XDown));
+    assert!(!calibrator.is_complete());
+    assert_eq!(
+        calibrator.solve(),
+        Err(AccelCalibrationError::MissingPositions)
+    );
+}
+
+#[test]
+fn can_solve_offset_and_scale() {
+    let calibrator = calibrator_with_all_positions();
+    assert!(calibrator.is_complete());
+    let calibration = calibrator.solve().unwrap();
+    assert_eq!(calibration.offset, [20.0, -30.0, 10.0]);
+    assert_eq!(calibration.scale, [2000.0 / 2040.0, 2000.0 / 1960.0, 1.0]);
+    assert_eq!(calibration.apply(m(1040, -30, 10)), m(1000, 0, 0));
+    assert_eq!(calibration.apply(m(20, -1010, 10)), m(0, -1000, 0));
+}
+
+#[test]
+fn default_calibration_does_nothing() {
+    assert_eq!(
+        AccelCalibration::default().apply(m(12, -34, 1000)),
+        m(12, -34, 1000)
+    );
+}
+
+#[test]
+fn can_collect_samples_in_position() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![0]),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![BF::XYZDR]),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            normal_mode_data(16, -8, 1004),
+        ),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![BF::XYZDR]),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            normal_mode_data(24, -8, 996),
+        ),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut calibrator = AccelCalibrator::new();
+    sensor
+        .calibrate_accel_position(&mut calibrator, Position::ZUp, 2, &mut Delay::new())
+        .unwrap();
+    assert_eq!(calibrator.samples(Position::ZUp), 2);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn collecting_samples_in_wrong_position_fails() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![BF::XYZDR]),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            normal_mode_data(16, -8, 1004),
+        ),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut calibrator = AccelCalibrator::new();
+    sensor
+        .calibrate_accel_position(&mut calibrator, Position::XUp, 1, &mut Delay::new())
+        .expect_err("Should have returned error");
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn collecting_samples_requires_powered_up_accelerometer() {
+    let mut sensor = new_i2c(&[]);
+    let mut calibrator = AccelCalibrator::new();
+    match sensor.calibrate_accel_position(&mut calibrator, Position::ZUp, 1, &mut Delay::new()) {
+        Err(Error::NotReady) => (),
+        _ => panic!("NotReady error not returned."),
+    }
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn collecting_samples_times_out_without_new_data() {
+    let mut transactions = vec![I2cTrans::write(
+        ACCEL_ADDR,
+        vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+    )];
+    // 10 periods of 20 ms at 50 Hz, polled every millisecond
+    for _ in 0..201 {
+        transactions.push(I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::STATUS_REG_A],
+            vec![0],
+        ));
+    }
+    let mut sensor = new_i2c(&transactions);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut calibrator = AccelCalibrator::new();
+    match sensor.calibrate_accel_position(&mut calibrator, Position::ZUp, 1, &mut Delay::new()) {
+        Err(Error::Timeout) => (),
+        _ => panic!("Timeout error not returned."),
+    }
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn failed_collection_keeps_calibrator_unchanged() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![BF::XYZDR]),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            normal_mode_data(16, -8, 1004),
+        ),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![0])
+            .with_error(MockError::Io(ErrorKind::Other)),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut calibrator = AccelCalibrator::new();
+    sensor
+        .calibrate_accel_position(&mut calibrator, Position::ZUp, 2, &mut Delay::new())
+        .expect_err("Should have returned error");
+    assert_eq!(calibrator.samples(Position::ZUp), 0);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn accel_data_applies_calibration() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            normal_mode_data(1040, -1000, 12),
+        ),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            normal_mode_data(1040, -1000, 12),
+        ),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let calibration = AccelCalibration {
+        offset: [40.0, 0.0, 12.0],
+        scale: [1.0, 0.5, 1.0],
+    };
+    sensor.set_accel_calibration(Some(calibration));
+    assert_eq!(sensor.accel_calibration(), Some(calibration));
+    assert_eq!(sensor.accel_data().unwrap(), m(1000, -500, 0));
+    sensor.set_accel_calibration(None);
+    assert_eq!(sensor.accel_data().unwrap(), m(1040, -1000, 12));
+    destroy_i2c(sensor);
+}
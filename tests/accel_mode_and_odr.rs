@@ -14,6 +14,7 @@ macro_rules! normal_pwr {
                 vec![Register::CTRL_REG1_A, $value | DEFAULT_CTRL_REG1_A],
             )]);
             sensor.set_accel_odr(ODR::$hz).unwrap();
+            assert_eq!(sensor.get_accel_odr(), Some(ODR::$hz));
             destroy_i2c(sensor);
         }
     };
@@ -110,6 +111,7 @@ fn can_power_down() {
         vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A],
     )]);
     sensor.set_accel_mode(Mode::PowerDown).unwrap();
+    assert_eq!(sensor.get_accel_odr(), None);
     destroy_i2c(sensor);
 }
 
@@ -165,3 +167,20 @@ fn can_power_down_after_odr3() {
     sensor.set_accel_mode(Mode::PowerDown).unwrap();
     destroy_i2c(sensor);
 }
+
+#[test]
+fn can_lower_odr_from_khz1_344() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, 9 << 4 | DEFAULT_CTRL_REG1_A],
+        ),
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, 2 << 4 | DEFAULT_CTRL_REG1_A],
+        ),
+    ]);
+    sensor.set_accel_odr(ODR::Khz1_344).unwrap();
+    sensor.set_accel_odr(ODR::Hz10).unwrap();
+    destroy_i2c(sensor);
+}
@@ -0,0 +1,227 @@
+mod common;
+use crate::common::{
+    destroy_i2c, new_i2c, BitFlags as BF, Register, ACCEL_ADDR, DEFAULT_CTRL_REG1_A, HZ50,
+};
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use lsm303agr::{
+    AccelOutputDataRate, AccelScale, AutoRangeConfig, AutoRangedMeasurement, AutoRanger, Error,
+    Measurement,
+};
+
+fn set_odr() -> I2cTrans {
+    I2cTrans::write(
+        ACCEL_ADDR,
+        vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+    )
+}
+
+/// Sample with raw 10-bit values in normal mode
+fn raw_sample(x: i16, y: i16, z: i16) -> I2cTrans {
+    I2cTrans::write_read(
+        ACCEL_ADDR,
+        vec![Register::OUT_X_L_A | 0x80],
+        [x, y, z]
+            .iter()
+            .flat_map(|v| (v << 6).to_le_bytes().to_vec())
+            .collect(),
+    )
+}
+
+fn set_scale(fs: u8) -> I2cTrans {
+    I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG4_A, fs << 4])
+}
+
+/// Scale change by the auto-ranger: the old sample is discarded and the next
+/// read waits for new data
+fn switch_scale(fs: u8) -> Vec<I2cTrans> {
+    vec![
+        set_scale(fs),
+        raw_sample(0, 0, 0),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![BF::XYZDR]),
+    ]
+}
+
+fn ranged(x: i32, y: i32, z: i32, scale: AccelScale, clipped: bool) -> AutoRangedMeasurement {
+    AutoRangedMeasurement {
+        data: Measurement { x, y, z },
+        scale,
+        clipped,
+    }
+}
+
+fn ranger() -> AutoRanger {
+    AutoRanger::new(AutoRangeConfig {
+        up_percent: 90,
+        down_percent: 50,
+        hold_samples: 3,
+    })
+}
+
+#[test]
+fn steps_up_near_the_limit() {
+    let mut sensor = new_i2c(
+        &[
+            vec![set_odr(), raw_sample(0, 10, 460), raw_sample(0, 10, 461)],
+            switch_scale(0b01),
+            vec![raw_sample(0, 5, 470)],
+            switch_scale(0b10),
+            vec![raw_sample(0, 3, 500)],
+            switch_scale(0b11),
+            vec![raw_sample(0, 2, 300)],
+        ]
+        .concat(),
+    );
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut ranger = ranger();
+    let results: Vec<_> = (0..5)
+        .map(|_| sensor.accel_data_auto_ranged(&mut ranger).unwrap())
+        .collect();
+    assert_eq!(
+        results,
+        [
+            ranged(0, 40, 1840, AccelScale::G2, false),
+            ranged(0, 40, 1844, AccelScale::G2, false),
+            ranged(0, 40, 3760, AccelScale::G4, false),
+            ranged(0, 48, 8000, AccelScale::G8, false),
+            ranged(0, 96, 14400, AccelScale::G16, false),
+        ]
+    );
+    assert_eq!(sensor.get_accel_scale(), AccelScale::G16);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn reports_clipping() {
+    let mut sensor = new_i2c(&[
+        set_odr(),
+        set_scale(0b11),
+        raw_sample(511, 0, 0),
+        raw_sample(0, -512, 0),
+        raw_sample(0, 0, 510),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    sensor.set_accel_scale(AccelScale::G16).unwrap();
+    let mut ranger = ranger();
+    assert_eq!(
+        sensor.accel_data_auto_ranged(&mut ranger).unwrap(),
+        ranged(24528, 0, 0, AccelScale::G16, true)
+    );
+    assert_eq!(
+        sensor.accel_data_auto_ranged(&mut ranger).unwrap(),
+        ranged(0, -24576, 0, AccelScale::G16, true)
+    );
+    assert_eq!(
+        sensor.accel_data_auto_ranged(&mut ranger).unwrap(),
+        ranged(0, 0, 24480, AccelScale::G16, false)
+    );
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn steps_down_with_hysteresis() {
+    let mut sensor = new_i2c(
+        &[
+            vec![
+                set_odr(),
+                set_scale(0b01),
+                // Below 50% of the ±2g range
+                raw_sample(100, 0, 127),
+                raw_sample(100, 0, 127),
+                // Between the thresholds: restarts the count
+                raw_sample(100, 0, 200),
+                raw_sample(100, 0, 127),
+                raw_sample(100, 0, 127),
+                raw_sample(100, 0, 127),
+            ],
+            switch_scale(0b00),
+            vec![
+                raw_sample(200, 0, 254),
+                // Already at the smallest scale
+                raw_sample(1, 0, 1),
+                raw_sample(1, 0, 1),
+                raw_sample(1, 0, 1),
+            ],
+        ]
+        .concat(),
+    );
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    sensor.set_accel_scale(AccelScale::G4).unwrap();
+    let mut ranger = ranger();
+    let results: Vec<_> = (0..10)
+        .map(|_| sensor.accel_data_auto_ranged(&mut ranger).unwrap())
+        .collect();
+    assert!(results[..6].iter().all(|r| r.scale == AccelScale::G4));
+    assert_eq!(results[5], ranged(800, 0, 1016, AccelScale::G4, false));
+    assert_eq!(results[6], ranged(800, 0, 1016, AccelScale::G2, false));
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn respects_min_scale() {
+    let mut sensor = new_i2c(&[
+        set_odr(),
+        set_scale(0b01),
+        raw_sample(0, 0, 10),
+        raw_sample(0, 0, 10),
+        raw_sample(0, 0, 10),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    sensor.set_accel_scale(AccelScale::G4).unwrap();
+    let mut ranger = ranger().with_min_scale(AccelScale::G4);
+    for _ in 0..3 {
+        sensor.accel_data_auto_ranged(&mut ranger).unwrap();
+    }
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn rejects_powered_down_accelerometer_and_invalid_config() {
+    let mut sensor = new_i2c(&[set_odr()]);
+    let mut ranger = AutoRanger::default();
+    match sensor.accel_data_auto_ranged(&mut ranger) {
+        Err(nb::Error::Other(Error::NotReady)) => (),
+        _ => panic!("NotReady error not returned."),
+    }
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut ranger = AutoRanger::new(AutoRangeConfig {
+        up_percent: 50,
+        down_percent: 50,
+        hold_samples: 1,
+    });
+    match sensor.accel_data_auto_ranged(&mut ranger) {
+        Err(nb::Error::Other(Error::InvalidInputData)) => (),
+        _ => panic!("InvalidInputData error not returned."),
+    }
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn waits_for_new_data_after_scale_change() {
+    let mut sensor = new_i2c(
+        &[
+            vec![set_odr(), raw_sample(0, 0, 480)],
+            switch_scale(0b01)[..2].to_vec(),
+            vec![
+                I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![0]),
+                I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![BF::XYZDR]),
+                raw_sample(0, 0, 240),
+            ],
+        ]
+        .concat(),
+    );
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut ranger = ranger();
+    assert_eq!(
+        sensor.accel_data_auto_ranged(&mut ranger).unwrap(),
+        ranged(0, 0, 1920, AccelScale::G2, false)
+    );
+    match sensor.accel_data_auto_ranged(&mut ranger) {
+        Err(nb::Error::WouldBlock) => (),
+        _ => panic!("WouldBlock not returned."),
+    }
+    assert_eq!(
+        sensor.accel_data_auto_ranged(&mut ranger).unwrap(),
+        ranged(0, 0, 1920, AccelScale::G4, false)
+    );
+    destroy_i2c(sensor);
+}
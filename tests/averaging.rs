@@ -0,0 +1,226 @@
+mod common;
+use crate::common::{
+    destroy_i2c, new_i2c, BitFlags as BF, RecordingDelay, Register, ACCEL_ADDR,
+    DEFAULT_CTRL_REG1_A, HZ50, MAG_ADDR,
+};
+use embedded_hal_mock::{delay::MockNoop as Delay, i2c::Transaction as I2cTrans};
+use lsm303agr::{AccelOutputDataRate, AveragedAxis, Error};
+
+fn accel_status(status: u8) -> I2cTrans {
+    I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![status])
+}
+
+fn accel_sample(x: i16, y: i16, z: i16) -> I2cTrans {
+    I2cTrans::write_read(
+        ACCEL_ADDR,
+        vec![Register::OUT_X_L_A | 0x80],
+        [x, y, z]
+            .iter()
+            .flat_map(|v| ((v / 4) << 6).to_le_bytes().to_vec())
+            .collect(),
+    )
+}
+
+fn mag_status(status: u8) -> I2cTrans {
+    I2cTrans::write_read(MAG_ADDR, vec![Register::STATUS_REG_M], vec![status])
+}
+
+fn mag_sample(x: i16, y: i16, z: i16) -> I2cTrans {
+    I2cTrans::write_read(
+        MAG_ADDR,
+        vec![Register::OUTX_L_REG_M | 0x80],
+        [x, y, z]
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect(),
+    )
+}
+
+fn assert_axis(axis: AveragedAxis, mean: f32, std_dev: f32) {
+    assert!((axis.mean - mean).abs() < 1e-3, "{:?}", axis);
+    assert!((axis.std_dev - std_dev).abs() < 1e-3, "{:?}", axis);
+}
+
+#[test]
+fn averages_fresh_accel_samples() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        accel_status(0),
+        accel_status(BF::XYZDR),
+        accel_sample(100, 0, 1000),
+        accel_status(BF::XYZDR | BF::XYZOR),
+        accel_sample(104, 0, 1004),
+        accel_status(0),
+        accel_status(BF::XYZDR),
+        accel_sample(108, 0, 996),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut delay = RecordingDelay::default();
+    let average = sensor.accel_data_averaged(3, &mut delay).unwrap();
+    assert_eq!(average.samples, 3);
+    assert_axis(average.x, 104.0, 4.0);
+    assert_axis(average.y, 0.0, 0.0);
+    assert_axis(average.z, 1000.0, 4.0);
+    // Polls at an eighth of the sample period while waiting for new data.
+    assert_eq!(delay.delays, [2500, 2500]);
+    assert_eq!(sensor.overrun_counters().accel, 1);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn average_keeps_fractional_mean() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        accel_status(BF::XYZDR),
+        accel_sample(0, -4, 1000),
+        accel_status(BF::XYZDR),
+        accel_sample(4, -4, 1000),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let average = sensor.accel_data_averaged(2, &mut Delay::new()).unwrap();
+    assert_axis(average.x, 2.0, 2.828);
+    assert_axis(average.y, -4.0, 0.0);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn single_sample_has_no_deviation() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        accel_status(BF::XYZDR),
+        accel_sample(8, -4, 1000),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let average = sensor.accel_data_averaged(1, &mut Delay::new()).unwrap();
+    assert_axis(average.x, 8.0, 0.0);
+    assert_axis(average.z, 1000.0, 0.0);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn accel_average_requires_samples_and_odr() {
+    let mut sensor = new_i2c(&[I2cTrans::write(
+        ACCEL_ADDR,
+        vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+    )]);
+    match sensor.accel_data_averaged(4, &mut Delay::new()) {
+        Err(Error::NotReady) => (),
+        _ => panic!("NotReady error not returned."),
+    }
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    match sensor.accel_data_averaged(0, &mut Delay::new()) {
+        Err(Error::InvalidInputData) => (),
+        _ => panic!("InvalidInputData error not returned."),
+    }
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn averages_continuous_mag_samples() {
+    let sensor = new_i2c(&[
+        I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_A_M, 0]),
+        mag_status(BF::XYZDR),
+        mag_sample(10, 20, -30),
+        mag_status(0),
+        mag_status(BF::XYZDR),
+        mag_sample(12, 20, -34),
+    ]);
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    let mut delay = RecordingDelay::default();
+    let average = sensor.mag_data_averaged(2, &mut delay).unwrap();
+    assert_eq!(average.samples, 2);
+    assert_axis(average.x, 1650.0, 212.132);
+    assert_axis(average.y, 3000.0, 0.0);
+    assert_axis(average.z, -4800.0, 424.264);
+    assert_eq!(delay.delays, [12_500]);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn averages_one_shot_mag_measurements() {
+    let mut sensor = new_i2c(&[
+        mag_status(0),
+        I2cTrans::write_read(MAG_ADDR, vec![Register::CFG_REG_A_M], vec![0]),
+        I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_A_M, 1]),
+        mag_status(BF::XYZDR),
+        mag_sample(10, 20, -30),
+        mag_status(0),
+        I2cTrans::write_read(MAG_ADDR, vec![Register::CFG_REG_A_M], vec![0]),
+        I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_A_M, 1]),
+        mag_status(BF::XYZDR),
+        mag_sample(10, 22, -30),
+    ]);
+    let mut delay = RecordingDelay::default();
+    let average = sensor.mag_data_averaged(2, &mut delay).unwrap();
+    assert_axis(average.x, 1500.0, 0.0);
+    assert_axis(average.y, 3150.0, 212.132);
+    assert_eq!(delay.delays, [12_500, 12_500]);
+    match sensor.mag_data_averaged(0, &mut delay) {
+        Err(Error::InvalidInputData) => (),
+        _ => panic!("InvalidInputData error not returned."),
+    }
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn accel_average_times_out_without_new_data() {
+    let mut transactions = vec![I2cTrans::write(
+        ACCEL_ADDR,
+        vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+    )];
+    transactions.extend(vec![accel_status(0); 81]);
+    let mut sensor = new_i2c(&transactions);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut delay = RecordingDelay::default();
+    match sensor.accel_data_averaged(4, &mut delay) {
+        Err(Error::Timeout) => (),
+        _ => panic!("Timeout error not returned."),
+    }
+    assert_eq!(delay.delays, vec![2_500; 80]);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn continuous_mag_average_times_out_without_new_data() {
+    let mut transactions = vec![
+        I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_A_M, 0]),
+        mag_status(BF::XYZDR),
+        mag_sample(10, 20, -30),
+    ];
+    transactions.extend(vec![mag_status(0); 81]);
+    let sensor = new_i2c(&transactions);
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    match sensor.mag_data_averaged(2, &mut Delay::new()) {
+        Err(Error::Timeout) => (),
+        _ => panic!("Timeout error not returned."),
+    }
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn one_shot_mag_average_times_out_if_measurement_does_not_complete() {
+    let mut transactions = Vec::new();
+    for _ in 0..81 {
+        transactions.push(mag_status(0));
+        transactions.push(I2cTrans::write_read(
+            MAG_ADDR,
+            vec![Register::CFG_REG_A_M],
+            vec![1],
+        ));
+    }
+    let mut sensor = new_i2c(&transactions);
+    match sensor.mag_data_averaged(2, &mut Delay::new()) {
+        Err(Error::Timeout) => (),
+        _ => panic!("Timeout error not returned."),
+    }
+    destroy_i2c(sensor);
+}
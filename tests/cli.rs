@@ -0,0 +1,122 @@
+#![cfg(feature = "cli")]
+
+use std::process::{Command, Output};
+
+fn lsm303agr(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lsm303agr"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn shows_info() {
+    let output = lsm303agr(&["--sim", "info"]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Accelerometer: WHO_AM_I_A 0x33 (detected)\n\
+         Magnetometer: WHO_AM_I_M 0x40 (detected)\n"
+    );
+}
+
+#[test]
+fn dumps_registers() {
+    let output = lsm303agr(&["--sim", "dump"]);
+    assert!(output.status.success());
+    let stdout = stdout(&output);
+    assert!(stdout.contains("who_am_i_a: 0x33,"));
+    assert!(stdout.contains("who_am_i_m: 0x40,"));
+    assert!(stdout.contains("fifo_src_reg_a: FifoSrcRegA {"));
+    assert!(stdout.contains("md: 0x3,"));
+}
+
+#[test]
+fn streams_csv() {
+    let output = lsm303agr(&[
+        "--sim",
+        "stream",
+        "--odr",
+        "50",
+        "--mode",
+        "high-resolution",
+        "--scale",
+        "4",
+        "--mag-odr",
+        "20",
+        "--count",
+        "2",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "sample,accel_x_mg,accel_y_mg,accel_z_mg,mag_x_nt,mag_y_nt,mag_z_nt,temperature_c\n\
+         0,0,0,1000,19950,0,-44850,25.00\n\
+         1,0,0,1000,19950,0,-44850,25.00\n"
+    );
+}
+
+#[test]
+fn streams_json() {
+    let output = lsm303agr(&["--sim", "stream", "--format", "json", "--count", "1"]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "{\"sample\":0,\"accel_mg\":[0,0,1000],\"mag_nt\":[19950,0,-44850],\"temperature_c\":25.00}\n"
+    );
+}
+
+#[test]
+fn rejects_odr_unavailable_in_mode() {
+    let output = lsm303agr(&["--sim", "stream", "--odr", "5376", "--count", "1"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("5376 Hz is not available"));
+}
+
+#[test]
+fn runs_self_tests() {
+    let output = lsm303agr(&["--sim", "selftest"]);
+    assert!(output.status.success());
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Accelerometer: PASS"));
+    assert!(stdout.contains("Magnetometer: PASS"));
+}
+
+#[test]
+fn reports_calibration_without_rotation() {
+    let output = lsm303agr(&["--sim", "calibrate", "--samples", "100"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output)
+        .contains("calibration failed: the device was not rotated in enough directions"));
+}
+
+#[test]
+fn rejects_invalid_arguments() {
+    for args in [
+        &[][..],
+        &["--sim"][..],
+        &["--sim", "frobnicate"][..],
+        &["--sim", "stream", "--scale", "3"][..],
+        &["--sim", "stream", "--odr"][..],
+        &["--sim", "info", "extra"][..],
+    ] {
+        let output = lsm303agr(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(stderr(&output).contains("Usage:"));
+    }
+}
+
+#[test]
+fn reports_missing_bus() {
+    let output = lsm303agr(&["--device", "/dev/i2c-lsm303agr-missing", "info"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("cannot open /dev/i2c-lsm303agr-missing"));
+}
@@ -24,9 +24,25 @@ impl Register {
     pub const WHO_AM_I_A: u8 = 0x0F;
     pub const TEMP_CFG_REG_A: u8 = 0x1F;
     pub const CTRL_REG1_A: u8 = 0x20;
+    pub const CTRL_REG3_A: u8 = 0x22;
     pub const CTRL_REG4_A: u8 = 0x23;
+    pub const CTRL_REG5_A: u8 = 0x24;
+    pub const CTRL_REG6_A: u8 = 0x25;
     pub const STATUS_REG_A: u8 = 0x27;
     pub const OUT_X_L_A: u8 = 0x28;
+    pub const FIFO_CTRL_REG_A: u8 = 0x2E;
+    pub const FIFO_SRC_REG_A: u8 = 0x2F;
+    pub const INT1_CFG_A: u8 = 0x30;
+    pub const INT1_SRC_A: u8 = 0x31;
+    pub const INT1_THS_A: u8 = 0x32;
+    pub const INT1_DURATION_A: u8 = 0x33;
+    pub const INT2_CFG_A: u8 = 0x34;
+    pub const INT2_SRC_A: u8 = 0x35;
+    pub const INT2_THS_A: u8 = 0x36;
+    pub const INT2_DURATION_A: u8 = 0x37;
+    pub const OFFSET_X_REG_L_M: u8 = 0x45;
+    pub const OFFSET_Y_REG_L_M: u8 = 0x47;
+    pub const OFFSET_Z_REG_L_M: u8 = 0x49;
     pub const WHO_AM_I_M: u8 = 0x4F;
     pub const CFG_REG_A_M: u8 = 0x60;
     pub const CFG_REG_C_M: u8 = 0x62;
@@ -64,6 +80,38 @@ impl BitFlags {
 
     pub const TEMP_EN0: u8 = 1 << 6;
     pub const TEMP_EN1: u8 = 1 << 7;
+
+    pub const I1_AOI1: u8 = 1 << 6;
+    pub const I1_WTM: u8 = 1 << 2;
+    pub const FIFO_EN: u8 = 1 << 6;
+    pub const LIR_INT1: u8 = 1 << 3;
+    pub const D4D_INT1: u8 = 1 << 2;
+    pub const LIR_INT2: u8 = 1 << 1;
+    pub const D4D_INT2: u8 = 1;
+    pub const I2_INT1: u8 = 1 << 6;
+    pub const I2_INT2: u8 = 1 << 5;
+
+    pub const AOI: u8 = 1 << 7;
+    pub const SIXD: u8 = 1 << 6;
+    pub const ZHIE: u8 = 1 << 5;
+    pub const ZLIE: u8 = 1 << 4;
+    pub const YHIE: u8 = 1 << 3;
+    pub const YLIE: u8 = 1 << 2;
+    pub const XHIE: u8 = 1 << 1;
+    pub const XLIE: u8 = 1;
+
+    pub const IA: u8 = 1 << 6;
+    pub const ZH: u8 = 1 << 5;
+    pub const ZL: u8 = 1 << 4;
+    pub const YH: u8 = 1 << 3;
+    pub const YL: u8 = 1 << 2;
+    pub const XH: u8 = 1 << 1;
+    pub const XL: u8 = 1;
+
+    pub const FIFO_WTM: u8 = 1 << 7;
+    pub const OVRN_FIFO: u8 = 1 << 6;
+    pub const FIFO_EMPTY: u8 = 1 << 5;
+    pub const FIFO_TR: u8 = 1 << 5;
 }
 
 #[allow(unused)]
@@ -77,8 +125,8 @@ pub fn default_cs_n(n: usize) -> PinMock {
         &[PinTrans::set(PinState::Low), PinTrans::set(PinState::High)]
             .iter()
             .cycle()
-            .cloned()
             .take(n * 2)
+            .cloned()
             .collect::<Vec<_>>(),
     )
 }
@@ -129,3 +177,16 @@ pub fn new_i2c(
 pub fn destroy_i2c<MODE>(sensor: Lsm303agr<interface::I2cInterface<I2cMock>, MODE>) {
     sensor.destroy().done();
 }
+
+/// Delay recording the requested durations
+#[allow(unused)]
+#[derive(Debug, Default)]
+pub struct RecordingDelay {
+    pub delays: Vec<u32>,
+}
+
+impl embedded_hal::blocking::delay::DelayUs<u32> for RecordingDelay {
+    fn delay_us(&mut self, us: u32) {
+        self.delays.push(us);
+    }
+}
@@ -0,0 +1,118 @@
+#!/usr/bin/env python3
+"""Generate the synthetic accelerometer traces used by tests/pedometer.rs.
+
+The traces are not recordings of a real device. They are produced from a
+simple gait model: per step, a vertical acceleration pulse with a second
+harmonic, a forward component and a lateral sway at half the step rate, with
+random variation of the step period and amplitude, sensor noise, and the
+sensor mounted at a fixed tilt. Values are in mg and quantized to the
+resolution of the accelerometer in normal mode.
+
+Run from the repository root: python3 tests/data/generate_traces.py
+"""
+import math
+import random
+
+NOISE_MG = 15
+
+
+def rotate(v, roll, pitch):
+    x, y, z = v
+    cr, sr = math.cos(roll), math.sin(roll)
+    y, z = y * cr - z * sr, y * sr + z * cr
+    cp, sp = math.cos(pitch), math.sin(pitch)
+    x, z = x * cp + z * sp, -x * sp + z * cp
+    return x, y, z
+
+
+class Trace:
+    def __init__(self, odr, resolution, roll, pitch):
+        self.odr = odr
+        self.resolution = resolution
+        self.roll = roll
+        self.pitch = pitch
+        self.samples = []
+
+    def add(self, v):
+        x, y, z = rotate(v, self.roll, self.pitch)
+        out = []
+        for c in (x, y, z):
+            c += random.gauss(0, NOISE_MG)
+            out.append(int(round(c / self.resolution)) * self.resolution)
+        self.samples.append(out)
+
+    def still(self, seconds):
+        for _ in range(int(seconds * self.odr)):
+            self.add((0, 0, 1000))
+
+    def steps(self, count, rate, amplitude):
+        for i in range(count):
+            period = random.uniform(0.92, 1.08) / rate
+            a = amplitude * random.uniform(0.85, 1.15)
+            n = int(round(period * self.odr))
+            side = 1 if i % 2 == 0 else -1
+            for k in range(n):
+                p = k / n
+                vertical = a * (math.sin(2 * math.pi * p) + 0.3 * math.sin(4 * math.pi * p + 0.5))
+                forward = 0.4 * a * math.cos(2 * math.pi * p)
+                lateral = 0.2 * a * side * math.sin(math.pi * p)
+                self.add((forward, lateral, 1000 + vertical))
+
+    def shake(self, seconds, frequency, amplitude):
+        for k in range(int(seconds * self.odr)):
+            t = k / self.odr
+            self.add((amplitude * math.sin(2 * math.pi * frequency * t), 0, 1000))
+
+    def bump(self, amplitude, seconds):
+        n = int(seconds * self.odr)
+        for k in range(n):
+            self.add((0, 0, 1000 + amplitude * math.sin(math.pi * k / n)))
+
+    def tilt(self, seconds, angle):
+        n = int(seconds * self.odr)
+        for k in range(n):
+            a = angle * k / n
+            self.add((1000 * math.sin(a), 0, 1000 * math.cos(a)))
+
+    def write(self, name, description, expected_steps):
+        with open("tests/data/" + name, "w") as f:
+            f.write("# Synthetic trace generated by tests/data/generate_traces.py\n")
+            f.write("# %s\n" % description)
+            f.write("# odr: %d\n" % self.odr)
+            f.write("# steps: %d\n" % expected_steps)
+            f.write("# x,y,z in mg\n")
+            for s in self.samples:
+                f.write("%d,%d,%d\n" % tuple(s))
+
+
+random.seed(303)
+
+t = Trace(50, 4, 0.3, -0.2)
+t.still(3)
+t.steps(40, 1.8, 300)
+t.still(3)
+t.write("walking_50hz.csv", "Standing, 40 walking steps at about 1.8 steps/s, standing", 40)
+
+t = Trace(100, 8, -0.5, 0.4)
+t.still(2)
+t.steps(60, 2.8, 1000)
+t.still(2)
+t.write("running_100hz.csv", "Standing, 60 running steps at about 2.8 steps/s, standing", 60)
+
+t = Trace(50, 4, 0.0, 0.0)
+t.still(2)
+t.steps(3, 1.8, 300)
+t.still(3)
+t.bump(400, 0.2)
+t.still(3)
+t.tilt(1, 1.2)
+t.still(1)
+t.bump(-300, 0.3)
+t.still(3)
+t.write("handling_50hz.csv", "Three steps, then picking up, tilting and putting down the device", 0)
+
+t = Trace(50, 4, 0.1, 0.1)
+t.still(2)
+t.shake(4, 6, 800)
+t.still(2)
+t.write("shaking_50hz.csv", "Shaking at 6 Hz", 0)
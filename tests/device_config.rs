@@ -0,0 +1,127 @@
+mod common;
+use crate::common::{
+    destroy_i2c, new_i2c, BitFlags as BF, Register, ACCEL_ADDR, DEFAULT_CFG_REG_A_M,
+    DEFAULT_CTRL_REG1_A, HZ50, MAG_ADDR,
+};
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use lsm303agr::{
+    AccelMode, AccelOutputDataRate as ODR, AccelScale, DeviceConfig, Error, MagOutputDataRate,
+};
+
+const TEMP_EN: u8 = BF::TEMP_EN0 | BF::TEMP_EN1;
+
+fn high_resolution() -> DeviceConfig {
+    DeviceConfig::default()
+        .with_accel_odr(ODR::Hz50)
+        .with_accel_mode(AccelMode::HighResolution)
+        .with_accel_scale(AccelScale::G4)
+        .with_mag_odr(MagOutputDataRate::Hz20)
+}
+
+fn high_resolution_writes() -> Vec<I2cTrans> {
+    vec![
+        I2cTrans::write(ACCEL_ADDR, vec![Register::TEMP_CFG_REG_A, TEMP_EN]),
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, HZ50 | DEFAULT_CTRL_REG1_A],
+        ),
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG4_A, BF::ACCEL_BDU | 1 << 4 | BF::HR],
+        ),
+        I2cTrans::write(
+            MAG_ADDR,
+            vec![Register::CFG_REG_A_M, 1 << 2 | DEFAULT_CFG_REG_A_M],
+        ),
+        I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_C_M, BF::MAG_BDU]),
+    ]
+}
+
+#[test]
+fn applies_config() {
+    let config = high_resolution();
+    let mut sensor = new_i2c(&high_resolution_writes());
+    sensor.apply_config(&config).unwrap();
+    assert_eq!(sensor.device_config(), config);
+    // Nothing to do the second time.
+    sensor.apply_config(&config).unwrap();
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn disables_high_resolution_before_enabling_low_power() {
+    let mut transactions = high_resolution_writes();
+    transactions.extend_from_slice(&[
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG4_A, BF::ACCEL_BDU]),
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![
+                Register::CTRL_REG1_A,
+                BF::LP_EN | 9 << 4 | DEFAULT_CTRL_REG1_A,
+            ],
+        ),
+    ]);
+    let mut sensor = new_i2c(&transactions);
+    sensor.apply_config(&high_resolution()).unwrap();
+    let config = high_resolution()
+        .with_accel_odr(ODR::Khz5_376LowPower)
+        .with_accel_mode(AccelMode::LowPower)
+        .with_accel_scale(AccelScale::G2);
+    sensor.apply_config(&config).unwrap();
+    assert_eq!(sensor.get_accel_odr(), Some(ODR::Khz5_376LowPower));
+    assert_eq!(sensor.device_config(), config);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn powers_down() {
+    let mut transactions = high_resolution_writes();
+    transactions.extend_from_slice(&[
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG4_A, BF::ACCEL_BDU]),
+        I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_A_M, DEFAULT_CFG_REG_A_M]),
+    ]);
+    let mut sensor = new_i2c(&transactions);
+    sensor.apply_config(&high_resolution()).unwrap();
+    sensor.apply_config(&DeviceConfig::default()).unwrap();
+    assert_eq!(sensor.get_accel_odr(), None);
+    assert_eq!(sensor.device_config(), DeviceConfig::default());
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn rejects_invalid_config_without_writing() {
+    let mut sensor = new_i2c(&[]);
+    let incompatible = [
+        (
+            high_resolution().with_accel_odr(ODR::Khz1_620LowPower),
+            ODR::Khz1_620LowPower,
+            AccelMode::HighResolution,
+        ),
+        (
+            high_resolution()
+                .with_accel_odr(ODR::Khz1_344)
+                .with_accel_mode(AccelMode::LowPower),
+            ODR::Khz1_344,
+            AccelMode::LowPower,
+        ),
+        (
+            high_resolution().with_accel_mode(AccelMode::PowerDown),
+            ODR::Hz50,
+            AccelMode::PowerDown,
+        ),
+    ];
+    for (config, expected_odr, expected_mode) in &incompatible {
+        match sensor.apply_config(config) {
+            Err(Error::IncompatibleOdrMode { odr, mode })
+                if odr == *expected_odr && mode == *expected_mode => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+    match sensor.apply_config(&DeviceConfig::default().with_accel_mode(AccelMode::Normal)) {
+        Err(Error::InvalidInputData) => (),
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(sensor.device_config(), DeviceConfig::default());
+    destroy_i2c(sensor);
+}
@@ -0,0 +1,126 @@
+use embedded_hal_mock::{i2c::Transaction as I2cTrans, MockError};
+use lsm303agr::{AccelMode, AccelOutputDataRate, Error, Measurement, Sensor};
+use std::io::ErrorKind;
+mod common;
+use crate::common::{destroy_i2c, new_i2c, Register, ACCEL_ADDR, MAG_ADDR};
+
+type E = Error<&'static str, ()>;
+
+#[test]
+fn error_codes_are_stable() {
+    let errors: [(E, u16); 9] = [
+        (Error::Comm("nack"), 1),
+        (Error::Pin(()), 2),
+        (Error::InvalidInputData, 3),
+        (
+            Error::WriteMismatch {
+                register: 0x20,
+                written: 0x57,
+                read: 0x07,
+            },
+            4,
+        ),
+        (
+            Error::WrongDeviceId {
+                sensor: Sensor::Accelerometer,
+                expected: 0x33,
+                found: 0xFF,
+            },
+            5,
+        ),
+        (
+            Error::IncompatibleOdrMode {
+                odr: AccelOutputDataRate::Khz1_344,
+                mode: AccelMode::LowPower,
+            },
+            6,
+        ),
+        (Error::NotReady, 7),
+        (Error::Timeout, 8),
+        (
+            Error::SelfTestFailed {
+                sensor: Sensor::Magnetometer,
+                difference: Measurement::default(),
+            },
+            9,
+        ),
+    ];
+    for (error, code) in &errors {
+        assert_eq!(error.code(), *code);
+    }
+}
+
+#[test]
+fn displays_errors_with_context() {
+    let error: E = Error::WriteMismatch {
+        register: 0x20,
+        written: 0x57,
+        read: 0x07,
+    };
+    assert_eq!(
+        error.to_string(),
+        "register 0x20 reads 0x07 after writing 0x57"
+    );
+    let error: E = Error::WrongDeviceId {
+        sensor: Sensor::Magnetometer,
+        expected: 0x40,
+        found: 0x00,
+    };
+    assert_eq!(
+        error.to_string(),
+        "wrong magnetometer device ID 0x00, expected 0x40"
+    );
+    let error: E = Error::IncompatibleOdrMode {
+        odr: AccelOutputDataRate::Khz1_344,
+        mode: AccelMode::LowPower,
+    };
+    assert_eq!(
+        error.to_string(),
+        "accelerometer output data rate Khz1_344 is not available in LowPower mode"
+    );
+    let error: E = Error::Comm("nack");
+    assert_eq!(error.to_string(), "I2C/SPI communication error: \"nack\"");
+}
+
+#[test]
+fn checks_device_ids() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::WHO_AM_I_A], vec![0x33]),
+        I2cTrans::write_read(MAG_ADDR, vec![Register::WHO_AM_I_M], vec![0x40]),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::WHO_AM_I_A], vec![0x33]),
+        I2cTrans::write_read(MAG_ADDR, vec![Register::WHO_AM_I_M], vec![0x3D]),
+    ]);
+    sensor.check_device_ids().unwrap();
+    match sensor.check_device_ids() {
+        Err(Error::WrongDeviceId {
+            sensor: Sensor::Magnetometer,
+            expected: 0x40,
+            found: 0x3D,
+        }) => (),
+        other => panic!("unexpected result {:?}", other),
+    }
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn mode_change_error_keeps_device_and_context() {
+    let sensor = new_i2c(&[I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_A_M, 0])
+        .with_error(MockError::Io(ErrorKind::Other))]);
+    let error = sensor.into_mag_continuous().unwrap_err();
+    assert_eq!(error.code(), 1);
+    assert!(error
+        .to_string()
+        .starts_with("could not change the magnetometer mode: I2C/SPI communication error"));
+    destroy_i2c(error.dev);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn implements_std_error() {
+    fn source_of(error: &dyn std::error::Error) -> Option<String> {
+        error.source().map(|source| source.to_string())
+    }
+    let error: E = Error::Timeout;
+    assert_eq!(source_of(&error), None);
+    assert_eq!(error.to_string(), "timeout waiting for new data");
+}
@@ -0,0 +1,179 @@
+use lsm303agr::{
+    filter::{Biquad, ExponentialLowPass, Filter, Median, MovingAverage},
+    Measurement, UnscaledMeasurement,
+};
+
+fn m(x: i32, y: i32, z: i32) -> Measurement {
+    Measurement { x, y, z }
+}
+
+fn sine(amplitude: f32, frequency_hz: f32, sample_rate_hz: f32, n: usize) -> Measurement {
+    let v = amplitude
+        * libm::sinf(2.0 * core::f32::consts::PI * frequency_hz * n as f32 / sample_rate_hz);
+    m(v as i32, 0, 1000)
+}
+
+/// Peak amplitude of the X axis after the filter has settled
+fn filtered_amplitude<F: Filter>(mut filter: F, frequency_hz: f32, sample_rate_hz: f32) -> i32 {
+    let n = 2000;
+    (0..n)
+        .map(|i| {
+            filter
+                .update(sine(1000.0, frequency_hz, sample_rate_hz, i))
+                .x
+        })
+        .skip(n / 2)
+        .map(i32::abs)
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn moving_average() {
+    let mut filter = MovingAverage::<4>::new();
+    assert_eq!(filter.update(m(4, -4, 1)), m(4, -4, 1));
+    assert_eq!(filter.update(m(8, -8, 2)), m(6, -6, 2));
+    assert_eq!(filter.update(m(12, -12, 3)), m(8, -8, 2));
+    assert_eq!(filter.update(m(16, -16, 4)), m(10, -10, 3));
+    // The oldest sample leaves the window.
+    assert_eq!(filter.update(m(20, -20, 5)), m(14, -14, 4));
+    filter.reset();
+    assert_eq!(filter.update(m(1, 2, 3)), m(1, 2, 3));
+}
+
+#[test]
+fn moving_average_of_constant_is_exact() {
+    let mut filter = MovingAverage::<5>::default();
+    for _ in 0..20 {
+        assert_eq!(filter.update(m(1_000_001, -7, 3)), m(1_000_001, -7, 3));
+    }
+}
+
+#[test]
+fn median_removes_spikes() {
+    let mut filter = Median::<3>::new();
+    let input = [
+        m(10, 0, 1000),
+        m(11, 0, 1000),
+        m(900, 0, -3000),
+        m(12, 0, 1000),
+        m(13, 0, 1000),
+    ];
+    let output: Vec<Measurement> = input.iter().map(|s| filter.update(*s)).collect();
+    assert_eq!(
+        output,
+        [
+            m(10, 0, 1000),
+            m(10, 0, 1000),
+            m(11, 0, 1000),
+            m(12, 0, 1000),
+            m(13, 0, 1000)
+        ]
+    );
+}
+
+#[test]
+fn median_of_even_window() {
+    let mut filter = Median::<4>::new();
+    filter.update(m(1, 0, 0));
+    filter.update(m(3, 0, 0));
+    filter.update(m(100, 0, 0));
+    assert_eq!(filter.update(m(5, 0, 0)), m(4, 0, 0));
+}
+
+#[test]
+fn exponential_low_pass() {
+    let mut filter = ExponentialLowPass::new(0.5).unwrap();
+    assert_eq!(filter.update(m(100, 0, 0)), m(100, 0, 0));
+    assert_eq!(filter.update(m(200, 0, 0)), m(150, 0, 0));
+    assert_eq!(filter.update(m(200, 0, 0)), m(175, 0, 0));
+    filter.reset();
+    assert_eq!(filter.update(m(0, 0, 0)), m(0, 0, 0));
+}
+
+#[test]
+fn exponential_low_pass_rejects_invalid_parameters() {
+    assert!(ExponentialLowPass::new(0.0).is_none());
+    assert!(ExponentialLowPass::new(1.5).is_none());
+    assert!(ExponentialLowPass::from_cutoff(0.0, 100.0).is_none());
+    assert!(ExponentialLowPass::from_cutoff(5.0, 0.0).is_none());
+}
+
+#[test]
+fn exponential_low_pass_cutoff() {
+    let filter = ExponentialLowPass::from_cutoff(5.0, 100.0).unwrap();
+    assert!((filter.alpha() - 0.2391).abs() < 1e-3);
+    assert!(filtered_amplitude(filter, 0.5, 100.0) > 980);
+    assert!(filtered_amplitude(filter, 40.0, 100.0) < 150);
+}
+
+#[test]
+fn biquad_low_pass() {
+    let filter = Biquad::low_pass(5.0, 100.0).unwrap();
+    assert!(filtered_amplitude(filter, 0.5, 100.0) > 990);
+    // -3 dB at the cutoff frequency
+    let at_cutoff = filtered_amplitude(filter, 5.0, 100.0);
+    assert!((690..=725).contains(&at_cutoff), "{}", at_cutoff);
+    assert!(filtered_amplitude(filter, 40.0, 100.0) < 20);
+}
+
+#[test]
+fn biquad_high_pass() {
+    let filter = Biquad::high_pass(5.0, 100.0).unwrap();
+    assert!(filtered_amplitude(filter, 0.5, 100.0) < 20);
+    let at_cutoff = filtered_amplitude(filter, 5.0, 100.0);
+    assert!((690..=725).contains(&at_cutoff), "{}", at_cutoff);
+    assert!(filtered_amplitude(filter, 30.0, 100.0) > 980);
+}
+
+#[test]
+fn biquad_starts_in_steady_state() {
+    let mut low_pass = Biquad::low_pass(1.0, 50.0).unwrap();
+    let mut high_pass = Biquad::high_pass(1.0, 50.0).unwrap();
+    for _ in 0..10 {
+        assert_eq!(low_pass.update(m(10, -20, 1000)), m(10, -20, 1000));
+        assert_eq!(high_pass.update(m(10, -20, 1000)), m(0, 0, 0));
+    }
+}
+
+#[test]
+fn biquad_rejects_invalid_cutoff() {
+    assert!(Biquad::low_pass(0.0, 100.0).is_none());
+    assert!(Biquad::low_pass(50.0, 100.0).is_none());
+    assert!(Biquad::high_pass(60.0, 100.0).is_none());
+}
+
+#[test]
+fn chained_filters() {
+    let mut filter = Median::<3>::new().chain(MovingAverage::<2>::new());
+    assert_eq!(filter.update(m(10, 0, 0)), m(10, 0, 0));
+    assert_eq!(filter.update(m(20, 0, 0)), m(13, 0, 0));
+    // The spike is removed by the median before reaching the average.
+    assert_eq!(filter.update(m(5000, 0, 0)), m(18, 0, 0));
+    filter.reset();
+    assert_eq!(filter.update(m(1, 0, 0)), m(1, 0, 0));
+}
+
+#[test]
+fn filters_slices() {
+    let mut samples = [m(2, 0, 0), m(4, 0, 0), m(6, 0, 0)];
+    MovingAverage::<2>::new().update_slice(&mut samples);
+    assert_eq!(samples, [m(2, 0, 0), m(3, 0, 0), m(5, 0, 0)]);
+}
+
+#[test]
+fn filters_unscaled_measurements_with_saturation() {
+    let mut filter = Biquad::new([2.0, 0.0, 0.0], [0.0, 0.0]);
+    assert_eq!(
+        filter.update(UnscaledMeasurement {
+            x: 20000,
+            y: -20000,
+            z: 5
+        }),
+        UnscaledMeasurement {
+            x: i16::MAX,
+            y: i16::MIN,
+            z: 10
+        }
+    );
+}
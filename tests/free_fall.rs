@@ -0,0 +1,116 @@
+mod common;
+use crate::common::{
+    destroy_i2c, new_i2c, BitFlags as BF, Register, ACCEL_ADDR, DEFAULT_CTRL_REG1_A,
+};
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use lsm303agr::{AccelOutputDataRate as ODR, AccelScale, InterruptPin};
+
+const HZ100: u8 = 5 << 4;
+const HZ400: u8 = 7 << 4;
+
+#[test]
+fn can_enable_free_fall_detection() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ100],
+        ),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG5_A, BF::LIR_INT1]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_THS_A, 22]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_DURATION_A, 3]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_CFG_A, 0x95]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG3_A, BF::I1_AOI1]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG6_A, 0]),
+    ]);
+    sensor.set_accel_odr(ODR::Hz100).unwrap();
+    sensor
+        .enable_free_fall_detection(350, 30, InterruptPin::Int1)
+        .unwrap();
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn free_fall_duration_is_rounded_up_and_scale_is_used() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ400],
+        ),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG4_A, 0b01 << 4]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG5_A, BF::LIR_INT1]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_THS_A, 11]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_DURATION_A, 10]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_CFG_A, 0x95]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG3_A, 0]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG6_A, BF::I2_INT1]),
+    ]);
+    sensor.set_accel_odr(ODR::Hz400).unwrap();
+    sensor.set_accel_scale(AccelScale::G4).unwrap();
+    // 23 ms at 400 Hz are 9.2 periods, rounded up to 10
+    sensor
+        .enable_free_fall_detection(350, 23, InterruptPin::Int2)
+        .unwrap();
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn free_fall_requires_odr() {
+    let mut sensor = new_i2c(&[]);
+    sensor
+        .enable_free_fall_detection(350, 30, InterruptPin::Int1)
+        .expect_err("Should have returned error");
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn free_fall_duration_too_long_for_odr() {
+    let mut sensor = new_i2c(&[I2cTrans::write(
+        ACCEL_ADDR,
+        vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ400],
+    )]);
+    sensor.set_accel_odr(ODR::Hz400).unwrap();
+    // 128 periods
+    sensor
+        .enable_free_fall_detection(350, 320, InterruptPin::Int1)
+        .expect_err("Should have returned error");
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn free_fall_threshold_too_low_for_scale() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ100],
+        ),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG4_A, 0b11 << 4]),
+    ]);
+    sensor.set_accel_odr(ODR::Hz100).unwrap();
+    sensor.set_accel_scale(AccelScale::G16).unwrap();
+    sensor
+        .enable_free_fall_detection(50, 30, InterruptPin::Int1)
+        .expect_err("Should have returned error");
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn can_check_free_fall_event() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::INT1_SRC_A],
+            vec![BF::IA | BF::ZL | BF::YL | BF::XL],
+        ),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::INT1_SRC_A], vec![BF::ZH]),
+    ]);
+    assert!(sensor.free_fall_detected().unwrap());
+    assert!(!sensor.free_fall_detected().unwrap());
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn odr_frequency() {
+    assert_eq!(ODR::Hz1.frequency_hz(), 1);
+    assert_eq!(ODR::Khz1_344.frequency_hz(), 1344);
+    assert_eq!(ODR::Khz5_376LowPower.frequency_hz(), 5376);
+}
@@ -0,0 +1,127 @@
+mod common;
+use crate::common::{
+    destroy_i2c, new_i2c, BitFlags as BF, Register, ACCEL_ADDR, DEFAULT_CTRL_REG1_A, HZ50, MAG_ADDR,
+};
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use lsm303agr::{
+    AccelOutputDataRate, DataFreshness, FreshMeasurement, Measurement, OverrunCounters,
+};
+
+fn set_odr() -> I2cTrans {
+    I2cTrans::write(
+        ACCEL_ADDR,
+        vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+    )
+}
+
+fn accel_read(status: u8) -> [I2cTrans; 2] {
+    [
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![status]),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            vec![0x40, 0x0A, 0x00, 0xF6, 0x00, 0x40],
+        ),
+    ]
+}
+
+fn mag_read(status: u8) -> [I2cTrans; 2] {
+    [
+        I2cTrans::write_read(MAG_ADDR, vec![Register::STATUS_REG_M], vec![status]),
+        I2cTrans::write_read(
+            MAG_ADDR,
+            vec![Register::OUTX_L_REG_M | 0x80],
+            vec![0x01, 0x00, 0xFF, 0xFF, 0x0A, 0x00],
+        ),
+    ]
+}
+
+const ACCEL: Measurement = Measurement {
+    x: 164,
+    y: -160,
+    z: 1024,
+};
+
+const MAG: Measurement = Measurement {
+    x: 150,
+    y: -150,
+    z: 1500,
+};
+
+fn fresh(data: Measurement, freshness: DataFreshness) -> FreshMeasurement {
+    FreshMeasurement { data, freshness }
+}
+
+#[test]
+fn accel_data_checked_reports_freshness_and_counts_overruns() {
+    let mut transactions = vec![set_odr()];
+    transactions.extend_from_slice(&accel_read(BF::XYZDR));
+    transactions.extend_from_slice(&accel_read(0));
+    transactions.extend_from_slice(&accel_read(BF::XYZDR | BF::XYZOR));
+    transactions.extend_from_slice(&accel_read(BF::XYZDR | BF::XYZOR));
+    let mut sensor = new_i2c(&transactions);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    assert_eq!(
+        sensor.accel_data_checked().unwrap(),
+        fresh(ACCEL, DataFreshness::New)
+    );
+    assert_eq!(
+        sensor.accel_data_checked().unwrap(),
+        fresh(ACCEL, DataFreshness::Stale)
+    );
+    assert_eq!(
+        sensor.accel_data_checked().unwrap(),
+        fresh(ACCEL, DataFreshness::Overrun)
+    );
+    assert_eq!(
+        sensor.accel_data_checked().unwrap(),
+        fresh(ACCEL, DataFreshness::Overrun)
+    );
+    assert_eq!(
+        sensor.overrun_counters(),
+        OverrunCounters { accel: 2, mag: 0 }
+    );
+    sensor.reset_overrun_counters();
+    assert_eq!(sensor.overrun_counters(), OverrunCounters::default());
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn mag_data_checked_reports_freshness_and_counts_overruns() {
+    let mut transactions = vec![I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_A_M, 0])];
+    transactions.extend_from_slice(&mag_read(0));
+    transactions.extend_from_slice(&mag_read(BF::XYZDR | BF::XYZOR));
+    transactions.extend_from_slice(&mag_read(BF::XYZDR));
+    let sensor = new_i2c(&transactions);
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    assert_eq!(
+        sensor.mag_data_checked().unwrap(),
+        fresh(MAG, DataFreshness::Stale)
+    );
+    assert_eq!(
+        sensor.mag_data_checked().unwrap(),
+        fresh(MAG, DataFreshness::Overrun)
+    );
+    assert_eq!(
+        sensor.mag_data_checked().unwrap(),
+        fresh(MAG, DataFreshness::New)
+    );
+    assert_eq!(
+        sensor.overrun_counters(),
+        OverrunCounters { accel: 0, mag: 1 }
+    );
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn counters_survive_mode_change() {
+    let mut transactions = vec![set_odr()];
+    transactions.extend_from_slice(&accel_read(BF::XYZDR | BF::XYZOR));
+    transactions.push(I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_A_M, 0]));
+    let mut sensor = new_i2c(&transactions);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    sensor.accel_data_checked().unwrap();
+    let sensor = sensor.into_mag_continuous().ok().unwrap();
+    assert_eq!(sensor.overrun_counters().accel, 1);
+    destroy_i2c(sensor);
+}
@@ -0,0 +1,216 @@
+use lsm303agr::{Ahrs, EulerAngles, FusionAlgorithm, Measurement, Quaternion};
+
+const GRAVITY_MG: [f32; 3] = [0.0, 0.0, 1000.0];
+/// Magnetic field pointing north and downwards in nT
+const FIELD_NT: [f32; 3] = [20000.0, 0.0, -40000.0];
+
+/// Rotation matrix from the sensor frame into the earth frame (X north,
+/// Y west, Z up) for Euler angles in degrees.
+fn rotation(roll: f32, pitch: f32, yaw: f32) -> [[f32; 3]; 3] {
+    let (sr, cr) = roll.to_radians().sin_cos();
+    // Pitch up and yaw towards east are negative rotations around Y and Z.
+    let (sp, cp) = (-pitch).to_radians().sin_cos();
+    let (sy, cy) = (-yaw).to_radians().sin_cos();
+    let rx = [[1.0, 0.0, 0.0], [0.0, cr, -sr], [0.0, sr, cr]];
+    let ry = [[cp, 0.0, sp], [0.0, 1.0, 0.0], [-sp, 0.0, cp]];
+    let rz = [[cy, -sy, 0.0], [sy, cy, 0.0], [0.0, 0.0, 1.0]];
+    multiply(rz, multiply(ry, rx))
+}
+
+fn multiply(a: [[f32; 3]; 3], b: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut r = [[0.0; 3]; 3];
+    for (i, row) in r.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    r
+}
+
+/// Measurement of an earth frame vector by a sensor with the given rotation
+fn measure(r: [[f32; 3]; 3], v: [f32; 3]) -> Measurement {
+    let component = |i: usize| (0..3).map(|k| r[k][i] * v[k]).sum::<f32>().round() as i32;
+    Measurement {
+        x: component(0),
+        y: component(1),
+        z: component(2),
+    }
+}
+
+fn readings(roll: f32, pitch: f32, yaw: f32) -> (Measurement, Measurement) {
+    let r = rotation(roll, pitch, yaw);
+    (measure(r, GRAVITY_MG), measure(r, FIELD_NT))
+}
+
+fn angle_difference(a: f32, b: f32) -> f32 {
+    let d = (a - b) % 360.0;
+    if d > 180.0 {
+        d - 360.0
+    } else if d < -180.0 {
+        d + 360.0
+    } else {
+        d
+    }
+}
+
+fn assert_angles(actual: EulerAngles, roll: f32, pitch: f32, yaw: f32, tolerance: f32) {
+    assert!(
+        angle_difference(actual.roll, roll).abs() <= tolerance
+            && (actual.pitch - pitch).abs() <= tolerance
+            && angle_difference(actual.yaw, yaw).abs() <= tolerance,
+        "{:?} is not within {}° of roll {}, pitch {}, yaw {}",
+        actual,
+        tolerance,
+        roll,
+        pitch,
+        yaw
+    );
+}
+
+#[test]
+fn identity_has_zero_angles() {
+    let q = Quaternion::default();
+    assert_eq!((q.w, q.x, q.y, q.z), (1.0, 0.0, 0.0, 0.0));
+    assert_angles(q.euler_angles(), 0.0, 0.0, 0.0, 0.0);
+}
+
+#[test]
+fn quaternion_to_euler_angles() {
+    let half = std::f32::consts::FRAC_1_SQRT_2;
+    // 90° around Z (up) turns the X axis towards west.
+    let q = Quaternion {
+        w: half,
+        x: 0.0,
+        y: 0.0,
+        z: half,
+    };
+    assert_angles(q.euler_angles(), 0.0, 0.0, -90.0, 0.01);
+    // -90° around Y (west) turns the X axis upwards.
+    let q = Quaternion {
+        w: half,
+        x: 0.0,
+        y: -half,
+        z: 0.0,
+    };
+    assert_angles(q.euler_angles(), 0.0, 90.0, 0.0, 0.05);
+}
+
+#[test]
+fn initializes_from_accel_and_mag() {
+    for (roll, pitch, yaw) in [
+        (0.0, 0.0, 0.0),
+        (20.0, -10.0, 45.0),
+        (-150.0, 60.0, -120.0),
+        (5.0, 80.0, 170.0),
+    ]
+    .iter()
+    {
+        let mut ahrs = Ahrs::default();
+        let (accel, mag) = readings(*roll, *pitch, *yaw);
+        ahrs.update(accel, mag, None, 0.01);
+        assert_angles(ahrs.euler_angles(), *roll, *pitch, *yaw, 0.2);
+    }
+}
+
+#[test]
+fn ignores_zero_measurements() {
+    let mut ahrs = Ahrs::default();
+    let (accel, _) = readings(10.0, 0.0, 0.0);
+    ahrs.update(accel, Measurement::default(), None, 0.01);
+    assert_eq!(ahrs.quaternion(), Quaternion::default());
+}
+
+#[test]
+fn smooths_without_gyro() {
+    let mut ahrs = Ahrs::default().with_smoothing(0.1);
+    let (accel, mag) = readings(0.0, 0.0, 0.0);
+    ahrs.update(accel, mag, None, 0.01);
+    let (accel, mag) = readings(0.0, 0.0, 30.0);
+    let yaw = ahrs.update(accel, mag, None, 0.01).euler_angles().yaw;
+    assert!(yaw > 1.0 && yaw < 5.0, "yaw {}", yaw);
+    for _ in 0..200 {
+        ahrs.update(accel, mag, None, 0.01);
+    }
+    assert_angles(ahrs.euler_angles(), 0.0, 0.0, 30.0, 0.2);
+    ahrs.reset();
+    assert_eq!(ahrs.quaternion(), Quaternion::default());
+}
+
+/// Turn the flat sensor from north to east at 30°/s and then roll it to 45°
+/// at 15°/s, feeding consistent gyroscope readings.
+fn track_rotation(algorithm: FusionAlgorithm) {
+    let dt = 0.01;
+    let mut ahrs = Ahrs::new(algorithm);
+    let (accel, mag) = readings(0.0, 0.0, 0.0);
+    ahrs.update(accel, mag, Some([0.0; 3]), dt);
+    for i in 1..=300 {
+        let yaw = 90.0 * i as f32 / 300.0;
+        let (accel, mag) = readings(0.0, 0.0, yaw);
+        // Yaw towards east is a negative rotation around Z.
+        ahrs.update(accel, mag, Some([0.0, 0.0, -30f32.to_radians()]), dt);
+        assert_angles(ahrs.euler_angles(), 0.0, 0.0, yaw, 1.0);
+    }
+    for i in 1..=300 {
+        let roll = 45.0 * i as f32 / 300.0;
+        let (accel, mag) = readings(roll, 0.0, 90.0);
+        ahrs.update(accel, mag, Some([15f32.to_radians(), 0.0, 0.0]), dt);
+        assert_angles(ahrs.euler_angles(), roll, 0.0, 90.0, 1.0);
+    }
+}
+
+#[test]
+fn madgwick_tracks_rotation() {
+    track_rotation(FusionAlgorithm::Madgwick { beta: 0.1 });
+}
+
+#[test]
+fn mahony_tracks_rotation() {
+    track_rotation(FusionAlgorithm::Mahony { kp: 1.0, ki: 0.05 });
+}
+
+/// A stationary sensor with a biased gyroscope
+fn hold_with_gyro_bias(algorithm: FusionAlgorithm) -> EulerAngles {
+    let mut ahrs = Ahrs::new(algorithm);
+    let (accel, mag) = readings(10.0, -20.0, 60.0);
+    for _ in 0..6000 {
+        ahrs.update(accel, mag, Some([0.01, -0.02, 0.02]), 0.01);
+    }
+    ahrs.euler_angles()
+}
+
+#[test]
+fn madgwick_corrects_gyro_bias() {
+    assert_angles(
+        hold_with_gyro_bias(FusionAlgorithm::Madgwick { beta: 0.1 }),
+        10.0,
+        -20.0,
+        60.0,
+        1.0,
+    );
+}
+
+#[test]
+fn mahony_corrects_gyro_bias() {
+    assert_angles(
+        hold_with_gyro_bias(FusionAlgorithm::Mahony { kp: 1.0, ki: 0.2 }),
+        10.0,
+        -20.0,
+        60.0,
+        0.5,
+    );
+}
+
+#[test]
+fn gyro_rejects_short_disturbance() {
+    let mut ahrs = Ahrs::default();
+    let (accel, mag) = readings(0.0, 0.0, 0.0);
+    ahrs.update(accel, mag, Some([0.0; 3]), 0.01);
+    // Shock on the X axis
+    let shock = Measurement {
+        x: 3000,
+        y: 0,
+        z: 1000,
+    };
+    ahrs.update(shock, mag, Some([0.0; 3]), 0.01);
+    assert_angles(ahrs.euler_angles(), 0.0, 0.0, 0.0, 0.2);
+}
@@ -0,0 +1,213 @@
+#![cfg(feature = "iio")]
+
+use lsm303agr::{
+    iio::{IioDevice, IioError},
+    AccelOutputDataRate, AccelScale, MagOutputDataRate, Measurement, Sensor, UnscaledMeasurement,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Fake `/sys/bus/iio/devices` directory, removed on drop
+struct FakeSysfs {
+    root: PathBuf,
+}
+
+impl FakeSysfs {
+    fn new(name: &str) -> Self {
+        let root =
+            std::env::temp_dir().join(format!("lsm303agr-iio-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        FakeSysfs { root }
+    }
+
+    fn device(&self, number: u32, name: &str, attributes: &[(&str, &str)]) -> PathBuf {
+        let dir = self.root.join(format!("iio:device{}", number));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("name"), format!("{}\n", name)).unwrap();
+        for (attribute, value) in attributes {
+            fs::write(dir.join(attribute), format!("{}\n", value)).unwrap();
+        }
+        dir
+    }
+
+    fn lsm303agr(&self) -> (PathBuf, PathBuf) {
+        self.device(0, "lis3dh", &[]);
+        let accel = self.device(
+            1,
+            "lsm303agr_accel",
+            &[
+                ("in_accel_x_raw", "51"),
+                ("in_accel_y_raw", "-102"),
+                ("in_accel_z_raw", "1020"),
+                ("in_accel_scale", "0.009806"),
+                (
+                    "in_accel_scale_available",
+                    "0.078450 0.009806 0.039227 0.019613",
+                ),
+                ("sampling_frequency", "100"),
+            ],
+        );
+        let mag = self.device(
+            2,
+            "lsm303agr_magn",
+            &[
+                ("in_magn_x_raw", "200"),
+                ("in_magn_y_raw", "-30"),
+                ("in_magn_z_raw", "-300"),
+                ("in_magn_scale", "0.001500"),
+                ("sampling_frequency", "10.000000"),
+            ],
+        );
+        (accel, mag)
+    }
+}
+
+impl Drop for FakeSysfs {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn finds_devices_by_name() {
+    let sysfs = FakeSysfs::new("find");
+    let (accel, mag) = sysfs.lsm303agr();
+    let sensor = IioDevice::find_in(&sysfs.root).unwrap();
+    assert_eq!(sensor.accel_dir(), accel);
+    assert_eq!(sensor.mag_dir(), mag);
+}
+
+#[test]
+fn reports_missing_devices() {
+    let sysfs = FakeSysfs::new("missing");
+    sysfs.device(0, "lsm303agr_magn", &[]);
+    match IioDevice::find_in(&sysfs.root) {
+        Err(IioError::NotFound(Sensor::Accelerometer)) => (),
+        other => panic!("unexpected result {:?}", other),
+    }
+    sysfs.device(1, "lsm303agr_accel", &[]);
+    fs::remove_dir_all(sysfs.root.join("iio:device0")).unwrap();
+    match IioDevice::find_in(&sysfs.root) {
+        Err(IioError::NotFound(Sensor::Magnetometer)) => (),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn reads_scaled_accel_data() {
+    let sysfs = FakeSysfs::new("accel");
+    let (accel, mag) = sysfs.lsm303agr();
+    let sensor = IioDevice::new(accel, mag);
+    assert_eq!(
+        sensor.accel_data().unwrap(),
+        Measurement {
+            x: 51,
+            y: -102,
+            z: 1020
+        }
+    );
+    assert_eq!(
+        sensor.accel_data_unscaled().unwrap(),
+        UnscaledMeasurement {
+            x: 51,
+            y: -102,
+            z: 1020
+        }
+    );
+    assert_eq!(sensor.accel_sampling_frequency().unwrap(), 100.0);
+}
+
+#[test]
+fn reads_scaled_mag_data() {
+    let sysfs = FakeSysfs::new("mag");
+    let (accel, mag) = sysfs.lsm303agr();
+    let sensor = IioDevice::new(accel, &mag);
+    let expected = Measurement {
+        x: 30_000,
+        y: -4_500,
+        z: -45_000,
+    };
+    assert_eq!(sensor.mag_data().unwrap(), expected);
+    assert_eq!(
+        sensor.mag_data_unscaled().unwrap(),
+        UnscaledMeasurement {
+            x: 200,
+            y: -30,
+            z: -300
+        }
+    );
+    assert_eq!(sensor.mag_sampling_frequency().unwrap(), 10.0);
+
+    // Per-axis scales take precedence over the shared one.
+    fs::write(mag.join("in_magn_z_scale"), "0.003000\n").unwrap();
+    assert_eq!(
+        sensor.mag_data().unwrap(),
+        Measurement {
+            z: -90_000,
+            ..expected
+        }
+    );
+}
+
+#[test]
+fn sets_odr_and_scale() {
+    let sysfs = FakeSysfs::new("settings");
+    let (accel, mag) = sysfs.lsm303agr();
+    let mut sensor = IioDevice::new(&accel, &mag);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz400).unwrap();
+    assert_eq!(read(&accel.join("sampling_frequency")), "400");
+    sensor.set_mag_odr(MagOutputDataRate::Hz50).unwrap();
+    assert_eq!(read(&mag.join("sampling_frequency")), "50");
+    sensor.set_accel_scale(AccelScale::G8).unwrap();
+    assert_eq!(read(&accel.join("in_accel_scale")), "0.039227");
+    sensor.set_accel_scale(AccelScale::G2).unwrap();
+    assert_eq!(read(&accel.join("in_accel_scale")), "0.009806");
+}
+
+#[test]
+fn rejects_unexpected_scales() {
+    let sysfs = FakeSysfs::new("scales");
+    let (accel, mag) = sysfs.lsm303agr();
+    let mut sensor = IioDevice::new(&accel, mag);
+    fs::write(
+        accel.join("in_accel_scale_available"),
+        "0.009806 0.019613\n",
+    )
+    .unwrap();
+    match sensor.set_accel_scale(AccelScale::G4) {
+        Err(IioError::Unsupported) => (),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn reports_invalid_and_missing_attributes() {
+    let sysfs = FakeSysfs::new("invalid");
+    let (accel, mag) = sysfs.lsm303agr();
+    let sensor = IioDevice::new(&accel, mag);
+    fs::write(accel.join("in_accel_y_raw"), "abc\n").unwrap();
+    match sensor.accel_data() {
+        Err(IioError::Parse { path }) => assert_eq!(path, accel.join("in_accel_y_raw")),
+        other => panic!("unexpected result {:?}", other),
+    }
+    fs::write(accel.join("in_accel_y_raw"), "40000\n").unwrap();
+    assert!(sensor.accel_data().is_ok());
+    assert!(matches!(
+        sensor.accel_data_unscaled(),
+        Err(IioError::Parse { .. })
+    ));
+    fs::remove_file(accel.join("in_accel_scale")).unwrap();
+    match sensor.accel_data() {
+        Err(error @ IioError::Io { .. }) => {
+            assert!(error.to_string().contains("in_accel_scale"))
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
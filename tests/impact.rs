@@ -0,0 +1,192 @@
+mod common;
+use crate::common::{
+    destroy_i2c, new_i2c, BitFlags as BF, Register, ACCEL_ADDR, DEFAULT_CTRL_REG1_A,
+};
+use embedded_hal_mock::{delay::MockNoop as Delay, i2c::Transaction as I2cTrans};
+use lsm303agr::{AccelOutputDataRate as ODR, AccelScale, ImpactLogger, Measurement};
+
+const KHZ1_344: u8 = 9 << 4;
+const G16: u8 = 0b11 << 4;
+
+fn m(x: i32, y: i32, z: i32) -> Measurement {
+    Measurement { x, y, z }
+}
+
+/// Accelerometer output registers for a measurement in mg in normal mode
+/// with ±16g scale (48 mg/LSB, 10-bit left-justified).
+fn g16_data(m: Measurement) -> Vec<u8> {
+    [m.x, m.y, m.z]
+        .iter()
+        .flat_map(|v| (((v / 48) << 6) as i16).to_le_bytes().to_vec())
+        .collect()
+}
+
+fn impact_window() -> Vec<Measurement> {
+    let mut window = vec![m(0, 0, 1008); 32];
+    window[10] = m(4992, -1968, 1008);
+    window[11] = m(7968, -3024, 1488);
+    window[12] = m(4512, -1008, 1008);
+    window
+}
+
+fn setup_transactions() -> Vec<I2cTrans> {
+    vec![
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | KHZ1_344],
+        ),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG4_A, G16]),
+    ]
+}
+
+fn enabled_transactions() -> Vec<I2cTrans> {
+    let mut transactions = setup_transactions();
+    transactions.extend_from_slice(&[
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG5_A, BF::LIR_INT2]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT2_THS_A, 22]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT2_DURATION_A, 0]),
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::INT2_CFG_A, BF::ZHIE | BF::YHIE | BF::XHIE],
+        ),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG6_A, BF::I2_INT2]),
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG5_A, BF::FIFO_EN | BF::LIR_INT2],
+        ),
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::FIFO_CTRL_REG_A, 0b11 << 6 | BF::FIFO_TR],
+        ),
+    ]);
+    transactions
+}
+
+#[test]
+fn can_enable_impact_logger() {
+    let mut sensor = new_i2c(&enabled_transactions());
+    sensor.set_accel_odr(ODR::Khz1_344).unwrap();
+    sensor.set_accel_scale(AccelScale::G16).unwrap();
+    sensor
+        .enable_impact_logger(&ImpactLogger::new(4000))
+        .unwrap();
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn impact_logger_requires_odr() {
+    let mut sensor = new_i2c(&[]);
+    sensor
+        .enable_impact_logger(&ImpactLogger::new(1000))
+        .expect_err("Should have returned error");
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn no_impact() {
+    let mut transactions = setup_transactions();
+    transactions.push(I2cTrans::write_read(
+        ACCEL_ADDR,
+        vec![Register::INT2_SRC_A],
+        vec![0],
+    ));
+    let mut sensor = new_i2c(&transactions);
+    sensor.set_accel_odr(ODR::Khz1_344).unwrap();
+    sensor.set_accel_scale(AccelScale::G16).unwrap();
+    let impact = sensor
+        .read_impact(&ImpactLogger::new(4000), &mut Delay::new())
+        .unwrap();
+    assert_eq!(impact, None);
+    destroy_i2c(sensor);
+}
+
+fn impact_transactions(window: &[Measurement]) -> Vec<I2cTrans> {
+    let mut transactions = enabled_transactions();
+    transactions.extend_from_slice(&[
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::INT2_SRC_A],
+            vec![BF::IA | BF::XH],
+        ),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::FIFO_SRC_REG_A],
+            vec![BF::FIFO_WTM | BF::OVRN_FIFO | 31],
+        ),
+    ]);
+    transactions.push(I2cTrans::write_read(
+        ACCEL_ADDR,
+        vec![Register::OUT_X_L_A | 0x80],
+        window.iter().copied().flat_map(g16_data).collect(),
+    ));
+    transactions.extend_from_slice(&[
+        I2cTrans::write(ACCEL_ADDR, vec![Register::FIFO_CTRL_REG_A, BF::FIFO_TR]),
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::FIFO_CTRL_REG_A, 0b11 << 6 | BF::FIFO_TR],
+        ),
+    ]);
+    transactions
+}
+
+#[test]
+fn can_read_impact() {
+    let mut sensor = new_i2c(&impact_transactions(&impact_window()));
+    sensor.set_accel_odr(ODR::Khz1_344).unwrap();
+    sensor.set_accel_scale(AccelScale::G16).unwrap();
+    let logger = ImpactLogger::new(4000);
+    sensor.enable_impact_logger(&logger).unwrap();
+    let impact = sensor
+        .read_impact(&logger, &mut Delay::new())
+        .unwrap()
+        .unwrap();
+    assert_eq!(impact.peak, m(7968, -3024, 1488));
+    assert_eq!(impact.peak_magnitude_mg, 8651);
+    // 3 samples at 1344 Hz
+    assert_eq!(impact.duration_us, 2232);
+    assert_eq!(impact.trigger_index, 10);
+    assert_eq!(impact.window(), &impact_window()[..]);
+    assert_eq!(impact.pre_trigger().len(), 10);
+    assert_eq!(impact.post_trigger().len(), 22);
+    assert_eq!(impact.post_trigger()[0], m(4992, -1968, 1008));
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn reads_impact_just_over_hardware_threshold() {
+    // The interrupt generator triggers above 22 * 186 mg = 4092 mg.
+    let mut window = vec![m(0, 0, 1008); 32];
+    window[20] = m(4128, 0, 1008);
+    let mut sensor = new_i2c(&impact_transactions(&window));
+    sensor.set_accel_odr(ODR::Khz1_344).unwrap();
+    sensor.set_accel_scale(AccelScale::G16).unwrap();
+    let logger = ImpactLogger::new(4000);
+    sensor.enable_impact_logger(&logger).unwrap();
+    let impact = sensor
+        .read_impact(&logger, &mut Delay::new())
+        .unwrap()
+        .unwrap();
+    assert_eq!(impact.peak, m(4128, 0, 1008));
+    assert_eq!(impact.trigger_index, 20);
+    assert_eq!(impact.duration_us, 744);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn analysis_without_sample_above_threshold() {
+    let logger = ImpactLogger::new(9000);
+    assert_eq!(logger.threshold_mg(), 9000);
+    assert_eq!(logger.analyze(&impact_window(), ODR::Khz1_344), None);
+}
+
+#[test]
+fn analysis_of_negative_impact() {
+    let window = [m(0, 0, 1000), m(-300, 50, -2500), m(0, 0, 1000)];
+    let impact = ImpactLogger::new(2000)
+        .analyze(&window, ODR::Hz400)
+        .unwrap();
+    assert_eq!(impact.peak, m(-300, 50, -2500));
+    assert_eq!(impact.duration_us, 2500);
+    assert_eq!(impact.trigger_index, 1);
+    assert_eq!(impact.pre_trigger(), &window[..1]);
+}
@@ -0,0 +1,124 @@
+mod common;
+use crate::common::{destroy_i2c, new_i2c, Register, ACCEL_ADDR, DEFAULT_CTRL_REG1_A, HZ50};
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use lsm303agr::{AccelOutputDataRate, Axis, AxisOrientation, Inclinometer, Measurement};
+
+fn m(x: i32, y: i32, z: i32) -> Measurement {
+    Measurement { x, y, z }
+}
+
+macro_rules! assert_near {
+    ($actual:expr, $expected:expr, $tolerance:expr) => {
+        assert!(
+            ($actual - $expected).abs() <= $tolerance,
+            "{} is not within {} of {}",
+            $actual,
+            $tolerance,
+            $expected
+        );
+    };
+}
+
+#[test]
+fn level_device_has_no_inclination() {
+    let inclination = Inclinometer::default().compute(m(0, 0, 1000));
+    assert_eq!(inclination.pitch, 0.0);
+    assert_eq!(inclination.roll, 0.0);
+    assert_eq!(inclination.tilt, 0.0);
+    assert_eq!(inclination.g_deviation, 0);
+    assert!(inclination.steady);
+}
+
+#[test]
+fn can_compute_pitch() {
+    let inclination = Inclinometer::default().compute(m(500, 0, 866));
+    assert_near!(inclination.pitch, 30.0, 0.05);
+    assert_near!(inclination.roll, 0.0, 0.05);
+    assert_near!(inclination.tilt, 30.0, 0.05);
+}
+
+#[test]
+fn can_compute_roll() {
+    let inclination = Inclinometer::default().compute(m(0, -707, 707));
+    assert_near!(inclination.pitch, 0.0, 0.05);
+    assert_near!(inclination.roll, -45.0, 0.05);
+    assert_near!(inclination.tilt, 45.0, 0.05);
+}
+
+#[test]
+fn upside_down_is_tilted_180_degrees() {
+    let inclination = Inclinometer::default().compute(m(0, 0, -1000));
+    assert_near!(inclination.tilt, 180.0, 0.05);
+    assert_near!(inclination.roll.abs(), 180.0, 0.05);
+}
+
+#[test]
+fn flags_moving_device() {
+    let inclinometer = Inclinometer::default().with_tolerance(50);
+    let inclination = inclinometer.compute(m(0, 300, 1400));
+    assert!(!inclination.steady);
+    assert_eq!(inclination.g_deviation, 432);
+    assert!(inclinometer.compute(m(0, 0, 1040)).steady);
+    assert!(!inclinometer.compute(m(0, 0, 900)).steady);
+}
+
+#[test]
+fn applies_mounting_orientation() {
+    // Sensor mounted vertically: its X axis points upwards.
+    let inclinometer = Inclinometer::new(AxisOrientation {
+        x: Axis::PositiveY,
+        y: Axis::NegativeZ,
+        z: Axis::PositiveX,
+    });
+    let inclination = inclinometer.compute(m(866, 0, -500));
+    assert_near!(inclination.pitch, 0.0, 0.05);
+    assert_near!(inclination.roll, 30.0, 0.05);
+}
+
+#[test]
+fn fixed_point_matches_floating_point() {
+    let inclinometer = Inclinometer::default();
+    let samples = [
+        m(0, 0, 1000),
+        m(500, 0, 866),
+        m(0, -707, 707),
+        m(-250, 433, -866),
+        m(-1000, 0, 0),
+        m(10, 20, -990),
+        m(-640, -700, 310),
+        m(16000, -12000, 4000),
+    ];
+    for sample in samples.iter() {
+        let float = inclinometer.compute(*sample);
+        let fixed = inclinometer.compute_fixed(*sample);
+        assert_near!(fixed.pitch as f32 / 100.0, float.pitch, 0.02);
+        assert_near!(fixed.roll as f32 / 100.0, float.roll, 0.02);
+        assert_near!(fixed.tilt as f32 / 100.0, float.tilt, 0.02);
+        assert_near!(fixed.g_deviation, float.g_deviation, 1);
+        assert_eq!(fixed.steady, float.steady);
+    }
+}
+
+#[test]
+fn can_read_inclination() {
+    // 10-bit normal mode, 4 mg/LSB: x = 500 mg, z = 868 mg
+    let x = ((500 / 4) << 6) as u16;
+    let z = ((868 / 4) << 6) as u16;
+    let data = vec![x as u8, (x >> 8) as u8, 0, 0, z as u8, (z >> 8) as u8];
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::OUT_X_L_A | 0x80], data.clone()),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::OUT_X_L_A | 0x80], data),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let inclinometer = Inclinometer::default();
+    let inclination = sensor.inclination(&inclinometer).unwrap();
+    assert_near!(inclination.pitch, 29.94, 0.05);
+    assert!(inclination.steady);
+    let inclination = sensor.inclination_fixed(&inclinometer).unwrap();
+    assert_near!(inclination.pitch, 2994, 5);
+    destroy_i2c(sensor);
+}
@@ -0,0 +1,138 @@
+mod common;
+use crate::common::{destroy_i2c, new_i2c, Register, MAG_ADDR};
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use lsm303agr::{MagCalibration, MagCalibrationError, MagCalibrator, Measurement};
+
+const FIELD: f32 = 48_000.0;
+const HARD_IRON: [f32; 3] = [12_000.0, -8_000.0, 3_000.0];
+const SOFT_IRON: [[f32; 3]; 3] = [[1.2, 0.05, 0.0], [0.05, 0.9, 0.02], [0.0, 0.02, 1.05]];
+
+/// Points evenly spread over a sphere (Fibonacci lattice), distorted by the
+/// hard-iron and soft-iron effects above. The points are visited in a
+/// scrambled order like when the device is rotated by hand.
+fn distorted_samples(count: usize) -> Vec<Measurement> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+    (0..count)
+        .map(|i| (i * 7919) % count)
+        .map(|i| {
+            let z = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+            let r = (1.0 - z * z).sqrt();
+            let theta = golden_angle * i as f32;
+            let b = [FIELD * r * theta.cos(), FIELD * r * theta.sin(), FIELD * z];
+            let m = |row: usize| {
+                SOFT_IRON[row][0] * b[0]
+                    + SOFT_IRON[row][1] * b[1]
+                    + SOFT_IRON[row][2] * b[2]
+                    + HARD_IRON[row]
+            };
+            Measurement {
+                x: m(0) as i32,
+                y: m(1) as i32,
+                z: m(2) as i32,
+            }
+        })
+        .collect()
+}
+
+fn magnitude(m: Measurement) -> f32 {
+    ((m.x as f32).powi(2) + (m.y as f32).powi(2) + (m.z as f32).powi(2)).sqrt()
+}
+
+#[test]
+fn empty_calibrator_cannot_solve() {
+    let calibrator = MagCalibrator::new();
+    assert_eq!(
+        calibrator.solve(),
+        Err(MagCalibrationError::NotEnoughSamples)
+    );
+    let report = calibrator.report();
+    assert_eq!(report.samples, 0);
+    assert_eq!(report.coverage, 0.0);
+    assert_eq!(report.fit_error, None);
+}
+
+#[test]
+fn samples_in_a_single_plane_have_insufficient_coverage() {
+    let mut calibrator = MagCalibrator::new();
+    for i in 0..100 {
+        let angle = i as f32 * 0.0628;
+        calibrator.add_sample(Measurement {
+            x: (FIELD * angle.cos()) as i32,
+            y: (FIELD * angle.sin()) as i32,
+            z: 0,
+        });
+    }
+    assert!(calibrator.coverage() < 0.5);
+    assert_eq!(
+        calibrator.solve(),
+        Err(MagCalibrationError::InsufficientCoverage)
+    );
+}
+
+#[test]
+fn can_solve_hard_and_soft_iron() {
+    let samples = distorted_samples(500);
+    let mut calibrator = MagCalibrator::new();
+    for sample in samples.iter() {
+        calibrator.add_sample(*sample);
+    }
+    let report = calibrator.report();
+    assert_eq!(report.samples, 500);
+    assert!(report.coverage > 0.9, "coverage {}", report.coverage);
+    assert!(report.fit_error.unwrap() < 0.001);
+
+    let calibration = calibrator.solve().unwrap();
+    for (actual, expected) in calibration.hard_iron.iter().zip(HARD_IRON.iter()) {
+        assert!((actual - expected).abs() < 50.0);
+    }
+    for sample in samples.iter() {
+        let corrected = calibration.apply(*sample);
+        let error = (magnitude(corrected) - calibration.field_strength).abs();
+        assert!(error < calibration.field_strength * 0.005);
+    }
+    // The volume preserving radius is close to the original field.
+    assert!((calibration.field_strength - FIELD).abs() < FIELD * 0.05);
+}
+
+#[test]
+fn default_calibration_does_nothing() {
+    let m = Measurement {
+        x: 1500,
+        y: -3000,
+        z: 450,
+    };
+    assert_eq!(MagCalibration::default().apply(m), m);
+}
+
+#[test]
+fn can_offload_hard_iron_offset() {
+    let calibration = MagCalibration {
+        hard_iron: [1520.0, -310.0, 40.0],
+        ..MagCalibration::default()
+    };
+    let offset = calibration.hard_iron_offset();
+    assert_eq!((offset.x, offset.y, offset.z), (10, -2, 0));
+    let remaining = calibration.without_hard_iron_offset();
+    assert_eq!(remaining.hard_iron, [20.0, -10.0, 40.0]);
+    assert_eq!(remaining.soft_iron, calibration.soft_iron);
+}
+
+#[test]
+fn can_set_mag_offset() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(MAG_ADDR, vec![Register::OFFSET_X_REG_L_M, 0x34]),
+        I2cTrans::write(MAG_ADDR, vec![Register::OFFSET_X_REG_L_M + 1, 0x12]),
+        I2cTrans::write(MAG_ADDR, vec![Register::OFFSET_Y_REG_L_M, 0xFE]),
+        I2cTrans::write(MAG_ADDR, vec![Register::OFFSET_Y_REG_L_M + 1, 0xFF]),
+        I2cTrans::write(MAG_ADDR, vec![Register::OFFSET_Z_REG_L_M, 0]),
+        I2cTrans::write(MAG_ADDR, vec![Register::OFFSET_Z_REG_L_M + 1, 0]),
+    ]);
+    sensor
+        .set_mag_offset(lsm303agr::UnscaledMeasurement {
+            x: 0x1234,
+            y: -2,
+            z: 0,
+        })
+        .unwrap();
+    destroy_i2c(sensor);
+}
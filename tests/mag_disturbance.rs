@@ -0,0 +1,188 @@
+mod common;
+use crate::common::{
+    destroy_i2c, new_i2c, Register, ACCEL_ADDR, DEFAULT_CTRL_REG1_A, HZ50, MAG_ADDR,
+};
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use lsm303agr::{AccelOutputDataRate, MagCalibration, MagDisturbanceDetector, Measurement};
+
+const FLAT: Measurement = Measurement {
+    x: 0,
+    y: 0,
+    z: 1000,
+};
+
+/// Field of the given strength in nT pointing north with the given dip angle
+/// for a device lying flat
+fn field(strength: f32, dip_deg: f32) -> Measurement {
+    let dip = dip_deg.to_radians();
+    Measurement {
+        x: (strength * dip.cos()) as i32,
+        y: 0,
+        z: -(strength * dip.sin()) as i32,
+    }
+}
+
+fn detector() -> MagDisturbanceDetector {
+    MagDisturbanceDetector::new(50_000.0).with_dip_angle(60.0)
+}
+
+#[test]
+fn undisturbed_field_is_trustworthy() {
+    let status = detector().check(field(50_000.0, 60.0), Some(FLAT));
+    assert!(status.trustworthy);
+    assert!(!status.saturated);
+    assert!(!status.strength_disturbed);
+    assert!(!status.dip_disturbed);
+    assert!((status.field_strength - 50_000.0).abs() < 2.0);
+    assert!((status.dip_angle.unwrap() - 60.0).abs() < 0.01);
+}
+
+#[test]
+fn detects_field_strength_disturbance() {
+    let status = detector().check(field(60_000.0, 60.0), Some(FLAT));
+    assert!(status.strength_disturbed);
+    assert!(!status.dip_disturbed);
+    assert!(!status.trustworthy);
+    // Within the default tolerance of 15%
+    assert!(detector().check(field(57_000.0, 60.0), None).trustworthy);
+    assert!(
+        !detector()
+            .with_field_tolerance(10)
+            .check(field(57_000.0, 60.0), None)
+            .trustworthy
+    );
+}
+
+#[test]
+fn detects_dip_angle_disturbance() {
+    let status = detector().check(field(50_000.0, 45.0), Some(FLAT));
+    assert!(!status.strength_disturbed);
+    assert!(status.dip_disturbed);
+    assert!(!status.trustworthy);
+    assert!(
+        detector()
+            .with_dip_tolerance(20.0)
+            .check(field(50_000.0, 45.0), Some(FLAT))
+            .trustworthy
+    );
+}
+
+#[test]
+fn dip_angle_follows_device_tilt() {
+    // Device rolled by 90° around X: Y axis points up.
+    let mag = field(50_000.0, 60.0);
+    let rolled = Measurement {
+        x: mag.x,
+        y: mag.z,
+        z: -mag.y,
+    };
+    let accel = Measurement {
+        x: 0,
+        y: 1000,
+        z: 0,
+    };
+    let status = detector().check(rolled, Some(accel));
+    assert!((status.dip_angle.unwrap() - 60.0).abs() < 0.01);
+    assert!(status.trustworthy);
+}
+
+#[test]
+fn skips_dip_check_while_accelerating() {
+    let accel = Measurement {
+        x: 0,
+        y: 0,
+        z: 1500,
+    };
+    let status = detector().check(field(50_000.0, 20.0), Some(accel));
+    assert_eq!(status.dip_angle, None);
+    assert!(status.trustworthy);
+    let status = detector()
+        .with_accel_tolerance(600)
+        .check(field(50_000.0, 20.0), Some(accel));
+    assert!(status.dip_disturbed);
+}
+
+#[test]
+fn detects_saturation() {
+    let mag = Measurement {
+        x: 32767 * 150,
+        y: 0,
+        z: 0,
+    };
+    let status = MagDisturbanceDetector::new(4_915_050.0).check(mag, None);
+    assert!(status.saturated);
+    assert!(!status.strength_disturbed);
+    assert!(!status.trustworthy);
+    let mag = Measurement {
+        x: 0,
+        y: -31500 * 150,
+        z: 0,
+    };
+    assert!(detector().check(mag, None).saturated);
+}
+
+#[test]
+fn learns_expected_field() {
+    let mut detector = MagDisturbanceDetector::new(0.0);
+    assert_eq!(detector.dip_angle(), None);
+    detector.learn(field(48_000.0, 65.0), FLAT);
+    assert!((detector.field_strength() - 48_000.0).abs() < 2.0);
+    assert!((detector.dip_angle().unwrap() - 65.0).abs() < 0.01);
+    assert!(
+        detector
+            .check(field(48_000.0, 65.0), Some(FLAT))
+            .trustworthy
+    );
+    assert!(
+        !detector
+            .check(field(48_000.0, 50.0), Some(FLAT))
+            .trustworthy
+    );
+}
+
+#[test]
+fn uses_calibration() {
+    let calibration = MagCalibration {
+        hard_iron: [20_000.0, 0.0, 0.0],
+        field_strength: 50_000.0,
+        ..MagCalibration::default()
+    };
+    let detector = MagDisturbanceDetector::from_calibration(&calibration).with_dip_angle(60.0);
+    let mut mag = field(50_000.0, 60.0);
+    mag.x += 20_000;
+    assert!(detector.check(mag, Some(FLAT)).trustworthy);
+    assert!(
+        !MagDisturbanceDetector::new(50_000.0)
+            .check(mag, Some(FLAT))
+            .trustworthy
+    );
+}
+
+#[test]
+fn checks_field_read_from_device() {
+    let sensor = new_i2c(&[
+        I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_A_M, 0]),
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        // 30000 nT north, 60000 nT down
+        I2cTrans::write_read(
+            MAG_ADDR,
+            vec![Register::OUTX_L_REG_M | 0x80],
+            vec![200, 0, 0, 0, 0x70, 0xFE],
+        ),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            vec![0, 0, 0, 0, 0x00, 0x3E],
+        ),
+    ]);
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let detector = MagDisturbanceDetector::new(67_082.0).with_dip_angle(63.4);
+    let status = sensor.check_mag_field(&detector).unwrap();
+    assert!(status.trustworthy);
+    assert!((status.dip_angle.unwrap() - 63.43).abs() < 0.01);
+    destroy_i2c(sensor);
+}
@@ -19,6 +19,7 @@ macro_rules! set_mag_odr {
                 vec![Register::CFG_REG_A_M, $value | DEFAULT_CFG_REG_A_M],
             )]);
             sensor.set_mag_odr(ODR::$hz).unwrap();
+            assert_eq!(sensor.get_mag_odr(), ODR::$hz);
             destroy_i2c(sensor);
         }
     };
@@ -0,0 +1,281 @@
+mod common;
+use crate::common::{destroy_i2c, new_i2c, BitFlags as BF, Register, ACCEL_ADDR};
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use lsm303agr::{
+    AccelScale, Axis, AxisOrientation, Error, InterruptPin, Measurement, Orientation,
+    OrientationConfig, OrientationDetector, OrientationMode,
+};
+
+fn m(x: i32, y: i32, z: i32) -> Measurement {
+    Measurement { x, y, z }
+}
+
+#[test]
+fn detects_all_six_orientations() {
+    let mut detector = OrientationDetector::new(OrientationConfig::default());
+    assert_eq!(detector.orientation(), None);
+    assert_eq!(
+        detector.update(m(0, 980, 100)),
+        Some(Orientation::PortraitUp)
+    );
+    assert_eq!(
+        detector.update(m(0, -980, 100)),
+        Some(Orientation::PortraitDown)
+    );
+    assert_eq!(
+        detector.update(m(990, 0, 0)),
+        Some(Orientation::LandscapeLeft)
+    );
+    assert_eq!(
+        detector.update(m(-990, 0, 0)),
+        Some(Orientation::LandscapeRight)
+    );
+    assert_eq!(detector.update(m(50, 20, 1010)), Some(Orientation::FaceUp));
+    assert_eq!(
+        detector.update(m(50, 20, -1010)),
+        Some(Orientation::FaceDown)
+    );
+}
+
+#[test]
+fn keeps_orientation_between_positions() {
+    let mut detector = OrientationDetector::new(OrientationConfig::default());
+    detector.update(m(0, 1000, 0));
+    // Rotating towards landscape: at 45° no position is recognized.
+    assert_eq!(
+        detector.update(m(707, 707, 0)),
+        Some(Orientation::PortraitUp)
+    );
+    assert_eq!(
+        detector.update(m(780, 620, 0)),
+        Some(Orientation::PortraitUp)
+    );
+    assert_eq!(
+        detector.update(m(820, 570, 0)),
+        Some(Orientation::LandscapeLeft)
+    );
+    // Small movements back do not flip it.
+    assert_eq!(
+        detector.update(m(707, 707, 0)),
+        Some(Orientation::LandscapeLeft)
+    );
+}
+
+#[test]
+fn position_must_be_held_for_duration() {
+    let mut detector = OrientationDetector::new(OrientationConfig {
+        duration: 2,
+        ..OrientationConfig::default()
+    });
+    detector.update(m(0, 1000, 0));
+    detector.update(m(0, 1000, 0));
+    assert_eq!(
+        detector.update(m(0, 1000, 0)),
+        Some(Orientation::PortraitUp)
+    );
+    assert_eq!(
+        detector.update(m(1000, 0, 0)),
+        Some(Orientation::PortraitUp)
+    );
+    assert_eq!(
+        detector.update(m(1000, 0, 0)),
+        Some(Orientation::PortraitUp)
+    );
+    // interrupted
+    assert_eq!(
+        detector.update(m(0, 1000, 0)),
+        Some(Orientation::PortraitUp)
+    );
+    assert_eq!(
+        detector.update(m(1000, 0, 0)),
+        Some(Orientation::PortraitUp)
+    );
+    assert_eq!(
+        detector.update(m(1000, 0, 0)),
+        Some(Orientation::PortraitUp)
+    );
+    assert_eq!(
+        detector.update(m(1000, 0, 0)),
+        Some(Orientation::LandscapeLeft)
+    );
+}
+
+#[test]
+fn four_d_mode_ignores_z() {
+    let mut detector = OrientationDetector::new(OrientationConfig {
+        mode: OrientationMode::FourD,
+        ..OrientationConfig::default()
+    });
+    assert_eq!(detector.update(m(0, 0, 1000)), None);
+    assert_eq!(
+        detector.update(m(0, -1000, 0)),
+        Some(Orientation::PortraitDown)
+    );
+    assert_eq!(
+        detector.update(m(0, 0, 1000)),
+        Some(Orientation::PortraitDown)
+    );
+}
+
+#[test]
+fn applies_mounting_orientation() {
+    let mut detector = OrientationDetector::new(OrientationConfig {
+        axes: AxisOrientation {
+            x: Axis::NegativeY,
+            y: Axis::PositiveX,
+            z: Axis::PositiveZ,
+        },
+        ..OrientationConfig::default()
+    });
+    assert_eq!(
+        detector.update(m(1000, 0, 0)),
+        Some(Orientation::PortraitUp)
+    );
+}
+
+#[test]
+fn can_enable_orientation_detection() {
+    let cfg = BF::AOI | BF::SIXD | BF::ZHIE | BF::ZLIE | BF::YHIE | BF::YLIE | BF::XHIE | BF::XLIE;
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG4_A, 0b01 << 4]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG5_A, 0]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_THS_A, 25]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_DURATION_A, 3]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_CFG_A, cfg]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG3_A, BF::I1_AOI1]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG6_A, 0]),
+    ]);
+    sensor.set_accel_scale(AccelScale::G4).unwrap();
+    let config = OrientationConfig {
+        duration: 3,
+        ..OrientationConfig::default()
+    };
+    sensor
+        .enable_orientation_detection(&config, InterruptPin::Int1)
+        .unwrap();
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn can_enable_four_d_detection_on_int2() {
+    let cfg = BF::AOI | BF::SIXD | BF::ZHIE | BF::ZLIE | BF::YHIE | BF::YLIE | BF::XHIE | BF::XLIE;
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG5_A, BF::D4D_INT1]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_THS_A, 50]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_DURATION_A, 0]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::INT1_CFG_A, cfg]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG3_A, 0]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG6_A, BF::I2_INT1]),
+    ]);
+    let config = OrientationConfig {
+        mode: OrientationMode::FourD,
+        ..OrientationConfig::default()
+    };
+    sensor
+        .enable_orientation_detection(&config, InterruptPin::Int2)
+        .unwrap();
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn rejects_unrepresentable_threshold() {
+    let mut sensor = new_i2c(&[]);
+    let config = OrientationConfig {
+        threshold_mg: 2100,
+        ..OrientationConfig::default()
+    };
+    sensor
+        .enable_orientation_detection(&config, InterruptPin::Int1)
+        .expect_err("Should have returned error");
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn rejects_maximum_threshold_without_overflow() {
+    let mut sensor = new_i2c(&[I2cTrans::write(
+        ACCEL_ADDR,
+        vec![Register::CTRL_REG4_A, 0b11 << 4],
+    )]);
+    sensor.set_accel_scale(AccelScale::G16).unwrap();
+    let config = OrientationConfig {
+        threshold_mg: u16::MAX,
+        ..OrientationConfig::default()
+    };
+    match sensor.enable_orientation_detection(&config, InterruptPin::Int1) {
+        Err(Error::InvalidInputData) => (),
+        _ => panic!("InvalidInputData error not returned."),
+    }
+    destroy_i2c(sensor);
+}
+
+macro_rules! generator_position {
+    ($name:ident, $src:expr, $expected:expr) => {
+        #[test]
+        fn $name() {
+            let mut sensor = new_i2c(&[I2cTrans::write_read(
+                ACCEL_ADDR,
+                vec![Register::INT1_SRC_A],
+                vec![$src],
+            )]);
+            let config = OrientationConfig::default();
+            assert_eq!(sensor.accel_orientation(&config).unwrap(), $expected);
+            destroy_i2c(sensor);
+        }
+    };
+}
+
+generator_position!(generator_no_event, BF::ZH, None);
+generator_position!(
+    generator_face_up,
+    BF::IA | BF::ZH,
+    Some(Orientation::FaceUp)
+);
+generator_position!(
+    generator_face_down,
+    BF::IA | BF::ZL,
+    Some(Orientation::FaceDown)
+);
+generator_position!(
+    generator_portrait_up,
+    BF::IA | BF::YH,
+    Some(Orientation::PortraitUp)
+);
+generator_position!(
+    generator_portrait_down,
+    BF::IA | BF::YL,
+    Some(Orientation::PortraitDown)
+);
+generator_position!(
+    generator_landscape_left,
+    BF::IA | BF::XH,
+    Some(Orientation::LandscapeLeft)
+);
+generator_position!(
+    generator_landscape_right,
+    BF::IA | BF::XL,
+    Some(Orientation::LandscapeRight)
+);
+
+#[test]
+fn generator_and_software_paths_agree() {
+    let config = OrientationConfig::default();
+    let mut software = OrientationDetector::new(config);
+    let mut hardware = OrientationDetector::new(config);
+    let samples = [
+        (m(0, 990, 0), BF::IA | BF::YH),
+        (m(707, 707, 0), 0),
+        (m(820, 570, 0), BF::IA | BF::XH),
+        (m(0, 0, 0), 0),
+        (m(0, 0, -1000), BF::IA | BF::ZL),
+    ];
+    for (sample, src) in samples.iter() {
+        let mut sensor = new_i2c(&[I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::INT1_SRC_A],
+            vec![*src],
+        )]);
+        let position = sensor.accel_orientation(&config).unwrap();
+        assert_eq!(software.update(*sample), hardware.update_position(position));
+        destroy_i2c(sensor);
+    }
+}
@@ -0,0 +1,216 @@
+mod common;
+use crate::common::{
+    destroy_i2c, new_i2c, BitFlags as BF, Register, ACCEL_ADDR, DEFAULT_CTRL_REG1_A, HZ50,
+};
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use lsm303agr::{
+    AccelOutputDataRate as ODR, FifoMode, FifoStatus, Measurement, StepCounter, StepCounterConfig,
+};
+
+/// Synthetic trace generated by `tests/data/generate_traces.py`: output data
+/// rate, expected number of steps and samples
+struct Trace {
+    odr: ODR,
+    steps: u32,
+    samples: Vec<Measurement>,
+}
+
+fn load_trace(name: &str) -> Trace {
+    let path = format!("{}/tests/data/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let content = std::fs::read_to_string(path).unwrap();
+    let (mut odr, mut steps) = (None, None);
+    let mut samples = Vec::new();
+    for line in content.lines() {
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut parts = comment.trim().splitn(2, ": ");
+            match (parts.next(), parts.next()) {
+                (Some("odr"), Some("50")) => odr = Some(ODR::Hz50),
+                (Some("odr"), Some("100")) => odr = Some(ODR::Hz100),
+                (Some("steps"), Some(value)) => steps = Some(value.parse().unwrap()),
+                _ => (),
+            }
+            continue;
+        }
+        let values: Vec<i32> = line.split(',').map(|v| v.parse().unwrap()).collect();
+        samples.push(Measurement {
+            x: values[0],
+            y: values[1],
+            z: values[2],
+        });
+    }
+    Trace {
+        odr: odr.unwrap(),
+        steps: steps.unwrap(),
+        samples,
+    }
+}
+
+fn count_steps(name: &str) -> (u32, u32) {
+    let trace = load_trace(name);
+    let mut counter = StepCounter::new(trace.odr, StepCounterConfig::default());
+    let reported: u32 = trace.samples.iter().map(|m| counter.update(*m)).sum();
+    assert_eq!(reported, counter.steps());
+    (counter.steps(), trace.steps)
+}
+
+#[test]
+fn counts_walking_steps() {
+    let (counted, expected) = count_steps("walking_50hz.csv");
+    assert_eq!(counted, expected);
+}
+
+#[test]
+fn counts_running_steps() {
+    let (counted, expected) = count_steps("running_100hz.csv");
+    assert_eq!(counted, expected);
+}
+
+#[test]
+fn ignores_handling_and_isolated_steps() {
+    let (counted, expected) = count_steps("handling_50hz.csv");
+    assert_eq!(counted, expected);
+}
+
+#[test]
+fn ignores_shaking() {
+    let (counted, expected) = count_steps("shaking_50hz.csv");
+    assert_eq!(counted, expected);
+}
+
+#[test]
+fn batches_give_same_result() {
+    let trace = load_trace("walking_50hz.csv");
+    let mut counter = StepCounter::new(trace.odr, StepCounterConfig::default());
+    let total: u32 = trace
+        .samples
+        .chunks(32)
+        .map(|batch| counter.update_batch(batch))
+        .sum();
+    assert_eq!(total, trace.steps);
+    assert_eq!(counter.steps(), trace.steps);
+    counter.reset_steps();
+    assert_eq!(counter.steps(), 0);
+}
+
+#[test]
+fn can_set_fifo_mode() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG5_A, BF::FIFO_EN]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::FIFO_CTRL_REG_A, 0b10 << 6 | 25]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG3_A, BF::I1_WTM]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG5_A, 0]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::FIFO_CTRL_REG_A, 0]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG3_A, 0]),
+    ]);
+    sensor.set_fifo_mode(FifoMode::Stream, 25).unwrap();
+    sensor.set_fifo_watermark_interrupt(true).unwrap();
+    sensor.set_fifo_mode(FifoMode::Bypass, 0).unwrap();
+    sensor.set_fifo_watermark_interrupt(false).unwrap();
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn rejects_invalid_fifo_watermark() {
+    let mut sensor = new_i2c(&[]);
+    sensor
+        .set_fifo_mode(FifoMode::Fifo, 32)
+        .expect_err("Should have returned error");
+    destroy_i2c(sensor);
+}
+
+macro_rules! fifo_status_test {
+    ($name:ident, $src:expr, $watermark:expr, $overrun:expr, $empty:expr, $len:expr) => {
+        #[test]
+        fn $name() {
+            let mut sensor = new_i2c(&[I2cTrans::write_read(
+                ACCEL_ADDR,
+                vec![Register::FIFO_SRC_REG_A],
+                vec![$src],
+            )]);
+            assert_eq!(
+                sensor.fifo_status().unwrap(),
+                FifoStatus {
+                    watermark: $watermark,
+                    overrun: $overrun,
+                    empty: $empty,
+                    len: $len,
+                }
+            );
+            destroy_i2c(sensor);
+        }
+    };
+}
+
+fifo_status_test!(fifo_empty, BF::FIFO_EMPTY, false, false, true, 0);
+fifo_status_test!(fifo_partial, 12, false, false, false, 12);
+fifo_status_test!(fifo_watermark, BF::FIFO_WTM | 26, true, false, false, 26);
+fifo_status_test!(
+    fifo_full,
+    BF::FIFO_WTM | BF::OVRN_FIFO | 31,
+    true,
+    true,
+    false,
+    32
+);
+
+#[test]
+fn can_read_fifo_data() {
+    let sample = |x: i16| {
+        [x, 0, 1000]
+            .iter()
+            .flat_map(|v| ((v / 4) << 6).to_le_bytes().to_vec())
+            .collect::<Vec<u8>>()
+    };
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::FIFO_SRC_REG_A], vec![3]),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            [sample(100), sample(200)].concat(),
+        ),
+    ]);
+    sensor.set_accel_odr(ODR::Hz50).unwrap();
+    let mut buffer = [Measurement::default(); 2];
+    assert_eq!(sensor.fifo_data(&mut buffer).unwrap(), 2);
+    assert_eq!(
+        buffer[0],
+        Measurement {
+            x: 100,
+            y: 0,
+            z: 1000
+        }
+    );
+    assert_eq!(
+        buffer[1],
+        Measurement {
+            x: 200,
+            y: 0,
+            z: 1000
+        }
+    );
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn can_update_step_counter_from_fifo() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::FIFO_SRC_REG_A], vec![1]),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            vec![0, 0, 0, 0, 0xA0, 0x3E],
+        ),
+    ]);
+    sensor.set_accel_odr(ODR::Hz50).unwrap();
+    let mut counter = StepCounter::new(ODR::Hz50, StepCounterConfig::default());
+    assert_eq!(sensor.update_step_counter(&mut counter).unwrap(), 0);
+    destroy_i2c(sensor);
+}
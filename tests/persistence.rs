@@ -0,0 +1,159 @@
+use lsm303agr::{
+    AccelCalibration, AccelMode, AccelOutputDataRate, AccelScale, DeviceConfig, MagCalibration,
+    MagOutputDataRate, Settings, SettingsDecodeError,
+};
+
+fn full_settings() -> Settings {
+    Settings {
+        config: DeviceConfig {
+            accel_odr: Some(AccelOutputDataRate::Khz1_344),
+            accel_mode: AccelMode::HighResolution,
+            accel_scale: AccelScale::G8,
+            mag_odr: MagOutputDataRate::Hz50,
+        },
+        accel_calibration: Some(AccelCalibration {
+            offset: [12.5, -3.25, 40.0],
+            scale: [1.01, 0.98, 1.002],
+        }),
+        mag_calibration: Some(MagCalibration {
+            hard_iron: [12000.0, -8000.5, 3000.25],
+            soft_iron: [[1.2, 0.05, 0.0], [0.05, 0.9, 0.02], [0.0, 0.02, 1.05]],
+            field_strength: 48123.5,
+        }),
+    }
+}
+
+#[test]
+fn can_round_trip_full_settings() {
+    let settings = full_settings();
+    let data = settings.encode();
+    assert_eq!(data.len(), Settings::ENCODED_LEN);
+    assert_eq!(&data[..5], b"L3AG\x01");
+    assert_eq!(Settings::decode(&data), Ok(settings));
+}
+
+#[test]
+fn encodes_known_vectors() {
+    // CRC-32 values computed with an independent IEEE 802.3 implementation
+    let data = Settings::default().encode();
+    let mut expected = [0; Settings::ENCODED_LEN];
+    expected[..5].copy_from_slice(b"L3AG\x01");
+    expected[124..].copy_from_slice(&0x1AEF_6EF1_u32.to_le_bytes());
+    assert_eq!(data, expected);
+
+    let settings = Settings {
+        mag_calibration: None,
+        ..full_settings()
+    };
+    let data = settings.encode();
+    assert_eq!(&data[5..12], &[1, 0, 0, 8, 3, 2, 2]);
+    assert_eq!(&data[16..20], &12.5_f32.to_le_bytes());
+    assert_eq!(&data[124..], &0xA590_B545_u32.to_le_bytes());
+    assert_eq!(Settings::decode(&data), Ok(settings));
+}
+
+#[test]
+fn can_round_trip_default_settings() {
+    let settings = Settings::default();
+    assert_eq!(Settings::decode(&settings.encode()), Ok(settings));
+}
+
+#[test]
+fn can_round_trip_all_config_values() {
+    let odrs = [
+        None,
+        Some(AccelOutputDataRate::Hz1),
+        Some(AccelOutputDataRate::Hz400),
+        Some(AccelOutputDataRate::Khz5_376LowPower),
+    ];
+    let modes = [
+        AccelMode::PowerDown,
+        AccelMode::LowPower,
+        AccelMode::Normal,
+        AccelMode::HighResolution,
+    ];
+    let scales = [
+        AccelScale::G2,
+        AccelScale::G4,
+        AccelScale::G8,
+        AccelScale::G16,
+    ];
+    let mag_odrs = [
+        MagOutputDataRate::Hz10,
+        MagOutputDataRate::Hz20,
+        MagOutputDataRate::Hz50,
+        MagOutputDataRate::Hz100,
+    ];
+    for (i, &accel_odr) in odrs.iter().enumerate() {
+        let settings = Settings {
+            config: DeviceConfig {
+                accel_odr,
+                accel_mode: modes[i],
+                accel_scale: scales[i],
+                mag_odr: mag_odrs[i],
+            },
+            ..Settings::default()
+        };
+        assert_eq!(Settings::decode(&settings.encode()), Ok(settings));
+    }
+}
+
+#[test]
+fn missing_calibration_is_encoded_as_zero() {
+    let settings = Settings {
+        accel_calibration: None,
+        ..full_settings()
+    };
+    let data = settings.encode();
+    assert_eq!(data[5], 0b10);
+    assert!(data[16..40].iter().all(|b| *b == 0));
+    assert_eq!(Settings::decode(&data), Ok(settings));
+}
+
+#[test]
+fn rejects_invalid_length() {
+    let data = full_settings().encode();
+    assert_eq!(
+        Settings::decode(&data[..100]),
+        Err(SettingsDecodeError::InvalidLength)
+    );
+}
+
+#[test]
+fn rejects_invalid_magic() {
+    let mut data = full_settings().encode();
+    data[0] = b'X';
+    assert_eq!(
+        Settings::decode(&data),
+        Err(SettingsDecodeError::InvalidMagic)
+    );
+}
+
+#[test]
+fn rejects_unsupported_version() {
+    let mut data = full_settings().encode();
+    data[4] = 2;
+    assert_eq!(
+        Settings::decode(&data),
+        Err(SettingsDecodeError::UnsupportedVersion(2))
+    );
+}
+
+#[test]
+fn detects_corruption() {
+    let mut data = full_settings().encode();
+    data[50] ^= 0x01;
+    assert_eq!(
+        Settings::decode(&data),
+        Err(SettingsDecodeError::InvalidChecksum)
+    );
+}
+
+#[test]
+fn erased_flash_is_rejected() {
+    let data = [0xFF; Settings::ENCODED_LEN];
+    assert_eq!(
+        Settings::decode(&data),
+        Err(SettingsDecodeError::InvalidMagic)
+    );
+}
@@ -103,9 +103,9 @@ mod can_get_i2c {
     can_get_i2c!(low_power_8g,        LowPower,       G8,  Measurement { x: 512 * 4, y: 1024 * 4, z: 1536 * 4});
     can_get_i2c!(high_resolution_8g,  HighResolution, G8,  Measurement { x: 513 * 4, y: 1027 * 4, z: 1541 * 4});
     can_get_i2c!(normal_8g,           Normal,         G8,  Measurement { x: 512 * 4, y: 1024 * 4, z: 1540 * 4});
-    can_get_i2c!(low_power_16g,       LowPower,       G16, Measurement { x: 512 * 8, y: 1024 * 8, z: 1536 * 8});
-    can_get_i2c!(high_resolution_16g, HighResolution, G16, Measurement { x: 513 * 8, y: 1027 * 8, z: 1541 * 8});
-    can_get_i2c!(normal_16g,          Normal,         G16, Measurement { x: 512 * 8, y: 1024 * 8, z: 1540 * 8});
+    can_get_i2c!(low_power_16g,       LowPower,       G16, Measurement { x: 512 * 12, y: 1024 * 12, z: 1536 * 12});
+    can_get_i2c!(high_resolution_16g, HighResolution, G16, Measurement { x: 513 * 12, y: 1027 * 12, z: 1541 * 12});
+    can_get_i2c!(normal_16g,          Normal,         G16, Measurement { x: 512 * 12, y: 1024 * 12, z: 1540 * 12});
 }
 
 macro_rules! measurement_almost_eq {
@@ -0,0 +1,79 @@
+use lsm303agr::{
+    registers::{CfgRegAM, CtrlReg4A, FifoSrcRegA, Int1CfgA},
+    sim::Simulator,
+    AccelOutputDataRate, AccelScale, FifoMode, Lsm303agr, MagOutputDataRate, Measurement,
+    UnscaledMeasurement,
+};
+
+#[test]
+fn decodes_and_encodes_fields() {
+    let reg4 = CtrlReg4A::from(0b1011_0100);
+    assert_eq!(
+        reg4,
+        CtrlReg4A {
+            bdu: true,
+            ble: false,
+            fs: 0b11,
+            hr: false,
+            st: 0b10,
+            spi_enable: false,
+        }
+    );
+    assert_eq!(reg4.bits(), 0b1011_0100);
+    assert_eq!(u8::from(CfgRegAM::from(0xFF)), 0xFF);
+
+    // Reserved bits are dropped.
+    assert_eq!(FifoSrcRegA::from(0xFF).fss, 0x1F);
+    assert_eq!(CtrlReg4A::from(0xFF).bits(), 0xFF);
+    assert_eq!(
+        Int1CfgA {
+            six_d: true,
+            ..Default::default()
+        }
+        .bits(),
+        1 << 6
+    );
+}
+
+#[test]
+fn truncates_wide_field_values() {
+    let cfg = CfgRegAM {
+        odr: 0b111,
+        md: CfgRegAM::MD_SINGLE,
+        ..Default::default()
+    };
+    assert_eq!(cfg.bits(), 0b1101);
+    assert_eq!(CfgRegAM::ADDRESS, 0x60);
+}
+
+#[test]
+fn dumps_configuration_and_data() {
+    let sim = Simulator::new();
+    sim.set_acceleration(Measurement {
+        x: 0,
+        y: 0,
+        z: 1000,
+    });
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.init().unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz100).unwrap();
+    sensor.set_accel_scale(AccelScale::G4).unwrap();
+    sensor.set_fifo_mode(FifoMode::Stream, 10).unwrap();
+    sensor.set_mag_odr(MagOutputDataRate::Hz50).unwrap();
+    sim.advance_us(20_000);
+
+    let dump = sensor.dump_registers().unwrap();
+    assert_eq!(dump.accel.who_am_i_a, 0x33);
+    assert_eq!(dump.mag.who_am_i_m, 0x40);
+    assert_eq!(dump.accel.ctrl_reg1_a.odr, 5);
+    assert_eq!(dump.accel.ctrl_reg4_a.fs, 0b01);
+    assert!(dump.accel.ctrl_reg4_a.bdu);
+    assert!(dump.accel.ctrl_reg5_a.fifo_en);
+    assert_eq!(dump.accel.fifo_ctrl_reg_a.fth, 10);
+    assert_eq!(dump.accel.fifo_ctrl_reg_a.fm, 0b10);
+    assert!(dump.accel.status_reg_a.zyxda);
+    assert_ne!(dump.accel.out_a, UnscaledMeasurement::default());
+    assert_eq!(dump.mag.cfg_reg_a_m.odr, 2);
+    assert_eq!(dump.mag.cfg_reg_a_m.md, CfgRegAM::MD_IDLE);
+    assert!(dump.mag.cfg_reg_c_m.bdu);
+}
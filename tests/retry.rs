@@ -0,0 +1,153 @@
+use embedded_hal_mock::{
+    i2c::{Mock as I2cMock, Transaction as I2cTrans},
+    MockError,
+};
+use lsm303agr::{
+    interface::{RetryPolicy, RetryStats},
+    Error, Lsm303agr,
+};
+use std::io::ErrorKind;
+mod common;
+use crate::common::{RecordingDelay, Register, ACCEL_ADDR, MAG_ADDR};
+
+fn nack(transaction: I2cTrans) -> I2cTrans {
+    transaction.with_error(MockError::Io(ErrorKind::Other))
+}
+
+#[test]
+fn recovers_write_after_nack() {
+    let policy = RetryPolicy {
+        delay_us: Some(100),
+        ..RetryPolicy::default()
+    };
+    let mut sensor = Lsm303agr::new_with_i2c_retry(
+        I2cMock::new(&[
+            nack(I2cTrans::write(
+                ACCEL_ADDR,
+                vec![Register::TEMP_CFG_REG_A, 0xC0],
+            )),
+            I2cTrans::write(ACCEL_ADDR, vec![Register::TEMP_CFG_REG_A, 0xC0]),
+            I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG4_A, 0x80]),
+            nack(I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_C_M, 0x10])),
+            nack(I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_C_M, 0x10])),
+            I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_C_M, 0x10]),
+        ]),
+        policy,
+        RecordingDelay::default(),
+    );
+    sensor.init().unwrap();
+    assert_eq!(
+        sensor.retry_stats(),
+        RetryStats {
+            retries: 3,
+            recovered: 2,
+            failed: 0,
+        }
+    );
+    sensor.reset_retry_stats();
+    assert_eq!(sensor.retry_stats(), RetryStats::default());
+    let (mut i2c, delay) = sensor.destroy();
+    assert_eq!(delay.delays, [100, 100, 100]);
+    i2c.done();
+}
+
+#[test]
+fn fails_after_all_attempts() {
+    let mut sensor = Lsm303agr::new_with_i2c_retry(
+        I2cMock::new(&[
+            nack(I2cTrans::write_read(
+                ACCEL_ADDR,
+                vec![Register::WHO_AM_I_A],
+                vec![0],
+            )),
+            nack(I2cTrans::write_read(
+                ACCEL_ADDR,
+                vec![Register::WHO_AM_I_A],
+                vec![0],
+            )),
+            nack(I2cTrans::write_read(
+                ACCEL_ADDR,
+                vec![Register::WHO_AM_I_A],
+                vec![0],
+            )),
+        ]),
+        RetryPolicy::default(),
+        RecordingDelay::default(),
+    );
+    match sensor.accelerometer_id() {
+        Err(Error::Comm(_)) => (),
+        _ => panic!("expected communication error"),
+    }
+    assert_eq!(
+        sensor.retry_stats(),
+        RetryStats {
+            retries: 2,
+            recovered: 0,
+            failed: 1,
+        }
+    );
+    let (mut i2c, delay) = sensor.destroy();
+    assert!(delay.delays.is_empty());
+    i2c.done();
+}
+
+#[test]
+fn does_not_retry_data_reads_by_default() {
+    let mut sensor = Lsm303agr::new_with_i2c_retry(
+        I2cMock::new(&[nack(I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::OUT_X_L_A | 0x80],
+            vec![0; 6],
+        ))]),
+        RetryPolicy::default(),
+        RecordingDelay::default(),
+    );
+    assert!(sensor.accel_data_unscaled().is_err());
+    assert_eq!(sensor.retry_stats().retries, 0);
+    assert_eq!(sensor.retry_stats().failed, 1);
+    sensor.destroy().0.done();
+}
+
+#[test]
+fn retries_data_reads_if_enabled() {
+    let policy = RetryPolicy {
+        data_reads: true,
+        ..RetryPolicy::default()
+    };
+    let mut sensor = Lsm303agr::new_with_i2c_retry(
+        I2cMock::new(&[
+            nack(I2cTrans::write_read(
+                ACCEL_ADDR,
+                vec![Register::OUT_X_L_A | 0x80],
+                vec![0; 6],
+            )),
+            I2cTrans::write_read(
+                ACCEL_ADDR,
+                vec![Register::OUT_X_L_A | 0x80],
+                vec![0x10, 0x20, 0x30, 0x40, 0x50, 0x60],
+            ),
+        ]),
+        policy,
+        RecordingDelay::default(),
+    );
+    sensor.accel_data_unscaled().unwrap();
+    assert_eq!(sensor.retry_stats().recovered, 1);
+    sensor.destroy().0.done();
+}
+
+#[test]
+fn does_not_retry_interrupt_source_reads_by_default() {
+    let mut sensor = Lsm303agr::new_with_i2c_retry(
+        I2cMock::new(&[nack(I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::INT1_SRC_A],
+            vec![0x40],
+        ))]),
+        RetryPolicy::default(),
+        RecordingDelay::default(),
+    );
+    assert!(sensor.free_fall_detected().is_err());
+    assert_eq!(sensor.retry_stats().retries, 0);
+    assert_eq!(sensor.retry_stats().failed, 1);
+    sensor.destroy().0.done();
+}
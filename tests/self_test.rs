@@ -0,0 +1,152 @@
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTrans};
+use lsm303agr::{
+    sim::Simulator, AccelMode, AccelOutputDataRate, AccelScale, Error, Lsm303agr, Measurement,
+    Sensor,
+};
+mod common;
+use crate::common::{RecordingDelay, Register, ACCEL_ADDR, DEFAULT_CTRL_REG1_A};
+
+#[test]
+fn accel_self_test_passes_and_restores_configuration() {
+    let sim = Simulator::new();
+    sim.set_acceleration(Measurement {
+        x: 0,
+        y: 0,
+        z: 1000,
+    });
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.init().unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz10).unwrap();
+    sensor.set_accel_mode(AccelMode::HighResolution).unwrap();
+    sensor.set_accel_scale(AccelScale::G8).unwrap();
+    let (reg1, reg4) = (sim.accel_register(0x20), sim.accel_register(0x23));
+
+    let result = sensor.accel_self_test(&mut sim.delay()).unwrap();
+    assert!(result.passed);
+    assert_eq!(
+        result.difference,
+        Measurement {
+            x: 400,
+            y: 400,
+            z: 400
+        }
+    );
+    assert_eq!(sim.accel_register(0x20), reg1);
+    assert_eq!(sim.accel_register(0x23), reg4);
+    assert_eq!(sensor.get_accel_mode(), AccelMode::HighResolution);
+    assert_eq!(sensor.get_accel_scale(), AccelScale::G8);
+    assert_eq!(sensor.get_accel_odr(), Some(AccelOutputDataRate::Hz10));
+}
+
+#[test]
+fn accel_self_test_fails_when_output_saturates() {
+    let sim = Simulator::new();
+    // The deflection is clipped at the ±2g limit of the self-test scale.
+    sim.set_acceleration(Measurement {
+        x: 0,
+        y: 0,
+        z: 2000,
+    });
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.init().unwrap();
+    let result = sensor.accel_self_test(&mut sim.delay()).unwrap();
+    assert!(!result.passed);
+    assert_eq!(result.difference.x, 400);
+    assert!(result.difference.z < 68);
+    match result.check::<(), ()>() {
+        Err(Error::SelfTestFailed { sensor, difference }) => {
+            assert_eq!(sensor, Sensor::Accelerometer);
+            assert_eq!(difference, result.difference);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn mag_self_test_passes_and_restores_configuration() {
+    let sim = Simulator::new();
+    sim.set_magnetic_field(Measurement {
+        x: 19_950,
+        y: 0,
+        z: -44_850,
+    });
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.init().unwrap();
+    let cfg = [
+        sim.mag_register(0x60),
+        sim.mag_register(0x61),
+        sim.mag_register(0x62),
+    ];
+    let result = sensor.mag_self_test(&mut sim.delay()).unwrap();
+    assert!(result.passed);
+    assert_eq!(result.sensor, Sensor::Magnetometer);
+    result.check::<(), ()>().unwrap();
+    assert_eq!(
+        result.difference,
+        Measurement {
+            x: 15_000,
+            y: 15_000,
+            z: 15_000
+        }
+    );
+    assert_eq!(
+        [
+            sim.mag_register(0x60),
+            sim.mag_register(0x61),
+            sim.mag_register(0x62)
+        ],
+        cfg
+    );
+    // The magnetometer is back in idle mode and one-shot measurements work.
+    let mut delay = sim.delay();
+    let data = loop {
+        match sensor.mag_data() {
+            Ok(data) => break data,
+            Err(nb::Error::WouldBlock) => delay.delay_us(1_000u32),
+            Err(nb::Error::Other(e)) => panic!("{:?}", e),
+        }
+    };
+    assert_eq!(data.z, -44_850);
+}
+
+#[test]
+fn mag_self_test_runs_in_continuous_mode() {
+    let sim = Simulator::new();
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c())
+        .into_mag_continuous()
+        .ok()
+        .unwrap();
+    sensor.init().unwrap();
+    let cfg_a = sim.mag_register(0x60);
+    assert!(sensor.mag_self_test(&mut sim.delay()).unwrap().passed);
+    assert_eq!(sim.mag_register(0x60), cfg_a);
+}
+
+#[test]
+fn accel_self_test_times_out_without_new_data() {
+    let mut transactions = vec![
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG4_A, 0x80]),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG1_A, 0x57]),
+    ];
+    for _ in 0..81 {
+        transactions.push(I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::STATUS_REG_A],
+            vec![0],
+        ));
+    }
+    transactions.push(I2cTrans::write(
+        ACCEL_ADDR,
+        vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A],
+    ));
+    transactions.push(I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG4_A, 0]));
+    let mut sensor = Lsm303agr::new_with_i2c(I2cMock::new(&transactions));
+    let mut delay = RecordingDelay::default();
+    match sensor.accel_self_test(&mut delay) {
+        Err(Error::Timeout) => (),
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(delay.delays.len(), 81);
+    sensor.destroy().done();
+}
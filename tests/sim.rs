@@ -0,0 +1,351 @@
+use embedded_hal::blocking::i2c::Write;
+use lsm303agr::{
+    sim::{SimError, Simulator},
+    AccelMode, AccelOutputDataRate, AccelScale, AutoRanger, FifoMode, Lsm303agr, MagOutputDataRate,
+    Measurement, Sensor, StreamEvent, UnscaledMeasurement,
+};
+
+const ACCEL: Measurement = Measurement {
+    x: 123,
+    y: -456,
+    z: 1000,
+};
+
+const FIELD: Measurement = Measurement {
+    x: 30_000,
+    y: -4_500,
+    z: -45_000,
+};
+
+#[test]
+fn identifies_over_i2c() {
+    let sim = Simulator::new();
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    assert!(sensor.accelerometer_is_detected().unwrap());
+    assert!(sensor.magnetometer_is_detected().unwrap());
+}
+
+#[test]
+fn identifies_and_reads_over_spi() {
+    let sim = Simulator::new();
+    sim.set_acceleration(ACCEL);
+    sim.set_magnetic_field(FIELD);
+    let (spi, accel_cs, mag_cs) = sim.spi();
+    let mut sensor = Lsm303agr::new_with_spi(spi, accel_cs, mag_cs);
+    assert!(sensor.accelerometer_is_detected().unwrap());
+    assert!(sensor.magnetometer_is_detected().unwrap());
+    sensor.init().unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz100).unwrap();
+    sensor.set_accel_mode(AccelMode::HighResolution).unwrap();
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    sim.advance_us(100_000);
+    assert_eq!(sensor.accel_data().unwrap(), ACCEL);
+    assert_eq!(sensor.mag_data().unwrap(), FIELD);
+}
+
+#[test]
+fn reset_values_are_kept_in_register_file() {
+    let sim = Simulator::new();
+    assert_eq!(sim.accel_register(0x20), 0x07);
+    assert_eq!(sim.mag_register(0x60), 0x03);
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.init().unwrap();
+    sensor.set_accel_scale(AccelScale::G8).unwrap();
+    assert_eq!(sim.accel_register(0x23), 0xA0);
+    sim.reset();
+    assert_eq!(sim.accel_register(0x23), 0);
+}
+
+#[test]
+fn quantizes_acceleration_to_mode_and_scale() {
+    let cases = [
+        (AccelMode::HighResolution, AccelScale::G2, ACCEL),
+        (
+            AccelMode::Normal,
+            AccelScale::G2,
+            Measurement {
+                x: 124,
+                y: -456,
+                z: 1000,
+            },
+        ),
+        (
+            AccelMode::Normal,
+            AccelScale::G16,
+            Measurement {
+                x: 144,
+                y: -480,
+                z: 1008,
+            },
+        ),
+        (
+            AccelMode::LowPower,
+            AccelScale::G4,
+            Measurement {
+                x: 128,
+                y: -448,
+                z: 992,
+            },
+        ),
+    ];
+    for (mode, scale, expected) in cases.iter() {
+        let sim = Simulator::new();
+        sim.set_acceleration(ACCEL);
+        let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+        sensor.init().unwrap();
+        sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+        sensor.set_accel_mode(*mode).unwrap();
+        sensor.set_accel_scale(*scale).unwrap();
+        sim.advance_us(20_000);
+        assert_eq!(sensor.accel_data().unwrap(), *expected, "{:?}", mode);
+    }
+}
+
+#[test]
+fn clips_at_full_scale() {
+    let sim = Simulator::new();
+    sim.set_acceleration(Measurement {
+        x: 3000,
+        y: -3000,
+        z: 0,
+    });
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    sim.advance_us(20_000);
+    assert_eq!(
+        sensor.accel_data_unscaled().unwrap(),
+        UnscaledMeasurement {
+            x: 511,
+            y: -512,
+            z: 0
+        }
+    );
+}
+
+#[test]
+fn reports_new_data_and_overruns() {
+    let sim = Simulator::new();
+    sim.set_byte_time_ns(0);
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.set_accel_odr(AccelOutputDataRate::Hz10).unwrap();
+    assert!(!sensor.accel_status().unwrap().xyz_new_data);
+    sim.advance_us(99_999);
+    assert!(!sensor.accel_status().unwrap().xyz_new_data);
+    sim.advance_us(1);
+    let status = sensor.accel_status().unwrap();
+    assert!(status.xyz_new_data && !status.xyz_overrun);
+    sim.advance_us(100_000);
+    let status = sensor.accel_status().unwrap();
+    assert!(status.xyz_new_data && status.xyz_overrun);
+    sensor.accel_data().unwrap();
+    let status = sensor.accel_status().unwrap();
+    assert!(!status.xyz_new_data && !status.xyz_overrun);
+}
+
+#[test]
+fn bus_transfers_take_time() {
+    let sim = Simulator::new();
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.set_accel_odr(AccelOutputDataRate::Hz400).unwrap();
+    let mut polls = 0;
+    while !sensor.accel_status().unwrap().xyz_new_data {
+        polls += 1;
+    }
+    // 2.5 ms at 4 bytes of 22.5 µs per status read
+    assert_eq!(polls, 28);
+}
+
+#[test]
+fn takes_one_shot_mag_measurements() {
+    let sim = Simulator::new();
+    sim.set_magnetic_field(FIELD);
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.set_mag_odr(MagOutputDataRate::Hz100).unwrap();
+    let data = nb::block!(sensor.mag_data()).unwrap();
+    assert_eq!(data, FIELD);
+    assert!(sim.now_us() >= 10_000);
+    // The magnetometer returns to idle mode.
+    sim.advance_us(10_000);
+    assert_eq!(sim.mag_register(0x60) & 0b11, 0b11);
+}
+
+#[test]
+fn applies_mag_offset() {
+    let sim = Simulator::new();
+    sim.set_magnetic_field(FIELD);
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor
+        .set_mag_offset(UnscaledMeasurement {
+            x: 100,
+            y: -100,
+            z: 0,
+        })
+        .unwrap();
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    sim.advance_us(100_000);
+    assert_eq!(
+        sensor.mag_data().unwrap(),
+        Measurement {
+            x: 15_000,
+            y: 10_500,
+            z: -45_000
+        }
+    );
+}
+
+#[test]
+fn measures_temperature() {
+    let sim = Simulator::new();
+    sim.set_temperature(30.6);
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.init().unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz10).unwrap();
+    assert!(!sensor.temperature_status().unwrap().new_data);
+    sim.advance_us(100_000);
+    assert!(sensor.temperature_status().unwrap().new_data);
+    // 10-bit resolution in normal mode
+    assert_eq!(sensor.temperature_celsius().unwrap(), 30.5);
+    assert!(!sensor.temperature_status().unwrap().new_data);
+    sensor.set_accel_mode(AccelMode::HighResolution).unwrap();
+    sim.advance_us(100_000);
+    assert_eq!(sensor.temperature_celsius().unwrap(), 30.5);
+}
+
+#[test]
+fn measures_temperature_with_8_bits_in_low_power_mode() {
+    let sim = Simulator::new();
+    sim.set_temperature(30.6);
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.init().unwrap();
+    sensor.set_accel_mode(AccelMode::LowPower).unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz10).unwrap();
+    sim.advance_us(100_000);
+    assert_eq!(sensor.temperature_celsius().unwrap(), 30.0);
+}
+
+#[test]
+fn collects_samples_in_fifo() {
+    let sim = Simulator::new();
+    sim.set_acceleration(ACCEL);
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.set_accel_odr(AccelOutputDataRate::Hz100).unwrap();
+    sensor.set_fifo_mode(FifoMode::Fifo, 10).unwrap();
+    sim.advance_us(100_000);
+    let status = sensor.fifo_status().unwrap();
+    assert_eq!(status.len, 10);
+    assert!(!status.watermark);
+    sim.advance_us(500_000);
+    let status = sensor.fifo_status().unwrap();
+    assert_eq!(status.len, 32);
+    assert!(status.overrun && status.watermark);
+    let mut buffer = [Measurement::default(); 40];
+    assert_eq!(sensor.fifo_data(&mut buffer).unwrap(), 32);
+    assert!(buffer[..32].iter().all(|m| *m
+        == Measurement {
+            x: 124,
+            y: -456,
+            z: 1000
+        }));
+    assert!(sensor.fifo_status().unwrap().empty);
+}
+
+#[test]
+fn streams_samples_with_simulated_delay() {
+    let sim = Simulator::new();
+    sim.set_acceleration(ACCEL);
+    sim.set_magnetic_field(FIELD);
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    sensor.set_accel_mode(AccelMode::HighResolution).unwrap();
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    let mut delay = sim.delay();
+    let events: Vec<StreamEvent> = sensor
+        .sample_stream(&mut delay)
+        .unwrap()
+        .take(12)
+        .map(Result::unwrap)
+        .collect();
+    let accel = events
+        .iter()
+        .filter(|e| {
+            matches!(
+                e,
+                StreamEvent::Sample {
+                    sensor: Sensor::Accelerometer,
+                    ..
+                }
+            )
+        })
+        .count();
+    assert_eq!(accel, 10);
+    assert!(events.iter().all(|e| match e {
+        StreamEvent::Sample {
+            sensor: Sensor::Accelerometer,
+            data,
+            ..
+        } => *data == ACCEL,
+        StreamEvent::Sample {
+            sensor: Sensor::Magnetometer,
+            data,
+            ..
+        } => *data == FIELD,
+        StreamEvent::Dropped { .. } => false,
+    }));
+    assert_eq!(sensor.overrun_counters().accel, 0);
+}
+
+#[test]
+fn averages_with_simulated_delay() {
+    let sim = Simulator::new();
+    sim.set_acceleration(ACCEL);
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.set_accel_odr(AccelOutputDataRate::Hz100).unwrap();
+    sensor.set_accel_mode(AccelMode::HighResolution).unwrap();
+    let start = sim.now_us();
+    let average = sensor.accel_data_averaged(8, &mut sim.delay()).unwrap();
+    assert_eq!(average.samples, 8);
+    assert_eq!(average.x.mean, 123.0);
+    assert_eq!(average.z.std_dev, 0.0);
+    assert!(sim.now_us() - start >= 80_000);
+}
+
+#[test]
+fn auto_ranges_on_scale_change_mid_stream() {
+    let sim = Simulator::new();
+    sim.set_acceleration(ACCEL);
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.set_accel_odr(AccelOutputDataRate::Hz100).unwrap();
+    let mut ranger = AutoRanger::default();
+    sim.advance_us(10_000);
+    let reading = sensor.accel_data_auto_ranged(&mut ranger).unwrap();
+    assert_eq!(reading.scale, AccelScale::G2);
+    let shock = Measurement {
+        x: 0,
+        y: 0,
+        z: 3000,
+    };
+    sim.set_acceleration(shock);
+    sim.advance_us(10_000);
+    let reading = sensor.accel_data_auto_ranged(&mut ranger).unwrap();
+    assert_eq!(reading.scale, AccelScale::G2);
+    assert!(reading.clipped);
+    assert_eq!(sensor.get_accel_scale(), AccelScale::G4);
+    // The sample taken at ±2g is not reported as a ±4g one.
+    match sensor.accel_data_auto_ranged(&mut ranger) {
+        Err(nb::Error::WouldBlock) => (),
+        _ => panic!("WouldBlock not returned."),
+    }
+    sim.advance_us(10_000);
+    let reading = sensor.accel_data_auto_ranged(&mut ranger).unwrap();
+    assert_eq!(reading.data, shock);
+    assert_eq!(reading.scale, AccelScale::G4);
+    assert!(!reading.clipped);
+}
+
+#[test]
+fn unknown_address_is_not_acknowledged() {
+    let sim = Simulator::new();
+    let mut i2c = sim.i2c();
+    assert_eq!(i2c.write(0x20, &[0x0F]), Err(SimError::Nack));
+    assert_eq!(i2c.write(0x19, &[]), Err(SimError::EmptyTransfer));
+}
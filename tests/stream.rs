@@ -0,0 +1,178 @@
+mod common;
+use crate::common::{
+    destroy_i2c, new_i2c, BitFlags as BF, RecordingDelay, Register, ACCEL_ADDR,
+    DEFAULT_CTRL_REG1_A, HZ50, MAG_ADDR,
+};
+use embedded_hal_mock::{i2c::Transaction as I2cTrans, MockError};
+use lsm303agr::{AccelOutputDataRate, Measurement, Sensor, StreamEvent};
+use std::io::ErrorKind;
+
+fn accel_sample(x: i16) -> I2cTrans {
+    I2cTrans::write_read(
+        ACCEL_ADDR,
+        vec![Register::OUT_X_L_A | 0x80],
+        [x, 0, 1000]
+            .iter()
+            .flat_map(|v| ((v / 4) << 6).to_le_bytes().to_vec())
+            .collect(),
+    )
+}
+
+fn accel_status(status: u8) -> I2cTrans {
+    I2cTrans::write_read(ACCEL_ADDR, vec![Register::STATUS_REG_A], vec![status])
+}
+
+fn sample(sensor: Sensor, sequence: u32, data: Measurement) -> StreamEvent {
+    StreamEvent::Sample {
+        sensor,
+        sequence,
+        data,
+    }
+}
+
+fn accel(x: i32) -> Measurement {
+    Measurement { x, y: 0, z: 1000 }
+}
+
+#[test]
+fn accel_stream_requires_odr() {
+    let mut sensor = new_i2c(&[]);
+    let mut delay = RecordingDelay::default();
+    assert!(sensor.accel_stream(&mut delay).is_err());
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn streams_accel_samples_paced_by_odr() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        accel_status(BF::XYZDR),
+        accel_sample(100),
+        accel_status(0),
+        accel_status(BF::XYZDR),
+        accel_sample(200),
+        accel_status(BF::XYZDR | BF::XYZOR),
+        accel_sample(300),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut delay = RecordingDelay::default();
+    let events: Vec<StreamEvent> = sensor
+        .accel_stream(&mut delay)
+        .unwrap()
+        .take(4)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        events,
+        [
+            sample(Sensor::Accelerometer, 0, accel(100)),
+            sample(Sensor::Accelerometer, 1, accel(200)),
+            StreamEvent::Dropped {
+                sensor: Sensor::Accelerometer,
+                sequence: 2
+            },
+            sample(Sensor::Accelerometer, 2, accel(300)),
+        ]
+    );
+    // One period, retry after an eighth of a period, one period
+    assert_eq!(delay.delays, [20_000, 2_500, 20_000]);
+    assert_eq!(sensor.overrun_counters().accel, 1);
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn stream_resumes_after_errors() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        accel_status(BF::XYZDR).with_error(MockError::Io(ErrorKind::Other)),
+        accel_status(BF::XYZDR),
+        accel_sample(100),
+    ]);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut delay = RecordingDelay::default();
+    let mut stream = sensor.accel_stream(&mut delay).unwrap();
+    assert!(stream.next().unwrap().is_err());
+    assert_eq!(
+        stream.next().unwrap().unwrap(),
+        sample(Sensor::Accelerometer, 0, accel(100))
+    );
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn interleaves_accel_and_mag_samples() {
+    let mag_data = vec![0x10, 0x00, 0x20, 0x00, 0x30, 0x00];
+    let mag = Measurement {
+        x: 0x10 * 150,
+        y: 0x20 * 150,
+        z: 0x30 * 150,
+    };
+    let sensor = new_i2c(&[
+        I2cTrans::write(MAG_ADDR, vec![Register::CFG_REG_A_M, 0]),
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, DEFAULT_CTRL_REG1_A | HZ50],
+        ),
+        // t = 0 ms
+        accel_status(BF::XYZDR),
+        accel_sample(100),
+        I2cTrans::write_read(MAG_ADDR, vec![Register::STATUS_REG_M], vec![BF::XYZDR]),
+        I2cTrans::write_read(
+            MAG_ADDR,
+            vec![Register::OUTX_L_REG_M | 0x80],
+            mag_data.clone(),
+        ),
+        // t = 20, 40, 60, 80 ms
+        accel_status(BF::XYZDR),
+        accel_sample(200),
+        accel_status(BF::XYZDR),
+        accel_sample(300),
+        accel_status(BF::XYZDR),
+        accel_sample(400),
+        accel_status(BF::XYZDR),
+        accel_sample(500),
+        // t = 100 ms
+        accel_status(BF::XYZDR),
+        accel_sample(600),
+        I2cTrans::write_read(
+            MAG_ADDR,
+            vec![Register::STATUS_REG_M],
+            vec![BF::XYZDR | BF::XYZOR],
+        ),
+        I2cTrans::write_read(MAG_ADDR, vec![Register::OUTX_L_REG_M | 0x80], mag_data),
+    ]);
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    let mut delay = RecordingDelay::default();
+    let events: Vec<StreamEvent> = sensor
+        .sample_stream(&mut delay)
+        .unwrap()
+        .take(9)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        events,
+        [
+            sample(Sensor::Accelerometer, 0, accel(100)),
+            sample(Sensor::Magnetometer, 0, mag),
+            sample(Sensor::Accelerometer, 1, accel(200)),
+            sample(Sensor::Accelerometer, 2, accel(300)),
+            sample(Sensor::Accelerometer, 3, accel(400)),
+            sample(Sensor::Accelerometer, 4, accel(500)),
+            sample(Sensor::Accelerometer, 5, accel(600)),
+            StreamEvent::Dropped {
+                sensor: Sensor::Magnetometer,
+                sequence: 1
+            },
+            sample(Sensor::Magnetometer, 1, mag),
+        ]
+    );
+    assert_eq!(delay.delays, [20_000; 5]);
+    destroy_i2c(sensor);
+}
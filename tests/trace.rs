@@ -0,0 +1,284 @@
+use embedded_hal_mock::{
+    i2c::{Mock as I2cMock, Transaction as I2cTrans},
+    MockError,
+};
+use lsm303agr::{
+    interface::ReadData,
+    sim::Simulator,
+    trace::{Access, ReplayError, Replayer},
+    AccelMode, AccelOutputDataRate, Error, FifoMode, Lsm303agr, MagOutputDataRate, Measurement,
+    Sensor,
+};
+use std::io::ErrorKind;
+mod common;
+use crate::common::{Register, ACCEL_ADDR};
+
+const ACCEL: Measurement = Measurement {
+    x: 120,
+    y: -480,
+    z: 1000,
+};
+
+const FIELD: Measurement = Measurement {
+    x: 30_000,
+    y: -4_500,
+    z: -45_000,
+};
+
+fn replay_error<T: core::fmt::Debug>(result: Result<T, Error<ReplayError, ()>>) -> ReplayError {
+    match result {
+        Err(Error::Comm(e)) => e,
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+fn counter() -> impl FnMut() -> u64 {
+    let mut now = 0;
+    move || {
+        now += 10;
+        now
+    }
+}
+
+#[test]
+fn records_in_line_format() {
+    let sim = Simulator::new();
+    let mut trace = String::new();
+    let mut sensor = Lsm303agr::new_with_i2c_recorder(sim.i2c(), &mut trace, counter());
+    assert!(sensor.accelerometer_is_detected().unwrap());
+    sensor.init().unwrap();
+    sensor.destroy();
+    assert_eq!(
+        trace,
+        "10 A R 0f 33\n\
+         20 A W 1f c0\n\
+         30 A W 23 80\n\
+         40 M W 62 10\n"
+    );
+}
+
+#[test]
+fn records_clock_timestamps() {
+    let sim = Simulator::new();
+    let mut trace = String::new();
+    let mut sensor = Lsm303agr::new_with_i2c_recorder(sim.i2c(), &mut trace, || sim.now_us());
+    sensor.init().unwrap();
+    sim.advance_us(1_000_000);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz10).unwrap();
+    sensor.destroy();
+    let last = trace.lines().last().unwrap();
+    let timestamp: u64 = last.split(' ').next().unwrap().parse().unwrap();
+    assert!(timestamp > 1_000_000);
+    assert!(last.ends_with(" A W 20 27"));
+}
+
+#[test]
+fn records_failed_transactions() {
+    let i2c = I2cMock::new(&[
+        I2cTrans::write_read(ACCEL_ADDR, vec![Register::WHO_AM_I_A], vec![0x33])
+            .with_error(MockError::Io(ErrorKind::Other)),
+        I2cTrans::write(ACCEL_ADDR, vec![Register::CTRL_REG1_A, 0x27])
+            .with_error(MockError::Io(ErrorKind::Other)),
+    ]);
+    let mut trace = String::new();
+    let mut sensor = Lsm303agr::new_with_i2c_recorder(i2c, &mut trace, counter());
+    assert!(sensor.accelerometer_is_detected().is_err());
+    assert!(sensor.set_accel_odr(AccelOutputDataRate::Hz10).is_err());
+    let (mut i2c, _) = sensor.destroy();
+    i2c.done();
+    assert_eq!(trace, "10 A R 0f !\n20 A W 20 27 !\n");
+
+    let mut sensor = Lsm303agr::new_with_replayer(Replayer::new(&trace));
+    assert_eq!(
+        replay_error(sensor.accelerometer_is_detected()),
+        ReplayError::Failed { line: 1 }
+    );
+    assert_eq!(
+        replay_error(sensor.set_accel_odr(AccelOutputDataRate::Hz10)),
+        ReplayError::Failed { line: 2 }
+    );
+}
+
+#[test]
+fn records_over_spi() {
+    let sim = Simulator::new();
+    let (spi, accel_cs, mag_cs) = sim.spi();
+    let mut trace = String::new();
+    let mut sensor = Lsm303agr::new_with_spi_recorder(spi, accel_cs, mag_cs, &mut trace, counter());
+    assert!(sensor.magnetometer_is_detected().unwrap());
+    let _ = sensor.destroy();
+    assert_eq!(trace, "10 M R 4f 40\n");
+}
+
+#[test]
+fn replays_recorded_session() {
+    let sim = Simulator::new();
+    sim.set_acceleration(ACCEL);
+    sim.set_magnetic_field(FIELD);
+    let mut trace = String::new();
+    let mut sensor = Lsm303agr::new_with_i2c_recorder(sim.i2c(), &mut trace, || sim.now_us());
+    sensor.init().unwrap();
+    sensor.set_accel_mode(AccelMode::HighResolution).unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz100).unwrap();
+    sensor.set_mag_odr(MagOutputDataRate::Hz100).unwrap();
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    sim.advance_us(20_000);
+    let accel = sensor.accel_data().unwrap();
+    let mag = sensor.mag_data().unwrap();
+    let temperature = sensor.temperature_data().unwrap();
+    sensor.destroy();
+    assert_eq!(accel, ACCEL);
+    assert_eq!(mag, FIELD);
+
+    let mut sensor = Lsm303agr::new_with_replayer(Replayer::new(&trace));
+    sensor.init().unwrap();
+    sensor.set_accel_mode(AccelMode::HighResolution).unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz100).unwrap();
+    sensor.set_mag_odr(MagOutputDataRate::Hz100).unwrap();
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    assert_eq!(sensor.accel_data().unwrap(), accel);
+    assert_eq!(sensor.mag_data().unwrap(), mag);
+    assert_eq!(sensor.temperature_data().unwrap(), temperature);
+    let replayer = sensor.destroy();
+    assert!(replayer.is_finished());
+    assert_eq!(replayer.line(), trace.lines().count());
+    assert!(replayer.timestamp_us().unwrap() >= 20_000);
+}
+
+#[test]
+fn replays_fifo_burst_read() {
+    let sim = Simulator::new();
+    sim.set_acceleration(ACCEL);
+    let (spi, accel_cs, mag_cs) = sim.spi();
+    let mut trace = String::new();
+    let mut sensor = Lsm303agr::new_with_spi_recorder(spi, accel_cs, mag_cs, &mut trace, counter());
+    sensor.set_accel_odr(AccelOutputDataRate::Hz100).unwrap();
+    sensor.set_fifo_mode(FifoMode::Fifo, 0).unwrap();
+    sim.advance_us(30_000);
+    let mut samples = [Measurement::default(); 3];
+    assert_eq!(sensor.fifo_data(&mut samples).unwrap(), 3);
+    let _ = sensor.destroy();
+    assert_eq!(samples, [ACCEL; 3]);
+    let burst = trace.lines().last().unwrap();
+    assert!(burst.contains(" A R 28 "));
+    assert_eq!(burst.split(' ').count(), 4 + 18);
+
+    let mut sensor = Lsm303agr::new_with_replayer(Replayer::new(&trace));
+    sensor.set_accel_odr(AccelOutputDataRate::Hz100).unwrap();
+    sensor.set_fifo_mode(FifoMode::Fifo, 0).unwrap();
+    let mut replayed = [Measurement::default(); 3];
+    assert_eq!(sensor.fifo_data(&mut replayed).unwrap(), 3);
+    assert_eq!(replayed, samples);
+    assert!(sensor.destroy().is_finished());
+}
+
+#[test]
+fn rejects_replaying_reads_longer_than_fifo() {
+    let trace = "10 A R 28 00\n";
+    let mut replayer = Replayer::new(trace);
+    match replayer.read_accel_registers(Register::OUT_X_L_A, &mut [0; 193]) {
+        Err(Error::InvalidInputData) => (),
+        _ => panic!("InvalidInputData error not returned."),
+    }
+    assert!(!replayer.is_finished());
+}
+
+#[test]
+fn reports_mismatching_write() {
+    let trace = "10 A W 1f c0\n20 A W 23 80\n30 M W 62 10\n40 A W 20 57\n";
+    let mut sensor = Lsm303agr::new_with_replayer(Replayer::new(trace));
+    sensor.init().unwrap();
+    assert_eq!(
+        replay_error(sensor.set_accel_odr(AccelOutputDataRate::Hz50)),
+        ReplayError::Mismatch {
+            line: 4,
+            expected: Access::Write {
+                sensor: Sensor::Accelerometer,
+                register: Register::CTRL_REG1_A,
+                value: 0x57,
+            },
+            actual: Access::Write {
+                sensor: Sensor::Accelerometer,
+                register: Register::CTRL_REG1_A,
+                value: 0x47,
+            },
+        }
+    );
+}
+
+#[test]
+fn reports_mismatching_read() {
+    let trace = "10 A R 0f 33\n";
+    let mut sensor = Lsm303agr::new_with_replayer(Replayer::new(trace));
+    assert_eq!(
+        replay_error(sensor.magnetometer_is_detected()),
+        ReplayError::Mismatch {
+            line: 1,
+            expected: Access::Read {
+                sensor: Sensor::Accelerometer,
+                register: Register::WHO_AM_I_A,
+                len: 1,
+            },
+            actual: Access::Read {
+                sensor: Sensor::Magnetometer,
+                register: Register::WHO_AM_I_M,
+                len: 1,
+            },
+        }
+    );
+}
+
+#[test]
+fn reports_end_of_trace() {
+    let trace = "10 A R 0f 33\n";
+    let mut sensor = Lsm303agr::new_with_replayer(Replayer::new(trace));
+    assert!(sensor.accelerometer_is_detected().unwrap());
+    assert!(sensor.destroy().is_finished());
+    let mut sensor = Lsm303agr::new_with_replayer(Replayer::new(trace));
+    assert!(sensor.accelerometer_is_detected().unwrap());
+    assert_eq!(
+        replay_error(sensor.magnetometer_is_detected()),
+        ReplayError::EndOfTrace {
+            actual: Access::Read {
+                sensor: Sensor::Magnetometer,
+                register: Register::WHO_AM_I_M,
+                len: 1,
+            },
+        }
+    );
+}
+
+#[test]
+fn skips_comments_and_empty_lines() {
+    let trace = "# startup\n\n10 A R 0f 33\n  # done\n";
+    let replayer = Replayer::new(trace);
+    assert!(!replayer.is_finished());
+    let mut sensor = Lsm303agr::new_with_replayer(replayer);
+    assert!(sensor.accelerometer_is_detected().unwrap());
+    let replayer = sensor.destroy();
+    assert_eq!(replayer.line(), 3);
+    assert_eq!(replayer.timestamp_us(), Some(10));
+    assert!(replayer.is_finished());
+}
+
+#[test]
+fn reports_malformed_lines() {
+    let too_long = format!("10 A R 0f{}\n", " 33".repeat(193));
+    for trace in [
+        "10 A R 0f 333\n",
+        "10 X R 0f 33\n",
+        "A R 0f 33\n",
+        "10 A W 20\n",
+        "10 A R 0f\n",
+        &too_long,
+        "10 A R 0f ! 33\n",
+    ] {
+        let mut sensor = Lsm303agr::new_with_replayer(Replayer::new(trace));
+        assert_eq!(
+            replay_error(sensor.accelerometer_is_detected()),
+            ReplayError::Malformed { line: 1 },
+            "{}",
+            trace
+        );
+    }
+}
@@ -0,0 +1,94 @@
+mod common;
+use crate::common::{destroy_i2c, new_i2c, Register, ACCEL_ADDR, DEFAULT_CTRL_REG1_A, HZ50};
+use embedded_hal::blocking::i2c::Write;
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use lsm303agr::{
+    sim::Simulator, AccelMode, AccelOutputDataRate, Error, Lsm303agr, MagOutputDataRate,
+    RegisterDrift, Sensor,
+};
+
+#[test]
+fn verifies_writes() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, HZ50 | DEFAULT_CTRL_REG1_A],
+        ),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A],
+            vec![HZ50 | DEFAULT_CTRL_REG1_A],
+        ),
+    ]);
+    sensor.set_write_verification(true);
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn reports_write_mismatch() {
+    let mut sensor = new_i2c(&[
+        I2cTrans::write(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A, HZ50 | DEFAULT_CTRL_REG1_A],
+        ),
+        I2cTrans::write_read(
+            ACCEL_ADDR,
+            vec![Register::CTRL_REG1_A],
+            vec![DEFAULT_CTRL_REG1_A],
+        ),
+    ]);
+    sensor.set_write_verification(true);
+    match sensor.set_accel_odr(AccelOutputDataRate::Hz50) {
+        Err(Error::WriteMismatch {
+            register: Register::CTRL_REG1_A,
+            written,
+            read: DEFAULT_CTRL_REG1_A,
+        }) => assert_eq!(written, HZ50 | DEFAULT_CTRL_REG1_A),
+        other => panic!("unexpected result {:?}", other),
+    }
+    destroy_i2c(sensor);
+}
+
+#[test]
+fn verifies_writes_on_simulator() {
+    let sim = Simulator::new();
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.set_write_verification(true);
+    sensor.init().unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz100).unwrap();
+    sensor.set_accel_mode(AccelMode::HighResolution).unwrap();
+    sensor.set_mag_odr(MagOutputDataRate::Hz50).unwrap();
+    // Starting a one-shot measurement returns to idle mode on its own.
+    assert!(sensor.mag_data().is_err());
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    sensor.set_mag_odr(MagOutputDataRate::Hz100).unwrap();
+}
+
+#[test]
+fn detects_and_restores_drift() {
+    let sim = Simulator::new();
+    let mut sensor = Lsm303agr::new_with_i2c(sim.i2c());
+    sensor.init().unwrap();
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
+    assert!(sensor.check_config(false).unwrap().is_empty());
+
+    let reg1 = sim.accel_register(Register::CTRL_REG1_A);
+    sim.i2c()
+        .write(ACCEL_ADDR, &[Register::CTRL_REG1_A, reg1 ^ 0x10])
+        .unwrap();
+    let drift = sensor.check_config(false).unwrap();
+    assert_eq!(drift.len(), 1);
+    assert_eq!(
+        drift.iter().next(),
+        Some(&RegisterDrift {
+            sensor: Sensor::Accelerometer,
+            register: Register::CTRL_REG1_A,
+            expected: reg1,
+            actual: reg1 ^ 0x10,
+        })
+    );
+    assert_eq!(sensor.check_config(true).unwrap(), drift);
+    assert_eq!(sim.accel_register(Register::CTRL_REG1_A), reg1);
+    assert!(sensor.check_config(false).unwrap().is_empty());
+}
@@ -0,0 +1,86 @@
+use lsm303agr::{AccelOutputDataRate as ODR, Measurement, VibrationAnalysis, VIBRATION_BANDS};
+
+/// 200 samples at 400 Hz: 50 Hz with 1000 mg amplitude on X, 130 Hz with
+/// 300 mg amplitude on Y and gravity on Z.
+fn motor_block() -> Vec<Measurement> {
+    (0..200)
+        .map(|i| {
+            let t = i as f64 / 400.0;
+            let tau = 2.0 * std::f64::consts::PI;
+            Measurement {
+                x: (1000.0 * (tau * 50.0 * t).sin()).round() as i32,
+                y: (300.0 * (tau * 130.0 * t).sin()).round() as i32,
+                z: 1000,
+            }
+        })
+        .collect()
+}
+
+fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{} is not within {} of {}",
+        actual,
+        tolerance,
+        expected
+    );
+}
+
+#[test]
+fn needs_at_least_two_samples() {
+    assert_eq!(VibrationAnalysis::analyze(&[], ODR::Hz400), None);
+    assert_eq!(
+        VibrationAnalysis::analyze(&[Measurement::default()], ODR::Hz400),
+        None
+    );
+}
+
+#[test]
+fn computes_axis_statistics() {
+    let analysis = VibrationAnalysis::analyze(&motor_block(), ODR::Hz400).unwrap();
+    assert_close(analysis.x.rms, 707.1, 0.5);
+    assert_close(analysis.x.peak, 1000.0, 0.5);
+    assert_close(analysis.x.peak_to_peak, 2000.0, 1.0);
+    assert_close(analysis.x.crest_factor, 1.414, 0.002);
+    assert_close(analysis.y.rms, 212.1, 0.5);
+    assert_eq!(analysis.z.rms, 0.0);
+    assert_eq!(analysis.z.peak_to_peak, 0.0);
+    assert_eq!(analysis.z.crest_factor, 0.0);
+}
+
+#[test]
+fn computes_band_levels() {
+    let analysis = VibrationAnalysis::analyze(&motor_block(), ODR::Hz400).unwrap();
+    assert_eq!(analysis.resolution_hz, 2.0);
+    assert_eq!(analysis.dominant_hz, 50.0);
+    for (i, band) in analysis.bands.iter().enumerate() {
+        assert_eq!(band.low_hz, 25.0 * i as f32);
+        assert_eq!(band.high_hz, 25.0 * (i + 1) as f32);
+        let expected_x = if i == 2 { 707.1 } else { 0.0 };
+        let expected_y = if i == 5 { 212.1 } else { 0.0 };
+        assert_close(band.rms[0], expected_x, 1.0);
+        assert_close(band.rms[1], expected_y, 1.0);
+        assert_close(band.rms[2], 0.0, 0.01);
+    }
+}
+
+#[test]
+fn band_levels_add_up_to_rms() {
+    // Square wave with odd harmonics spread over several bands
+    let block: Vec<Measurement> = (0..256)
+        .map(|i| Measurement {
+            x: if (i / 8) % 2 == 0 { 500 } else { -500 },
+            y: (i % 7) * 40,
+            z: 980,
+        })
+        .collect();
+    let analysis = VibrationAnalysis::analyze(&block, ODR::Khz1_344).unwrap();
+    assert_eq!(analysis.bands.len(), VIBRATION_BANDS);
+    assert_close(analysis.x.crest_factor, 1.0, 0.001);
+    // 1344 Hz / 16 samples per period
+    assert_close(analysis.dominant_hz, 84.0, 0.01);
+    for (axis, stats) in [analysis.x, analysis.y].iter().enumerate() {
+        let sum: f32 = analysis.bands.iter().map(|b| b.rms[axis].powi(2)).sum();
+        assert_close(sum.sqrt(), stats.rms, stats.rms * 0.001);
+    }
+}